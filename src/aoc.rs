@@ -1,31 +1,45 @@
-use std::{collections::HashMap, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
 
 use chrono::{Datelike, Utc};
-use log::{error, info, warn};
 use mongodb::{
     bson::{doc, to_bson},
     options::FindOneAndUpdateOptions,
-    Collection, Database,
+    Collection,
 };
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serenity::{
-    all::{CommandInteraction, CommandOptionType, CreateCommand, CreateCommandOption},
+    all::{AutocompleteChoice, CommandInteraction, CreateCommandOption, Permissions},
     async_trait,
     futures::StreamExt,
-    model::prelude::{ChannelType, GuildId},
-    prelude::Context,
+    model::prelude::GuildId,
     utils::MessageBuilder,
 };
-use tokio::time::interval;
-use tokio_stream::wrappers::IntervalStream;
+use tracing::{error, info_span, warn, Instrument};
 
 use crate::{
-    commands::slash_commands::SlashCommands,
-    util::{retrieve_db_handle, CommandRunner, MakeCommandResponse},
+    commands::{
+        group::CommandGroup,
+        options::{command_options, CommandOptions},
+        registry::CommandRegistry,
+    },
+    context::BotContext,
+    cooldowns::{Cooldown, CooldownBucket},
+    error::{BotError, BotResult},
+    guild_config::GuildModule,
+    i18n::{t, Locale},
+    metrics::Metrics,
+    modules::Module,
+    permissions::CommandPermissions,
+    scheduler::{Job, Schedule, Scheduler},
+    util::{retrieve_db_handle, retrieve_metrics, CommandRunner, MakeCommandResponse},
     CommandResponse,
 };
-use anyhow::{anyhow, Result};
+use anyhow::{Context, Result};
 
 const PRIVATE_LEADERBOARDS_COLLECTION: &str = "private_leaderboards";
 
@@ -88,6 +102,18 @@ const fn maximum_weight_value() -> i64 {
     lang_weights_pool()[LANGS.len() - 1]
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct AocConfig {
+    pub(crate) enabled: bool,
+}
+
+impl Default for AocConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct CompletionDayLevel {
     star_index: i64,
@@ -175,11 +201,19 @@ async fn fetch_leaderboard(
         .await?)
 }
 
+/// Leaderboards are fetched at most once per interval unless the fetch is forced.
+fn should_fetch(last_update_timestamp: i64, now: i64, force: bool) -> bool {
+    force || now - last_update_timestamp > INTERVAL_TIME
+}
+
+/// Fetches the leaderboards of every year and returns how many were updated.
 async fn fetch_leaderboards(
     leaderboard_doc: PrivateLeaderboardDatabaseDoc,
     client: reqwest::Client,
     collection: &Collection<PrivateLeaderboardDatabaseDoc>,
-) {
+    metrics: &Metrics,
+    force: bool,
+) -> usize {
     let session_cookie = match leaderboard_doc.session_cookie.cookie.as_ref() {
         Some(c) => c,
         None => {
@@ -187,15 +221,23 @@ async fn fetch_leaderboards(
                 "Session cookie for guild {} is not set. Skipping.",
                 leaderboard_doc.guild_id
             );
-            return;
+            metrics.aoc_fetch("skipped");
+            return 0;
         }
     };
+    let updated = AtomicUsize::new(0);
     tokio_stream::iter(leaderboard_doc.leaderboards.iter())
         .for_each_concurrent(None, |(year, leaderboard)| {
             let client = client.clone();
+            let updated = &updated;
             async move {
-                if Utc::now().timestamp() - leaderboard.last_update_timestamp <= INTERVAL_TIME {
+                if !should_fetch(
+                    leaderboard.last_update_timestamp,
+                    Utc::now().timestamp(),
+                    force,
+                ) {
                     warn!("Tried to fetch too recently. Skipping.");
+                    metrics.aoc_fetch("skipped");
                     return;
                 }
                 let response = match fetch_leaderboard(
@@ -209,6 +251,7 @@ async fn fetch_leaderboards(
                     Ok(resp) => resp,
                     Err(e) => {
                         error!("Error fetching leaderboard: {:#?}", e);
+                        metrics.aoc_fetch("error");
                         return;
                     }
                 };
@@ -216,6 +259,7 @@ async fn fetch_leaderboards(
                     Ok(r) => r,
                     Err(e) => {
                         error!("Failed to convert response to bson: {:#?}", e);
+                        metrics.aoc_fetch("error");
                         return;
                     }
                 };
@@ -233,120 +277,234 @@ async fn fetch_leaderboards(
                     .await
                 {
                     error!("Failed to find and update leaderboard: {:#?}", e);
+                    metrics.aoc_fetch("error");
+                } else {
+                    metrics.aoc_fetch("ok");
+                    updated.fetch_add(1, Ordering::Relaxed);
                 }
             }
         })
         .await;
+    updated.into_inner()
 }
 
-pub async fn start_aoc_auto_fetch(db_handle: Database) {
-    let interval = interval(Duration::from_secs(INTERVAL_TIME as u64 + 5));
-    let db_handle = db_handle.clone();
-    IntervalStream::new(interval)
-        .for_each(|_| {
-            info!("Running AoC autofetch");
-            let db_handle = db_handle.clone();
+/// Fetches every private leaderboard whose session cookie is still fresh, `force` also fetches
+/// the ones updated within the last interval. Returns how many leaderboards were updated.
+pub(crate) async fn fetch_all_leaderboards(ctx: &BotContext, force: bool) -> Result<usize> {
+    let db_handle = retrieve_db_handle(ctx.data.clone()).await?;
+    let metrics = retrieve_metrics(ctx.data.clone()).await?;
+    let collection =
+        db_handle.collection::<PrivateLeaderboardDatabaseDoc>(PRIVATE_LEADERBOARDS_COLLECTION);
+    let leaderboards = collection.find(None, None).await.with_context(|| {
+        format!(
+            "Failed to fetch cursor for collection {}",
+            PRIVATE_LEADERBOARDS_COLLECTION
+        )
+    })?;
+    let client = reqwest::Client::new();
+    let updated = AtomicUsize::new(0);
+    leaderboards
+        .for_each_concurrent(None, |leaderboard_doc| {
+            let client = client.clone();
+            let collection = collection.clone();
+            let metrics = metrics.clone();
+            let updated = &updated;
             async move {
-                let collection = db_handle
-                    .collection::<PrivateLeaderboardDatabaseDoc>(PRIVATE_LEADERBOARDS_COLLECTION);
-                let leaderboards = match collection.find(None, None).await {
+                let leaderboard_doc = match leaderboard_doc {
                     Ok(lb) => lb,
                     Err(e) => {
-                        error!(
-                            "Failed to fetch cursor for collection {}: {:#?}",
-                            PRIVATE_LEADERBOARDS_COLLECTION, e
-                        );
+                        error!("Failed to fetch leaderboard: {:#?}", e);
                         return;
                     }
                 };
-                let client = reqwest::Client::new();
-                leaderboards
-                    .for_each_concurrent(None, |leaderboard_doc| {
-                        let client = client.clone();
-                        let collection = collection.clone();
-                        async move {
-                            let leaderboard_doc = match leaderboard_doc {
-                                Ok(lb) => lb,
-                                Err(e) => {
-                                    error!("Failed to fetch leaderboard: {:#?}", e);
-                                    return;
-                                }
-                            };
-                            // Safe unwrap because we skip if is none
-                            if leaderboard_doc.session_cookie.added_timestamp.is_none()
-                                || Utc::now().timestamp()
-                                    - leaderboard_doc.session_cookie.added_timestamp.unwrap()
-                                    > THIRTY_DAYS_TIME
-                            {
-                                warn!("Skipped fetching leaderboard for guild {} and leaderboard {} cookie is possibly expired!", leaderboard_doc.guild_id, leaderboard_doc.private_leaderboard_id);
-                                return;
-                            }
-                            fetch_leaderboards(leaderboard_doc, client, &collection).await;
-                        }
-                    })
+                // Safe unwrap because we skip if is none
+                if leaderboard_doc.session_cookie.added_timestamp.is_none()
+                    || Utc::now().timestamp()
+                        - leaderboard_doc.session_cookie.added_timestamp.unwrap()
+                        > THIRTY_DAYS_TIME
+                {
+                    warn!("Skipped fetching leaderboard for guild {} and leaderboard {} cookie is possibly expired!", leaderboard_doc.guild_id, leaderboard_doc.private_leaderboard_id);
+                    metrics.aoc_fetch("skipped");
+                    return;
+                }
+                let span = info_span!(
+                    "aoc_leaderboard",
+                    guild_id = leaderboard_doc.guild_id,
+                    leaderboard_id = leaderboard_doc.private_leaderboard_id,
+                );
+                let count = fetch_leaderboards(leaderboard_doc, client, &collection, &metrics, force)
+                    .instrument(span)
                     .await;
+                updated.fetch_add(count, Ordering::Relaxed);
             }
         })
         .await;
+    Ok(updated.into_inner())
+}
+
+/// Fetches every private leaderboard whose session cookie is still fresh.
+pub(crate) struct AocFetchJob;
+
+#[async_trait]
+impl Job for AocFetchJob {
+    fn name(&self) -> &'static str {
+        "aoc_fetch"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::every(Duration::from_secs(INTERVAL_TIME as u64 + 5))
+    }
+
+    fn jitter(&self) -> Duration {
+        Duration::from_secs(30)
+    }
+
+    async fn run(&self, ctx: &BotContext) -> Result<()> {
+        fetch_all_leaderboards(ctx, false).await?;
+        Ok(())
+    }
+}
+
+async fn autocomplete_leaderboard_id(
+    ctx: &BotContext,
+    command: &CommandInteraction,
+) -> Result<Vec<AutocompleteChoice>> {
+    let (guild_id, input) = match (command.guild_id, command.data.autocomplete()) {
+        (Some(guild_id), Some(option)) if option.name == PRIVATE_LEADERBOARD_ID_OPTION => {
+            (guild_id, option.value)
+        }
+        _ => return Ok(Vec::new()),
+    };
+    let db_handle = retrieve_db_handle(ctx.data.clone()).await?;
+    let leaderboard_ids = db_handle
+        .collection::<PrivateLeaderboardDatabaseDoc>(PRIVATE_LEADERBOARDS_COLLECTION)
+        .distinct(
+            "private_leaderboard_id",
+            doc! {"guild_id": guild_id.get() as i64},
+            None,
+        )
+        .await?;
+    Ok(leaderboard_ids
+        .iter()
+        .filter_map(|id| id.as_i64())
+        .filter(|id| id.to_string().starts_with(input.trim()))
+        .take(25)
+        .map(|id| AutocompleteChoice::new(id.to_string(), id))
+        .collect())
+}
+
+pub(crate) struct AocModule;
+
+impl Module for AocModule {
+    fn name(&self) -> &'static str {
+        "aoc"
+    }
+
+    fn guild_module(&self) -> Option<GuildModule> {
+        Some(GuildModule::Aoc)
+    }
+
+    fn register_commands(&self, registry: &mut CommandRegistry) {
+        registry.register(
+            CommandGroup::new("aoc", "Advent of Code")
+                .module(GuildModule::Aoc)
+                .subcommand(AddPrivateLeaderboardCommand)
+                .subcommand(SetSessionCookieCommand)
+                .subcommand(SpeedrunCommand)
+                .subcommand(RollCommand),
+        );
+    }
+
+    fn register_jobs(&self, scheduler: &mut Scheduler) {
+        scheduler.register(AocFetchJob);
+    }
+}
+
+command_options! {
+    struct SpeedrunOptions {
+        day: Option<i64> => (DAY_OPTION, "Speedrun for selected day").range(1, 25),
+        year: Option<i64> => (YEAR_OPTION, "Speedrun for selected year"),
+        leaderboard_id: Option<i64> => (
+            PRIVATE_LEADERBOARD_ID_OPTION,
+            "Speedrun for selected leaderboard"
+        )
+        .autocomplete(),
+    }
 }
 
 pub struct SpeedrunCommand;
 impl MakeCommandResponse for SpeedrunCommand {}
 
+impl SpeedrunCommand {
+    /// Seconds between the first and the second star of every member that finished `day`,
+    /// fastest first.
+    fn results(leaderboard: &PrivateLeaderboard, day: i64) -> Vec<(&String, i64)> {
+        let mut results = leaderboard
+            .members
+            .values()
+            .filter_map(|member| {
+                if let Some(day_result) = member.completion_day_level.get(&day.to_string()) {
+                    if let (Some(first), Some(second)) = (day_result.get("1"), day_result.get("2"))
+                    {
+                        return Some((&member.name, second.get_star_ts - first.get_star_ts));
+                    }
+                }
+                None
+            })
+            .collect::<Vec<(&String, i64)>>();
+        results.sort_by_key(|a| a.1);
+        results
+    }
+
+    fn render(locale: Locale, year: i64, day: i64, results: &[(&String, i64)]) -> String {
+        let mut message_builder = MessageBuilder::new();
+        message_builder.push_bold_line(t!(locale, "aoc.speedrun_title", year = year, day = day));
+        results.iter().for_each(|result| {
+            message_builder.push_line(format!(
+                "{}: {:#?}",
+                result.0,
+                Duration::from_secs(result.1 as u64)
+            ));
+        });
+        message_builder.build()
+    }
+}
+
 #[async_trait]
 impl CommandRunner for SpeedrunCommand {
-    fn register(&self) -> CreateCommand {
-        info!("Command registered: {}", SlashCommands::Speedrun.as_str());
-        let command = CreateCommand::new(SlashCommands::Speedrun.as_str());
-        command
-            .dm_permission(false)
-            .add_option(
-                CreateCommandOption::new(
-                    CommandOptionType::Integer,
-                    DAY_OPTION,
-                    "Speedrun for selected day",
-                )
-                .required(false)
-                .channel_types(vec![ChannelType::Text]),
-            )
-            .add_option(
-                CreateCommandOption::new(
-                    CommandOptionType::Integer,
-                    YEAR_OPTION,
-                    "Speedrun for selected year",
-                )
-                .required(false)
-                .channel_types(vec![ChannelType::Text]),
-            )
-            .add_option(
-                CreateCommandOption::new(
-                    CommandOptionType::Integer,
-                    PRIVATE_LEADERBOARD_ID_OPTION,
-                    "Speedrun for selected leaderboard",
-                )
-                .required(false)
-                .channel_types(vec![ChannelType::Text]),
-            )
-            .description("AoC Speedrun")
+    fn name(&self) -> &'static str {
+        "speedrun"
     }
 
-    async fn run(&self, ctx: &Context, command: &CommandInteraction) -> Result<CommandResponse> {
+    fn description(&self) -> &'static str {
+        "AoC Speedrun"
+    }
+
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        SpeedrunOptions::create_options(key)
+    }
+
+    async fn autocomplete(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> Result<Vec<AutocompleteChoice>> {
+        autocomplete_leaderboard_id(ctx, command).await
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
         let guild_id = command
             .guild_id
-            .ok_or_else(|| anyhow!("Command must be run in guild"))?;
+            .ok_or_else(|| BotError::bad_input(t!(ctx.locale, "errors.guild_only")))?;
+        let options = SpeedrunOptions::parse(command, ctx.locale)?;
         let db_handle = retrieve_db_handle(ctx.data.clone()).await?;
         let collection =
             db_handle.collection::<PrivateLeaderboardDatabaseDoc>(PRIVATE_LEADERBOARDS_COLLECTION);
-        let db_query = if let Some(leaderboard_id) = command
-            .data
-            .options
-            .iter()
-            .find(|opt| opt.name == PRIVATE_LEADERBOARD_ID_OPTION)
-        {
-            let leaderboard_id = leaderboard_id
-                .value
-                .as_i64()
-                .ok_or_else(|| anyhow!("Leaderboard ID value is missing"))?;
+        let db_query = if let Some(leaderboard_id) = options.leaderboard_id {
             collection.find_one(
                 doc! {
                     "guild_id": guild_id.get() as i64,
@@ -360,156 +518,94 @@ impl CommandRunner for SpeedrunCommand {
         if let Some(leaderboard_doc) = db_query.await? {
             let now = Utc::now();
             let month = now.month();
-            let year = if let Some(year_option) = command
-                .data
-                .options
-                .iter()
-                .find(|opt| opt.name == YEAR_OPTION)
-            {
-                year_option
-                    .value
-                    .as_i64()
-                    .ok_or_else(|| anyhow!("Year is missing"))?
+            let year = if let Some(year) = options.year {
+                year
             } else if month == 12 {
                 now.year() as i64
             } else {
                 (now.year() - 1) as i64
             };
-            let mut day = if let Some(day_option) = command
-                .data
-                .options
-                .iter()
-                .find(|opt| opt.name == DAY_OPTION)
-            {
-                day_option
-                    .value
-                    .as_i64()
-                    .ok_or_else(|| anyhow!("Day value is missing"))?
-            } else {
-                now.day() as i64
+            let day = match options.day {
+                Some(day) => day,
+                // Outside of the event days there is no puzzle for today
+                None if now.day() <= 25 => now.day() as i64,
+                None => 1,
             };
-            if !(1..=25).contains(&day) {
-                day = 1;
-            }
-            let mut results = leaderboard_doc
+            let leaderboard = leaderboard_doc
                 .leaderboards
                 .get(&year.to_string())
-                .ok_or_else(|| anyhow!("No leaderboard for selected year"))?
-                .members
-                .values()
-                .filter_map(|member| {
-                    if let Some(day_result) = member.completion_day_level.get(&day.to_string()) {
-                        if let (Some(first), Some(second)) =
-                            (day_result.get("1"), day_result.get("2"))
-                        {
-                            return Some((&member.name, second.get_star_ts - first.get_star_ts));
-                        }
-                    }
-                    None
-                })
-                .collect::<Vec<(&String, i64)>>();
-            results.sort_by(|a, b| a.1.cmp(&b.1));
+                .ok_or_else(|| BotError::not_found(t!(ctx.locale, "aoc.no_leaderboard")))?;
+            let results = Self::results(leaderboard, day);
             if results.is_empty() {
-                return Ok(self.make_response("There are no speedruns", false));
+                return Ok(self.make_response(t!(ctx.locale, "aoc.no_speedruns"), false));
             }
-            let mut message_builder = MessageBuilder::new();
-            message_builder.push_bold_line(format!("Speedrun for AoC{} day {}", year, day));
-            results.iter().for_each(|result| {
-                message_builder.push_line(format!(
-                    "{}: {:#?}",
-                    result.0,
-                    Duration::from_secs(result.1 as u64)
-                ));
-            });
-            Ok(self.make_response(message_builder.build(), false))
+            Ok(self.make_response(Self::render(ctx.locale, year, day, &results), false))
         } else {
-            Ok(self.make_response("There are no speedruns", false))
+            Ok(self.make_response(t!(ctx.locale, "aoc.no_speedruns"), false))
         }
     }
 }
 
+command_options! {
+    struct AddPrivateLeaderboardOptions {
+        leaderboard_id: i64 => (PRIVATE_LEADERBOARD_ID_OPTION, "Private leaderboard ID"),
+        year: i64 => (YEAR_OPTION, "Year"),
+        session_cookie: String => (SESSION_COOKIE_OPTION, "Session cookie string"),
+    }
+}
+
 pub struct AddPrivateLeaderboardCommand;
 impl MakeCommandResponse for AddPrivateLeaderboardCommand {}
 
 #[async_trait]
 impl CommandRunner for AddPrivateLeaderboardCommand {
-    fn register(&self) -> CreateCommand {
-        info!(
-            "Command registered: {}",
-            SlashCommands::AddPrivateLeaderboard.as_str()
-        );
-        let command = CreateCommand::new(SlashCommands::AddPrivateLeaderboard.as_str());
-        command
-            .dm_permission(false)
-            .add_option(
-                CreateCommandOption::new(
-                    CommandOptionType::Integer,
-                    PRIVATE_LEADERBOARD_ID_OPTION,
-                    "Private leaderboard ID",
-                )
-                .required(true)
-                .channel_types(vec![ChannelType::Text]),
-            )
-            .add_option(
-                CreateCommandOption::new(CommandOptionType::Integer, YEAR_OPTION, "Year")
-                    .required(true)
-                    .channel_types(vec![ChannelType::Text]),
-            )
-            .add_option(
-                CreateCommandOption::new(
-                    CommandOptionType::String,
-                    SESSION_COOKIE_OPTION,
-                    "Session cookie string",
-                )
-                .required(true)
-                .channel_types(vec![ChannelType::Text]),
-            )
-            .description("AoC add private leaderboard")
+    fn name(&self) -> &'static str {
+        "add"
+    }
+
+    fn description(&self) -> &'static str {
+        "AoC add private leaderboard"
     }
 
-    async fn run(&self, ctx: &Context, command: &CommandInteraction) -> Result<CommandResponse> {
+    fn permissions(&self) -> CommandPermissions {
+        CommandPermissions {
+            default_member_permissions: Some(Permissions::MANAGE_GUILD),
+            ..Default::default()
+        }
+    }
+
+    // Adding a leaderboard fetches it from AoC, which asks for at most one request per 15 minutes
+    fn cooldowns(&self) -> Vec<Cooldown> {
+        vec![Cooldown::new(
+            CooldownBucket::Guild,
+            1,
+            Duration::from_secs(INTERVAL_TIME as u64),
+        )]
+    }
+
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        AddPrivateLeaderboardOptions::create_options(key)
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
         let guild_id = command
             .guild_id
-            .ok_or_else(|| anyhow!("Command must be run in guild"))?;
+            .ok_or_else(|| BotError::bad_input(t!(ctx.locale, "errors.guild_only")))?;
 
         // WARN: Inefficient, but should be ran rarely
-        let leaderboard_id = command
-            .data
-            .options
-            .iter()
-            .find(|opt| opt.name == PRIVATE_LEADERBOARD_ID_OPTION)
-            .ok_or_else(|| anyhow!("Leaderboard ID is required"))?
-            .value
-            .as_i64()
-            .ok_or_else(|| anyhow!("Leaderboard ID value is missing"))?;
-        let year = command
-            .data
-            .options
-            .iter()
-            .find(|opt| opt.name == YEAR_OPTION)
-            .ok_or_else(|| anyhow!("Year is required"))?
-            .value
-            .as_i64()
-            .ok_or_else(|| anyhow!("Year value is missing"))?;
-        let mut session_cookie = match command
-            .data
-            .options
-            .iter()
-            .find(|opt| opt.name == SESSION_COOKIE_OPTION)
-        {
-            Some(sc) => {
-                let cookie = sc
-                    .value
-                    .as_str()
-                    .ok_or_else(|| anyhow!("Session cookie is missing"))?
-                    .to_string();
-                // TODO: Check why is this here
-                // cookie.remove(cookie.len() - 1);
-                // cookie.remove(0);
-                Some(cookie)
-            }
-            None => None,
-        };
+        let AddPrivateLeaderboardOptions {
+            leaderboard_id,
+            year,
+            session_cookie,
+        } = AddPrivateLeaderboardOptions::parse(command, ctx.locale)?;
+        // TODO: Check why is this here
+        // session_cookie.remove(session_cookie.len() - 1);
+        // session_cookie.remove(0);
+        let mut session_cookie = Some(session_cookie);
         let db_handle = retrieve_db_handle(ctx.data.clone()).await?;
         let collection =
             db_handle.collection::<PrivateLeaderboardDatabaseDoc>(PRIVATE_LEADERBOARDS_COLLECTION);
@@ -536,7 +632,6 @@ impl CommandRunner for AddPrivateLeaderboardCommand {
         } else if let Some(leaderboard_doc) = leaderboard_doc {
             session_cookie = leaderboard_doc.session_cookie.cookie;
         }
-        // WARN: Can be spammed and bypass minimum recommended 15 minutes between requests
         if let Some(session_cookie) = session_cookie {
             let client = reqwest::Client::new();
             let response =
@@ -556,7 +651,18 @@ impl CommandRunner for AddPrivateLeaderboardCommand {
                 )
                 .await?;
         }
-        Ok(self.make_response("Leaderboard has been added", false))
+        Ok(self.make_response(t!(ctx.locale, "aoc.leaderboard_added"), false))
+    }
+}
+
+command_options! {
+    struct SetSessionCookieOptions {
+        leaderboard_id: i64 => (
+            PRIVATE_LEADERBOARD_ID_OPTION,
+            "ID of a leaderboard to update the session cookie for"
+        )
+        .autocomplete(),
+        session_cookie: String => (SESSION_COOKIE_OPTION, "Session cookie string").length(2, 512),
     }
 }
 
@@ -565,60 +671,50 @@ impl MakeCommandResponse for SetSessionCookieCommand {}
 
 #[async_trait]
 impl CommandRunner for SetSessionCookieCommand {
-    fn register(&self) -> CreateCommand {
-        info!(
-            "Command registered: {}",
-            SlashCommands::SetSessionCookie.as_str()
-        );
-        let command = CreateCommand::new(SlashCommands::SetSessionCookie.as_str());
-        command
-            .dm_permission(false)
-            .add_option(
-                CreateCommandOption::new(
-                    CommandOptionType::Integer,
-                    PRIVATE_LEADERBOARD_ID_OPTION,
-                    "ID of a leaderboard to update the session cookie for",
-                )
-                .required(true)
-                .channel_types(vec![ChannelType::Text]),
-            )
-            .add_option(
-                CreateCommandOption::new(
-                    CommandOptionType::String,
-                    SESSION_COOKIE_OPTION,
-                    "Session cookie string",
-                )
-                .required(true)
-                .channel_types(vec![ChannelType::Text]),
-            )
-            .description("Adds a session cookie for fetching AoC private leaderboards")
+    fn name(&self) -> &'static str {
+        "cookie"
+    }
+
+    fn description(&self) -> &'static str {
+        "Adds a session cookie for fetching AoC private leaderboards"
+    }
+
+    fn permissions(&self) -> CommandPermissions {
+        CommandPermissions {
+            default_member_permissions: Some(Permissions::MANAGE_GUILD),
+            ..Default::default()
+        }
     }
 
-    async fn run(&self, ctx: &Context, command: &CommandInteraction) -> Result<CommandResponse> {
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        SetSessionCookieOptions::create_options(key)
+    }
+
+    async fn autocomplete(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> Result<Vec<AutocompleteChoice>> {
+        autocomplete_leaderboard_id(ctx, command).await
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
         let guild_id = command
             .guild_id
-            .ok_or_else(|| anyhow!("Command must be run in guild"))?;
-        let leaderboard_id = command
-            .data
-            .options
-            .iter()
-            .find(|opt| opt.name == PRIVATE_LEADERBOARD_ID_OPTION)
-            .ok_or_else(|| anyhow!("Leaderboard ID is required"))?
-            .value
-            .as_i64()
-            .ok_or_else(|| anyhow!("Leaderboard ID value is missing"))?;
-        let mut session_cookie = command
-            .data
-            .options
-            .iter()
-            .find(|opt| opt.name == SESSION_COOKIE_OPTION)
-            .ok_or_else(|| anyhow!("Session cookie is required"))?
-            .value
-            .as_str()
-            .ok_or_else(|| anyhow!("Session cookie value is missing"))?
-            .to_string();
-        session_cookie.remove(session_cookie.len() - 1);
-        session_cookie.remove(0);
+            .ok_or_else(|| BotError::bad_input(t!(ctx.locale, "errors.guild_only")))?;
+        let SetSessionCookieOptions {
+            leaderboard_id,
+            session_cookie,
+        } = SetSessionCookieOptions::parse(command, ctx.locale)?;
+        // The cookie is often pasted with the quotes around it
+        let session_cookie = session_cookie.trim().trim_matches('"');
+        if session_cookie.is_empty() {
+            return Err(BotError::bad_input(t!(ctx.locale, "aoc.empty_cookie")));
+        }
 
         let db_handle = retrieve_db_handle(ctx.data.clone()).await?;
         let collection =
@@ -640,9 +736,12 @@ impl CommandRunner for SetSessionCookieCommand {
             .await?
             .is_some()
         {
-            Ok(self.make_response("Successfully set session".to_string(), false))
+            Ok(self.make_response(t!(ctx.locale, "aoc.session_set"), false))
         } else {
-            Ok(self.make_response("Leaderboard not found".to_string(), false))
+            Err(BotError::not_found(t!(
+                ctx.locale,
+                "aoc.leaderboard_not_found"
+            )))
         }
     }
 }
@@ -652,15 +751,19 @@ impl MakeCommandResponse for RollCommand {}
 
 #[async_trait]
 impl CommandRunner for RollCommand {
-    fn register(&self) -> CreateCommand {
-        info!("Command registered: {}", SlashCommands::Roll.as_str());
-        let command = CreateCommand::new(SlashCommands::Roll.as_str());
-        command
-            .dm_permission(false)
-            .description("Rolls a programming language")
+    fn name(&self) -> &'static str {
+        "roll"
+    }
+
+    fn description(&self) -> &'static str {
+        "Rolls a programming language"
     }
 
-    async fn run(&self, _ctx: &Context, _command: &CommandInteraction) -> Result<CommandResponse> {
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        _command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
         let weights_pool = lang_weights_pool();
         let maximum_value = maximum_weight_value();
 
@@ -679,7 +782,113 @@ impl CommandRunner for RollCommand {
                 false,
             ))
         } else {
-            Ok(self.make_response("Dober kod pajdo.".to_string(), false))
+            Ok(self.make_response(t!(ctx.locale, "aoc.roll_fallback"), false))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::testing::{InteractionBuilder, TestHarness};
+
+    fn leaderboard() -> PrivateLeaderboard {
+        let member = |id: i64, name: &str, first: i64, second: Option<i64>| {
+            let mut day = json!({ "1": { "star_index": 1, "get_star_ts": first } });
+            if let Some(second) = second {
+                day["2"] = json!({ "star_index": 2, "get_star_ts": second });
+            }
+            json!({
+                "id": id,
+                "name": name,
+                "stars": 2,
+                "global_score": 0,
+                "local_score": 10,
+                "last_star_ts": second.unwrap_or(first),
+                "completion_day_level": { "3": day },
+            })
+        };
+        serde_json::from_value(json!({
+            "members": {
+                "1": member(1, "slow", 1000, Some(1600)),
+                "2": member(2, "fast", 1000, Some(1060)),
+                "3": member(3, "unfinished", 1000, None),
+            },
+            "owner_id": 1,
+            "event": "2023",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn speedrun_orders_finished_members() {
+        let leaderboard = leaderboard();
+        let results = SpeedrunCommand::results(&leaderboard, 3);
+        let names = results
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["fast", "slow"]);
+        assert!(SpeedrunCommand::results(&leaderboard, 4).is_empty());
+        assert_eq!(
+            SpeedrunCommand::render(Locale::En, 2023, 3, &results),
+            "**Speedrun for AoC2023 day 3**\nfast: 60s\nslow: 600s\n"
+        );
+    }
+
+    #[test]
+    fn force_fetches_recent_leaderboards() {
+        let now = 1_700_000_000;
+        let recent = now - 60;
+        assert!(!should_fetch(recent, now, false));
+        assert!(should_fetch(recent, now, true));
+        assert!(should_fetch(now - INTERVAL_TIME - 1, now, false));
+    }
+
+    #[tokio::test]
+    async fn speedrun_rejects_invalid_day() {
+        let harness = TestHarness::new().await;
+        let command = InteractionBuilder::new("aoc")
+            .subcommand("speedrun")
+            .option(DAY_OPTION, 30)
+            .build();
+        let err = harness.dispatch(&command).await.err().unwrap();
+        assert!(matches!(err, BotError::BadInput(_)), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn speedrun_requires_guild() {
+        let harness = TestHarness::new().await;
+        let command = InteractionBuilder::new("aoc")
+            .subcommand("speedrun")
+            .without_guild()
+            .build();
+        let err = harness.dispatch(&command).await.err().unwrap();
+        assert!(matches!(err, BotError::BadInput(_)), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn cookie_rejects_empty_session() {
+        let harness = TestHarness::new().await;
+        let command = InteractionBuilder::new("aoc")
+            .subcommand("cookie")
+            .option(PRIVATE_LEADERBOARD_ID_OPTION, 1)
+            .option(SESSION_COOKIE_OPTION, " \"\" ")
+            .build();
+        let err = harness.dispatch(&command).await.err().unwrap();
+        assert!(matches!(err, BotError::BadInput(_)), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn roll_picks_a_language() {
+        let harness = TestHarness::new().await;
+        let command = InteractionBuilder::new("aoc").subcommand("roll").build();
+        let response = harness.dispatch(&command).await.unwrap();
+        assert!(LANGS
+            .iter()
+            .any(|lang| response.content.starts_with(&format!("{} (", lang.lang))));
+        assert!(response.content.ends_with("%)"));
+    }
+}
@@ -2,14 +2,25 @@ use std::time::Duration;
 
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use mongodb::{bson::doc, options::FindOneAndUpdateOptions};
 use serde::{Deserialize, Serialize};
-use serenity::{all::CreateMessage, futures::StreamExt, model::prelude::Message, prelude::Context};
+use serenity::{
+    all::{CreateMessage, GatewayIntents, GuildId},
+    async_trait,
+    futures::StreamExt,
+    model::prelude::Message,
+    prelude::Context,
+};
 
 use crate::{
-    database::MongoDatabaseHandle, event_handlers::mr_handler::MrHandler, unban::BanRecordUser,
+    cooldowns::{Cooldown, CooldownBucket},
+    database::{BanRecordUser, MongoDatabaseHandle},
+    guild_config::GuildModule,
+    i18n::{t, Locale},
+    modules::Module,
+    util::{retrieve_cooldown_service, retrieve_guild_config_service},
 };
 
+// Defaults for the server the feature was written for, other servers have to configure it
 const MATTID: u64 = 252114544485335051;
 const SERVER: u64 = 173766075484340234;
 const EMOJIID: &str = "<:banajmatijosa:621685158600245248>";
@@ -36,10 +47,45 @@ const ALLOWEDMEMBERS: &[u64] = &[
 pub const MATT_BAN_COLLECTION: &str = "matt_ban";
 pub const BAN_COOLDOWN_TIME: i64 = 3600;
 
-#[derive(Serialize, Deserialize)]
-pub(crate) struct MattBanCooldown {
-    pub(crate) cooldown: i64,
-    pub(crate) last_ban_timestamp: i64,
+const BAN_COOLDOWN_KEY: &str = "banaj_matijosa";
+const BAN_COOLDOWN: Cooldown = Cooldown::new(
+    CooldownBucket::Global,
+    1,
+    Duration::from_secs(BAN_COOLDOWN_TIME as u64),
+);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct BanajMatijosaConfig {
+    pub(crate) enabled: bool,
+    pub(crate) target_user: Option<i64>,
+    pub(crate) emoji: String,
+    pub(crate) allowed_members: Vec<i64>,
+}
+
+impl Default for BanajMatijosaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_user: None,
+            emoji: EMOJIID.to_string(),
+            allowed_members: Vec::new(),
+        }
+    }
+}
+
+impl BanajMatijosaConfig {
+    pub(crate) fn default_for(guild_id: GuildId) -> Self {
+        if guild_id.get() != SERVER {
+            return Self::default();
+        }
+        Self {
+            enabled: true,
+            target_user: Some(MATTID as i64),
+            emoji: EMOJIID.to_string(),
+            allowed_members: ALLOWEDMEMBERS.iter().map(|id| *id as i64).collect(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -59,14 +105,83 @@ impl MattBan {
     }
 }
 
-impl MrHandler {
-    pub(crate) async fn banaj_matijosa(&self, ctx: &Context, message: &Message) -> Result<()> {
-        if message
+pub(crate) struct BanajMatijosaModule;
+
+#[async_trait]
+impl Module for BanajMatijosaModule {
+    fn name(&self) -> &'static str {
+        "banaj_matijosa"
+    }
+
+    fn guild_module(&self) -> Option<GuildModule> {
+        Some(GuildModule::BanajMatijosa)
+    }
+
+    /// Finding the member to ban lists guild members, which needs the members intent.
+    fn intents(&self) -> GatewayIntents {
+        GatewayIntents::GUILD_MESSAGES
+            | GatewayIntents::MESSAGE_CONTENT
+            | GatewayIntents::GUILD_MEMBERS
+    }
+
+    async fn message(&self, ctx: &Context, message: &Message) -> Result<()> {
+        self.banaj_matijosa(ctx, message).await
+    }
+}
+
+impl BanajMatijosaModule {
+    /// Says goodbye and bans the target, returns whether they were found in the guild.
+    async fn ban_target(
+        &self,
+        ctx: &Context,
+        message: &Message,
+        guild_id: GuildId,
+        target_user: i64,
+        language: Locale,
+    ) -> Result<bool> {
+        let mut members_stream = guild_id.members_iter(&ctx.http).boxed();
+        while let Some(member_result) = members_stream.next().await {
+            let member = member_result?;
+            if member.user.id.get() as i64 == target_user {
+                message
+                    .channel(&ctx.http)
+                    .await?
+                    .id()
+                    .send_message(
+                        &ctx.http,
+                        CreateMessage::new()
+                            .content(t!(language, "banaj_matijosa.goodbye"))
+                            .tts(true),
+                    )
+                    .await?;
+                tokio::time::sleep(Duration::from_secs(4)).await;
+                member.ban(&ctx.http, 0).await?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    async fn banaj_matijosa(&self, ctx: &Context, message: &Message) -> Result<()> {
+        let guild_id = message
             .guild_id
-            .ok_or_else(|| anyhow::anyhow!("Message is missing guild id."))?
-            == SERVER
-            && ALLOWEDMEMBERS.contains(&message.author.id.get())
-            && message.content.contains(EMOJIID)
+            .ok_or_else(|| anyhow::anyhow!("Message is missing guild id."))?;
+        let config_service = retrieve_guild_config_service(ctx.data.clone()).await?;
+        if !config_service
+            .is_enabled(guild_id, GuildModule::BanajMatijosa)
+            .await?
+        {
+            return Ok(());
+        }
+        let config = config_service.get(guild_id).await?;
+        let settings = &config.banaj_matijosa;
+        let Some(target_user) = settings.target_user else {
+            return Ok(());
+        };
+        if settings
+            .allowed_members
+            .contains(&(message.author.id.get() as i64))
+            && message.content.contains(&settings.emoji)
         {
             let author_id = message.author.id.get();
             let handle = ctx
@@ -76,74 +191,57 @@ impl MrHandler {
                 .get::<MongoDatabaseHandle>()
                 .ok_or_else(|| anyhow::anyhow!("Failed to retrieve MongoDatabaseHandle from data"))?
                 .clone();
-            let time_now = Utc::now().timestamp();
-            let last_ban = {
-                let collection = handle.collection::<MattBanCooldown>(MATT_BAN_COLLECTION);
-                let last_ban = collection.find_one(doc! {"_id": "COOLDOWN"}, None).await?;
-                match last_ban {
-                    Some(lb) => lb,
-                    None => MattBanCooldown {
-                        cooldown: 0,
-                        last_ban_timestamp: 0,
-                    },
-                }
-            };
-
-            if time_now - last_ban.cooldown > last_ban.last_ban_timestamp {
-                if let Some(guild) = ctx
-                    .cache
-                    .guilds()
-                    .iter()
-                    .find(|guild| guild.get() == SERVER)
-                {
-                    let mut members_stream = guild.members_iter(&ctx.http).boxed();
-                    while let Some(member_result) = members_stream.next().await {
-                        let member = member_result?;
-                        if member.user.id.get() == MATTID {
-                            message
-                                .channel(&ctx.http)
-                                .await?
-                                .id()
-                                .send_message(
-                                    &ctx.http,
-                                    CreateMessage::new().content("Ajde bok Matijoš!").tts(true),
-                                )
-                                .await?;
-                            tokio::time::sleep(Duration::from_secs(4)).await;
-                            member.ban(&ctx.http, 0).await?;
-                            handle
-                                .collection::<MattBan>(MATT_BAN_COLLECTION)
-                                .insert_one(MattBan::new(author_id, true), None)
-                                .await?;
-                            handle
-                                .collection::<MattBanCooldown>(MATT_BAN_COLLECTION)
-                                .find_one_and_update(
-                                    doc! {"_id": "COOLDOWN"},
-                                    doc! {"$set": {"cooldown": BAN_COOLDOWN_TIME, "last_ban_timestamp": time_now}},
-                                    Some(FindOneAndUpdateOptions::builder().upsert(true).build()),
-                                )
-                                .await?;
-                            break;
-                        }
+            let cooldown_service = retrieve_cooldown_service(ctx.data.clone()).await?;
+            let remaining = cooldown_service
+                .acquire(
+                    BAN_COOLDOWN_KEY,
+                    &[BAN_COOLDOWN],
+                    message.guild_id,
+                    message.author.id,
+                )
+                .await?;
+            match remaining {
+                None => {
+                    let banned = self
+                        .ban_target(ctx, message, guild_id, target_user, config.language)
+                        .await;
+                    // Only a ban that went through uses up the cooldown
+                    if !matches!(banned, Ok(true)) {
+                        cooldown_service
+                            .release(
+                                BAN_COOLDOWN_KEY,
+                                &[BAN_COOLDOWN],
+                                message.guild_id,
+                                message.author.id,
+                            )
+                            .await?;
+                    }
+                    if banned? {
+                        handle
+                            .collection::<MattBan>(MATT_BAN_COLLECTION)
+                            .insert_one(MattBan::new(author_id, true), None)
+                            .await?;
                     }
                 }
-            } else {
-                message
-                    .channel(&ctx.http)
-                    .await?
-                    .id()
-                    .send_message(
-                        &ctx.http,
-                        CreateMessage::new().content(&format!(
-                            "Nečem ga još banati! ({} s)",
-                            last_ban.cooldown - (time_now - last_ban.last_ban_timestamp)
-                        )),
-                    )
-                    .await?;
-                let collection = handle.collection::<MattBan>(MATT_BAN_COLLECTION);
-                collection
-                    .insert_one(MattBan::new(author_id, false), None)
-                    .await?;
+                Some(remaining) => {
+                    message
+                        .channel(&ctx.http)
+                        .await?
+                        .id()
+                        .send_message(
+                            &ctx.http,
+                            CreateMessage::new().content(t!(
+                                config.language,
+                                "banaj_matijosa.cooldown",
+                                seconds = remaining.as_secs()
+                            )),
+                        )
+                        .await?;
+                    handle
+                        .collection::<MattBan>(MATT_BAN_COLLECTION)
+                        .insert_one(MattBan::new(author_id, false), None)
+                        .await?;
+                }
             }
         }
         Ok(())
@@ -1,16 +1,21 @@
 use anyhow::Result;
-use log::info;
-use mongodb::{bson::doc, Collection, Cursor};
+use mongodb::{bson::doc, options::FindOptions, Collection, Cursor};
 use serde::{Deserialize, Serialize};
 use serenity::{
-    all::{CommandInteraction, CreateCommand},
+    all::{CommandInteraction, CommandType, ResolvedTarget, User},
     async_trait,
-    prelude::Context,
+    futures::TryStreamExt,
     utils::MessageBuilder,
 };
+use tracing::info;
 
 use crate::{
-    commands::slash_commands::SlashCommands,
+    commands::registry::CommandRegistry,
+    context::BotContext,
+    error::{BotError, BotResult},
+    i18n::{t, Locale},
+    modules::Module,
+    unban::BanRecord,
     util::{retrieve_db_handle, CommandRunner, MakeCommandResponse},
     CommandResponse, UNDERSCOREBANS,
 };
@@ -24,6 +29,21 @@ struct BanCountRecord {
     nickname: Option<String>,
 }
 
+pub(crate) struct BantopModule;
+
+impl Module for BantopModule {
+    fn name(&self) -> &'static str {
+        "bantop"
+    }
+
+    fn register_commands(&self, registry: &mut CommandRegistry) {
+        registry.register(BanTopCommand);
+        registry.register(BanHistoryCommand);
+    }
+}
+
+const BAN_HISTORY_LIMIT: i64 = 10;
+
 pub(crate) struct BanTopCommand;
 impl MakeCommandResponse for BanTopCommand {}
 
@@ -60,6 +80,31 @@ impl BanTopCommand {
         Self::ban_count_cursor_to_vec(cursor).await
     }
 
+    fn render(
+        locale: Locale,
+        most_banned_users: &[BanCountRecord],
+        most_bans_issued: &[BanCountRecord],
+    ) -> String {
+        let mut builder = MessageBuilder::new();
+        for (title, users) in [
+            (t!(locale, "bantop.top_banned"), most_banned_users),
+            (t!(locale, "bantop.top_bans"), most_bans_issued),
+        ] {
+            builder.push_bold_line(title);
+            users.iter().enumerate().for_each(|(idx, user)| {
+                builder
+                    .push_quote("")
+                    .push_bold(format!("{}. ", idx + 1))
+                    .push_line(format!(
+                        "{}: {}",
+                        user.nickname.as_ref().unwrap_or(&user.display_name),
+                        user.count
+                    ));
+            });
+        }
+        builder.build()
+    }
+
     async fn ban_count_cursor_to_vec(
         mut cursor: Cursor<BanCountRecord>,
     ) -> Result<Vec<BanCountRecord>> {
@@ -73,16 +118,23 @@ impl BanTopCommand {
 
 #[async_trait]
 impl CommandRunner for BanTopCommand {
-    fn register(&self) -> CreateCommand {
-        info!("Command registered: {}", SlashCommands::BanTop.as_str());
-        CreateCommand::new(SlashCommands::BanTop.as_str()).description("Ban leaderboard")
+    fn name(&self) -> &'static str {
+        "bantop"
     }
 
-    async fn run(&self, ctx: &Context, command: &CommandInteraction) -> Result<CommandResponse> {
+    fn description(&self) -> &'static str {
+        "Ban leaderboard"
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
         info!("BanTop command called");
         let guild_id = command
             .guild_id
-            .ok_or_else(|| anyhow::anyhow!("Command is not called from a guild!"))?;
+            .ok_or_else(|| BotError::bad_input(t!(ctx.locale, "errors.guild_only")))?;
         let db_handle = retrieve_db_handle(ctx.data.clone()).await?;
         let collection =
             db_handle.collection::<BanCountRecord>(&format!("{}{UNDERSCOREBANS}", guild_id.get()));
@@ -90,32 +142,167 @@ impl CommandRunner for BanTopCommand {
         let most_bans_issued = Self::get_users(&collection, "$banned_by", &guild_id_string).await?;
         let most_banned_users =
             Self::get_users(&collection, "$banned_user", &guild_id_string).await?;
+        Ok(self.make_response(
+            Self::render(ctx.locale, &most_banned_users, &most_bans_issued),
+            false,
+        ))
+    }
+}
+
+pub(crate) struct BanHistoryCommand;
+impl MakeCommandResponse for BanHistoryCommand {}
+
+impl BanHistoryCommand {
+    /// `history` holds the latest bans of `user`, newest first.
+    fn render(
+        locale: Locale,
+        user: &User,
+        times_banned: u64,
+        bans_issued: u64,
+        history: &[BanRecord],
+    ) -> String {
         let mut builder = MessageBuilder::new();
-        builder.push_bold_line("Top Banned:");
-        most_banned_users
-            .iter()
-            .enumerate()
-            .for_each(|(idx, user)| {
-                builder
-                    .push_quote("")
-                    .push_bold(format!("{}. ", idx + 1))
-                    .push_line(format!(
-                        "{}: {}",
-                        user.nickname.as_ref().unwrap_or(&user.display_name),
-                        user.count
-                    ));
-            });
-        builder.push_bold_line("Top Bans:");
-        most_bans_issued.iter().enumerate().for_each(|(idx, user)| {
-            builder
-                .push_quote("")
-                .push_bold(format!("{}. ", idx + 1))
-                .push_line(format!(
-                    "{}: {}",
-                    user.nickname.as_ref().unwrap_or(&user.display_name),
-                    user.count
-                ));
-        });
-        Ok(self.make_response(builder.build(), false))
+        builder
+            .push_bold_line(t!(
+                locale,
+                "bantop.history_title",
+                user = user.global_name.as_ref().unwrap_or(&user.name)
+            ))
+            .push_line(t!(
+                locale,
+                "bantop.history_summary",
+                banned = times_banned,
+                issued = bans_issued
+            ));
+        for ban in history {
+            let reason = match &ban.reason {
+                Some(reason) => reason.clone(),
+                None => t!(locale, "bantop.no_reason"),
+            };
+            builder.push_quote_line(t!(
+                locale,
+                "bantop.history_entry",
+                timestamp = ban.timestamp.timestamp(),
+                user = ban.banned_by.0,
+                reason = reason
+            ));
+        }
+        builder.build()
+    }
+}
+
+#[async_trait]
+impl CommandRunner for BanHistoryCommand {
+    fn name(&self) -> &'static str {
+        "Ban history"
+    }
+
+    fn description(&self) -> &'static str {
+        "Shows how often a user was banned and by whom"
+    }
+
+    fn kind(&self) -> CommandType {
+        CommandType::User
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let guild_id = command
+            .guild_id
+            .ok_or_else(|| BotError::bad_input(t!(ctx.locale, "errors.guild_only")))?;
+        let Some(ResolvedTarget::User(user, _)) = command.data.target() else {
+            return Err(BotError::bad_input(t!(ctx.locale, "bantop.target_user")));
+        };
+        let user_id = user.id.get() as i64;
+        let collection = retrieve_db_handle(ctx.data.clone())
+            .await?
+            .collection::<BanRecord>(&format!("{}{UNDERSCOREBANS}", guild_id.get()));
+        let times_banned = collection
+            .count_documents(doc! {"banned_user": user_id}, None)
+            .await?;
+        let bans_issued = collection
+            .count_documents(doc! {"banned_by": user_id}, None)
+            .await?;
+        let history = collection
+            .find(
+                doc! {"banned_user": user_id},
+                FindOptions::builder()
+                    .sort(doc! {"timestamp": -1})
+                    .limit(BAN_HISTORY_LIMIT)
+                    .build(),
+            )
+            .await?
+            .try_collect::<Vec<BanRecord>>()
+            .await?;
+        Ok(self.make_response(
+            Self::render(ctx.locale, user, times_banned, bans_issued, &history),
+            true,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::{
+        database::BanRecordUser,
+        testing::{InteractionBuilder, TestHarness},
+    };
+
+    fn record(user_id: i64, name: &str, nickname: Option<&str>, count: i64) -> BanCountRecord {
+        BanCountRecord {
+            user_id,
+            count,
+            display_name: name.to_string(),
+            nickname: nickname.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn renders_both_rankings() {
+        let rendered = BanTopCommand::render(
+            Locale::En,
+            &[record(1, "matija", Some("Matijoš"), 5)],
+            &[record(2, "klement", None, 3)],
+        );
+        assert_eq!(
+            rendered,
+            "**Top Banned:**\n> **1. **Matijoš: 5\n**Top Bans:**\n> **1. **klement: 3\n"
+        );
+    }
+
+    #[test]
+    fn renders_ban_history() {
+        let user: User = serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "username": "matija",
+            "discriminator": "0",
+            "global_name": "Matijoš",
+            "avatar": null,
+        }))
+        .unwrap();
+        let ban = BanRecord {
+            banned_by: BanRecordUser(2),
+            banned_user: BanRecordUser(1),
+            reason: None,
+            timestamp: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        };
+        assert_eq!(
+            BanHistoryCommand::render(Locale::En, &user, 1, 0, &[ban]),
+            "**Ban history of Matijoš**\nBanned 1 times, issued 0 bans\n> <t:1700000000:d> by <@2>: No reason\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn requires_guild() {
+        let harness = TestHarness::new().await;
+        let command = InteractionBuilder::new("bantop").without_guild().build();
+        let err = harness.dispatch(&command).await.err().unwrap();
+        assert!(matches!(err, BotError::BadInput(_)), "{}", err);
     }
 }
@@ -0,0 +1,470 @@
+use std::{sync::Arc, time::Instant};
+
+use anyhow::Result;
+#[cfg(feature = "music")]
+use serenity::all::{AutocompleteChoice, CreateCommandOption, GuildId};
+use serenity::{all::CommandInteraction, async_trait, prelude::TypeMapKey, utils::MessageBuilder};
+use tracing::{error, info};
+
+#[cfg(any(feature = "music", feature = "aoc"))]
+use crate::error::BotError;
+#[cfg(feature = "aoc")]
+use crate::guild_config::GuildModule;
+#[cfg(feature = "music")]
+use crate::{
+    commands::options::{command_options, CommandOptions},
+    music::leave_call,
+    music::SaveHandlerHandle,
+    util::truncate_chars,
+};
+use crate::{
+    commands::{
+        create_commands::register_all_commands, group::CommandGroup, registry::CommandRegistry,
+    },
+    config::{BotConfig, BotConfigHandle},
+    context::BotContext,
+    error::BotResult,
+    guild_config::{GuildConfigService, GuildConfigServiceHandle},
+    i18n::t,
+    permissions::{CommandPermissions, OwnerIds},
+    util::{
+        defer_response, format_duration, retrieve_bot_config, retrieve_db_handle, retrieve_modules,
+        retrieve_scheduler, CommandRunner, MakeCommandResponse,
+    },
+    CommandResponse,
+};
+
+#[cfg(feature = "music")]
+const GUILD_OPTION: &str = "guild";
+
+/// When the bot was started, for the uptime.
+pub(crate) struct StartedAt;
+impl TypeMapKey for StartedAt {
+    type Value = Instant;
+}
+
+pub(crate) fn register_commands(registry: &mut CommandRegistry) {
+    let group = CommandGroup::new("botadmin", "Run the bot")
+        .permissions(CommandPermissions {
+            owner_only: true,
+            ..Default::default()
+        })
+        .subcommand(StatusCommand)
+        .subcommand(ReloadConfigCommand)
+        .subcommand(RegisterCommandsCommand);
+    #[cfg(feature = "aoc")]
+    let group = group.subcommand(AocRefetchCommand);
+    #[cfg(feature = "music")]
+    let group = group.subcommand(LeaveVoiceCommand);
+    registry.register(group);
+}
+
+#[cfg(feature = "music")]
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Settings that are only read on startup, so changing them needs a restart.
+fn restart_required(old: &BotConfig, new: &BotConfig) -> Vec<&'static str> {
+    [
+        ("discord_token", old.discord_token != new.discord_token),
+        ("log_level", old.log_level != new.log_level),
+        ("log_format", old.log_format != new.log_format),
+        ("database.url", old.database_url != new.database_url),
+        ("database.name", old.database_name != new.database_name),
+        ("music.cache_dir", old.cache_dir != new.cache_dir),
+        ("music.yt_dlp_path", old.yt_dlp_path != new.yt_dlp_path),
+        (
+            "metrics.address",
+            old.metrics_address != new.metrics_address,
+        ),
+        // Modules are assembled with their commands, event hooks and jobs on startup
+        (
+            "enabled_modules",
+            old.enabled_modules != new.enabled_modules,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(setting, changed)| changed.then_some(setting))
+    .collect()
+}
+
+pub(crate) struct StatusCommand;
+impl MakeCommandResponse for StatusCommand {}
+
+impl StatusCommand {
+    #[cfg(feature = "music")]
+    async fn push_music_status(ctx: &BotContext, builder: &mut MessageBuilder) -> Result<()> {
+        let locale = ctx.locale;
+        if let Some(songbird) = ctx.songbird().await {
+            let calls = songbird.iter().collect::<Vec<_>>();
+            let mut active = 0;
+            for (_, call) in calls {
+                if call.lock().await.current_channel().is_some() {
+                    active += 1;
+                }
+            }
+            builder.push_line(t!(locale, "botadmin.voice_calls", count = active));
+        }
+        // Missing when music is turned off in the config
+        let Some(save_handler) = ctx.data.read().await.get::<SaveHandlerHandle>().cloned() else {
+            return Ok(());
+        };
+        let queued = save_handler.queued().await;
+        builder.push_line(t!(locale, "botadmin.downloads", count = queued.len()));
+        for title in queued.iter().take(10) {
+            builder.push_quote_line_safe(truncate_chars(title, 100));
+        }
+        match save_handler.cache_size().await {
+            Ok((files, bytes)) => builder.push_line(t!(
+                locale,
+                "botadmin.audio_cache",
+                files = files,
+                size = format_bytes(bytes)
+            )),
+            Err(e) => {
+                info!("Failed to read the audio cache: {:?}", e);
+                builder.push_line(t!(locale, "botadmin.audio_cache_unavailable"))
+            }
+        };
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CommandRunner for StatusCommand {
+    fn name(&self) -> &'static str {
+        "status"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show uptime, guilds, voice calls, downloads and jobs"
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        _command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let locale = ctx.locale;
+        let uptime = ctx
+            .data
+            .read()
+            .await
+            .get::<StartedAt>()
+            .map(|started_at| started_at.elapsed())
+            .unwrap_or_default();
+        let mut builder = MessageBuilder::new();
+        builder
+            .push_line(t!(
+                locale,
+                "botadmin.uptime",
+                uptime = format_duration(uptime)
+            ))
+            .push_line(t!(
+                locale,
+                "botadmin.guilds",
+                count = ctx.cache.guild_count()
+            ))
+            .push_line(t!(
+                locale,
+                "botadmin.modules",
+                modules = retrieve_modules(ctx.data.clone()).await?.names().join(", ")
+            ));
+        #[cfg(feature = "music")]
+        Self::push_music_status(ctx, &mut builder).await?;
+        builder
+            .push_line("")
+            .push_bold_line(t!(locale, "botadmin.jobs"))
+            .push(retrieve_scheduler(ctx.data.clone()).await?.render(locale));
+        Ok(self.make_response(builder.build(), true))
+    }
+}
+
+pub(crate) struct ReloadConfigCommand;
+impl MakeCommandResponse for ReloadConfigCommand {}
+
+impl ReloadConfigCommand {
+    /// Swaps in the new config and everything derived from it, then registers the commands
+    /// again so their permissions are up to date. Returns the changed settings that only apply
+    /// after a restart.
+    async fn reload(ctx: &BotContext) -> Result<Vec<&'static str>> {
+        let old = retrieve_bot_config(ctx.data.clone()).await?;
+        let new = Arc::new(BotConfig::load()?);
+        let db_handle = retrieve_db_handle(ctx.data.clone()).await?;
+        {
+            let mut data = ctx.data.write().await;
+            data.insert::<OwnerIds>(Arc::new(new.owner_ids.clone()));
+            data.insert::<GuildConfigServiceHandle>(Arc::new(GuildConfigService::new(
+                db_handle,
+                new.enabled_modules.clone(),
+            )));
+            data.insert::<BotConfigHandle>(new.clone());
+        }
+        info!("Config reloaded");
+        register_all_commands(ctx).await?;
+        Ok(restart_required(&old, &new))
+    }
+}
+
+#[async_trait]
+impl CommandRunner for ReloadConfigCommand {
+    fn name(&self) -> &'static str {
+        "reload_config"
+    }
+
+    fn description(&self) -> &'static str {
+        "Reload the config file and env, and register commands again"
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        defer_response(ctx, command).await?;
+        // The error can contain paths and values from the config, it only goes to the log
+        let restart_required = match Self::reload(ctx).await {
+            Ok(restart_required) => restart_required,
+            Err(e) => {
+                error!("Failed to reload config: {:#?}", e);
+                return Ok(self.make_response(t!(ctx.locale, "botadmin.reload_failed"), true));
+            }
+        };
+        let mut content = t!(ctx.locale, "botadmin.reloaded");
+        if !restart_required.is_empty() {
+            content.push('\n');
+            content.push_str(&t!(
+                ctx.locale,
+                "botadmin.restart_required",
+                settings = restart_required.join(", ")
+            ));
+        }
+        Ok(self.make_response(content, true))
+    }
+
+    fn has_deferred_response(&self) -> bool {
+        true
+    }
+}
+
+pub(crate) struct RegisterCommandsCommand;
+impl MakeCommandResponse for RegisterCommandsCommand {}
+
+#[async_trait]
+impl CommandRunner for RegisterCommandsCommand {
+    fn name(&self) -> &'static str {
+        "register_commands"
+    }
+
+    fn description(&self) -> &'static str {
+        "Register slash commands with Discord again"
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        defer_response(ctx, command).await?;
+        let failed = register_all_commands(ctx).await?;
+        let content = match failed {
+            0 => t!(ctx.locale, "botadmin.registered"),
+            failed => t!(ctx.locale, "botadmin.registration_failed", count = failed),
+        };
+        Ok(self.make_response(content, true))
+    }
+
+    fn has_deferred_response(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "aoc")]
+pub(crate) struct AocRefetchCommand;
+#[cfg(feature = "aoc")]
+impl MakeCommandResponse for AocRefetchCommand {}
+
+#[cfg(feature = "aoc")]
+#[async_trait]
+impl CommandRunner for AocRefetchCommand {
+    fn name(&self) -> &'static str {
+        "aoc_refetch"
+    }
+
+    fn description(&self) -> &'static str {
+        "Fetch AoC leaderboards now"
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        if !retrieve_bot_config(ctx.data.clone())
+            .await?
+            .is_module_enabled(GuildModule::Aoc)
+        {
+            return Err(BotError::bad_input(t!(ctx.locale, "botadmin.aoc_disabled")));
+        }
+        defer_response(ctx, command).await?;
+        let count = crate::aoc::fetch_all_leaderboards(ctx, true).await?;
+        Ok(self.make_response(
+            t!(ctx.locale, "botadmin.aoc_refetched", count = count),
+            true,
+        ))
+    }
+
+    fn has_deferred_response(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "music")]
+command_options! {
+    struct LeaveOptions {
+        guild: String => (GUILD_OPTION, "Guild to leave the voice channel in").autocomplete(),
+    }
+}
+
+#[cfg(feature = "music")]
+pub(crate) struct LeaveVoiceCommand;
+#[cfg(feature = "music")]
+impl MakeCommandResponse for LeaveVoiceCommand {}
+
+#[cfg(feature = "music")]
+impl LeaveVoiceCommand {
+    fn guild_name(ctx: &BotContext, guild_id: GuildId) -> String {
+        guild_id
+            .name(&ctx.cache)
+            .unwrap_or_else(|| guild_id.to_string())
+    }
+}
+
+#[cfg(feature = "music")]
+#[async_trait]
+impl CommandRunner for LeaveVoiceCommand {
+    fn name(&self) -> &'static str {
+        "leave_voice"
+    }
+
+    fn description(&self) -> &'static str {
+        "Leave the voice channel in any guild"
+    }
+
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        LeaveOptions::create_options(key)
+    }
+
+    async fn autocomplete(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> Result<Vec<AutocompleteChoice>> {
+        let input = match command.data.autocomplete() {
+            Some(option) if option.name == GUILD_OPTION => option.value.to_lowercase(),
+            _ => return Ok(Vec::new()),
+        };
+        let Some(songbird) = ctx.songbird().await else {
+            return Ok(Vec::new());
+        };
+        Ok(songbird
+            .iter()
+            .map(|(guild_id, _)| GuildId::new(guild_id.0.get()))
+            .map(|guild_id| (Self::guild_name(ctx, guild_id), guild_id))
+            .filter(|(name, guild_id)| {
+                name.to_lowercase().contains(input.trim())
+                    || guild_id.to_string().starts_with(input.trim())
+            })
+            .take(25)
+            .map(|(name, guild_id)| {
+                AutocompleteChoice::new(truncate_chars(&name, 100), guild_id.to_string())
+            })
+            .collect())
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let options = LeaveOptions::parse(command, ctx.locale)?;
+        let guild_id = options
+            .guild
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .filter(|id| *id != 0)
+            .map(GuildId::new)
+            .ok_or_else(|| {
+                BotError::bad_input(t!(
+                    ctx.locale,
+                    "botadmin.invalid_guild",
+                    guild = options.guild
+                ))
+            })?;
+        let guild = Self::guild_name(ctx, guild_id);
+        if !leave_call(ctx, guild_id).await? {
+            return Err(BotError::not_found(t!(
+                ctx.locale,
+                "botadmin.not_in_voice",
+                guild = guild
+            )));
+        }
+        Ok(self.make_response(t!(ctx.locale, "botadmin.left_voice", guild = guild), true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "music")]
+    use super::*;
+    use crate::testing::{InteractionBuilder, TestHarness};
+
+    #[cfg(feature = "music")]
+    #[test]
+    fn formats_bytes() {
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+
+    #[tokio::test]
+    async fn shows_status() {
+        let harness = TestHarness::new().await;
+        let command = InteractionBuilder::new("botadmin")
+            .subcommand("status")
+            .build();
+        let content = harness.dispatch(&command).await.unwrap().content;
+        assert!(content.contains("Servers: 0"), "{}", content);
+        assert!(content.contains("Modules: "), "{}", content);
+        assert!(content.contains("**cooldown_cleanup**"), "{}", content);
+    }
+
+    #[cfg(feature = "music")]
+    #[tokio::test]
+    async fn shows_status_with_music_disabled() {
+        let mut config = BotConfig::test();
+        config
+            .enabled_modules
+            .remove(&crate::guild_config::GuildModule::Music);
+        let harness = TestHarness::with_config(config).await;
+        let command = InteractionBuilder::new("botadmin")
+            .subcommand("status")
+            .build();
+        let content = harness.dispatch(&command).await.unwrap().content;
+        assert!(!content.contains("Downloads"), "{}", content);
+        assert!(content.contains("**cooldown_cleanup**"), "{}", content);
+    }
+}
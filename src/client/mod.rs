@@ -1,25 +1,12 @@
-use std::env;
+use std::sync::Arc;
 
-use serenity::all::{EventHandler, GatewayIntents};
-use songbird::SerenityInit;
+use crate::{event_handlers::mr_handler::MrHandler, modules::Modules};
 
-pub(crate) async fn init_serenity_client<H>(event_handlers: Vec<H>) -> serenity::Client
-where
-    H: EventHandler + 'static,
-{
-    let token = env::var("DISCORD_TOKEN").expect("DISCORD_TOKEN env variable is not defined!");
-    let gateway = GatewayIntents::GUILD_MEMBERS
-        | GatewayIntents::MESSAGE_CONTENT
-        | GatewayIntents::GUILDS
-        | GatewayIntents::GUILD_MODERATION
-        | GatewayIntents::GUILD_MESSAGES
-        | GatewayIntents::DIRECT_MESSAGES
-        | GatewayIntents::non_privileged();
-    let mut builder = serenity::Client::builder(token, gateway).register_songbird();
-
-    for handler in event_handlers {
-        builder = builder.event_handler(handler);
-    }
-
-    builder.await.expect("Error registering event handler")
+pub(crate) async fn init_serenity_client(token: &str, modules: Arc<Modules>) -> serenity::Client {
+    let builder = serenity::Client::builder(token, modules.intents());
+    modules
+        .init_client(builder)
+        .event_handler(MrHandler::new(modules))
+        .await
+        .expect("Error registering event handler")
 }
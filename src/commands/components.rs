@@ -0,0 +1,81 @@
+use std::fmt::Display;
+
+use serenity::{all::ComponentInteraction, async_trait};
+
+use crate::{context::BotContext, error::BotResult, CommandResponse};
+
+const CUSTOM_ID_SEPARATOR: char = ':';
+
+/// Custom ID of a button or select menu in the form `namespace:action:state...`.
+///
+/// The namespace routes the interaction back to the module that created the component, the
+/// state carries whatever that module needs to handle the click.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ComponentId {
+    pub(crate) namespace: String,
+    pub(crate) action: String,
+    pub(crate) state: Vec<String>,
+}
+
+// Only the music controls have buttons so far
+#[cfg_attr(not(feature = "music"), allow(dead_code))]
+impl ComponentId {
+    pub(crate) fn new(namespace: &str, action: &str) -> Self {
+        Self {
+            namespace: namespace.to_string(),
+            action: action.to_string(),
+            state: Vec::new(),
+        }
+    }
+
+    pub(crate) fn with_state(mut self, value: impl ToString) -> Self {
+        self.state.push(value.to_string());
+        self
+    }
+
+    pub(crate) fn parse(custom_id: &str) -> Option<Self> {
+        let mut parts = custom_id.split(CUSTOM_ID_SEPARATOR);
+        let namespace = parts.next().filter(|namespace| !namespace.is_empty())?;
+        let action = parts.next()?;
+        Some(Self {
+            namespace: namespace.to_string(),
+            action: action.to_string(),
+            state: parts.map(|part| part.to_string()).collect(),
+        })
+    }
+}
+
+impl Display for ComponentId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            self.namespace, CUSTOM_ID_SEPARATOR, self.action
+        )?;
+        for state in self.state.iter() {
+            write!(f, "{}{}", CUSTOM_ID_SEPARATOR, state)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(not(feature = "music"), allow(dead_code))]
+pub(crate) enum ComponentResponse {
+    /// Edits the message the component is attached to.
+    UpdateMessage(CommandResponse),
+    /// Replies with a new message.
+    Message(CommandResponse),
+    /// Acknowledges the click without changing anything.
+    Acknowledge,
+}
+
+#[async_trait]
+pub(crate) trait ComponentRunner: Send + Sync {
+    fn namespace(&self) -> &'static str;
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        interaction: &ComponentInteraction,
+        id: &ComponentId,
+    ) -> BotResult<ComponentResponse>;
+}
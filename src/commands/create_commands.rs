@@ -1,35 +1,333 @@
 use anyhow::Result;
-use log::info;
-use serenity::all::{Context, CreateCommand, Ready};
+use serde_json::{Map, Value};
+use serenity::all::{Command, CommandId, CreateCommand, GuildId, Http};
+use tracing::{error, info};
 
 use crate::{
-    aoc::{AddPrivateLeaderboardCommand, RollCommand, SetSessionCookieCommand, SpeedrunCommand},
-    bantop::BanTopCommand,
-    music::{PlayCommand, QueueCommand, SkipCommand, StopCommand},
-    util::CommandRunner,
+    context::BotContext,
+    util::{retrieve_bot_config, retrieve_command_registry},
 };
 
-pub(crate) async fn register_slash_commands(ctx: &Context, ready: &Ready) -> Result<()> {
-    for guild in ready.guilds.iter() {
-        guild
-            .id
-            .set_commands(&ctx.http, registered_commands())
-            .await?;
+/// Fields of a command that are compared when deciding whether it has to be updated.
+const COMPARED_FIELDS: &[&str] = &[
+    "type",
+    "name",
+    "name_localizations",
+    "description",
+    "description_localizations",
+    "options",
+    "default_member_permissions",
+    "dm_permission",
+    "nsfw",
+];
+const CHAT_INPUT_COMMAND_TYPE: u64 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RegistrationMode {
+    /// Commands are registered once for the whole application.
+    Global,
+    /// Commands are registered for every guild separately, updates show up instantly.
+    Guild,
+}
+
+impl RegistrationMode {
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "global" => Some(Self::Global),
+            "guild" => Some(Self::Guild),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum CommandScope {
+    Global,
+    Guild(GuildId),
+}
+
+impl CommandScope {
+    async fn get_commands(self, http: &Http) -> Result<Vec<Command>> {
+        Ok(match self {
+            Self::Global => Command::get_global_commands(http).await?,
+            Self::Guild(guild_id) => guild_id.get_commands(http).await?,
+        })
+    }
+
+    async fn create_command(self, http: &Http, command: CreateCommand) -> Result<()> {
+        match self {
+            Self::Global => {
+                Command::create_global_command(http, command).await?;
+            }
+            Self::Guild(guild_id) => {
+                guild_id.create_command(http, command).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn edit_command(self, http: &Http, id: CommandId, command: CreateCommand) -> Result<()> {
+        match self {
+            Self::Global => {
+                Command::edit_global_command(http, id, command).await?;
+            }
+            Self::Guild(guild_id) => {
+                guild_id.edit_command(http, id, command).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete_command(self, http: &Http, id: CommandId) -> Result<()> {
+        match self {
+            Self::Global => Command::delete_global_command(http, id).await?,
+            Self::Guild(guild_id) => guild_id.delete_command(http, id).await?,
+        }
+        Ok(())
+    }
+}
+
+/// Registers global commands on startup. In guild mode stale global commands are removed so
+/// they do not show up twice.
+pub(crate) async fn register_global_commands(ctx: &BotContext) -> Result<()> {
+    let commands = match retrieve_bot_config(ctx.data.clone())
+        .await?
+        .command_registration
+    {
+        RegistrationMode::Global => retrieve_command_registry(ctx.data.clone())
+            .await?
+            .create_commands(),
+        RegistrationMode::Guild => Vec::new(),
+    };
+    sync_commands(&ctx.http, CommandScope::Global, commands).await
+}
+
+/// Registers commands for a guild on `guild_create`, which also covers guilds joined after
+/// startup. In global mode stale guild commands are removed.
+pub(crate) async fn register_guild_commands(ctx: &BotContext, guild_id: GuildId) -> Result<()> {
+    let commands = match retrieve_bot_config(ctx.data.clone())
+        .await?
+        .command_registration
+    {
+        RegistrationMode::Global => Vec::new(),
+        RegistrationMode::Guild => retrieve_command_registry(ctx.data.clone())
+            .await?
+            .create_commands(),
+    };
+    sync_commands(&ctx.http, CommandScope::Guild(guild_id), commands).await
+}
+
+/// Registers commands again everywhere, e.g. after the command registry changed. Returns the
+/// number of guilds that failed.
+pub(crate) async fn register_all_commands(ctx: &BotContext) -> Result<usize> {
+    register_global_commands(ctx).await?;
+    let mut failed = 0;
+    for guild_id in ctx.cache.guilds() {
+        if let Err(e) = register_guild_commands(ctx, guild_id).await {
+            error!(%guild_id, "Failed to register commands: {:#?}", e);
+            failed += 1;
+        }
+    }
+    Ok(failed)
+}
+
+/// A change needed to bring the registered commands of a scope in line with the registry.
+enum CommandChange {
+    Create(String, CreateCommand),
+    Edit(String, CommandId, CreateCommand),
+    Delete(String, CommandId),
+}
+
+/// Compares the registered commands with `commands`, unchanged commands need no change.
+fn plan_sync(
+    mut existing: Vec<Command>,
+    commands: Vec<CreateCommand>,
+) -> Result<Vec<CommandChange>> {
+    let mut changes = Vec::new();
+    for command in commands {
+        let desired = normalize_command(serde_json::to_value(&command)?);
+        let position = existing.iter().position(|c| {
+            c.name == desired["name"] && u64::from(u8::from(c.kind)) == desired["type"]
+        });
+        match position.map(|position| existing.swap_remove(position)) {
+            Some(current) => {
+                if normalize_command(serde_json::to_value(&current)?) != desired {
+                    changes.push(CommandChange::Edit(current.name, current.id, command));
+                }
+            }
+            None => {
+                let name = desired["name"].as_str().unwrap_or_default().to_string();
+                changes.push(CommandChange::Create(name, command));
+            }
+        }
+    }
+    changes.extend(
+        existing
+            .into_iter()
+            .map(|stale| CommandChange::Delete(stale.name, stale.id)),
+    );
+    Ok(changes)
+}
+
+/// Creates, edits and deletes commands so that the scope matches `commands`, leaving unchanged
+/// commands alone.
+async fn sync_commands(
+    http: &Http,
+    scope: CommandScope,
+    commands: Vec<CreateCommand>,
+) -> Result<()> {
+    let changes = plan_sync(scope.get_commands(http).await?, commands)?;
+    let count = changes.len();
+    for change in changes {
+        match change {
+            CommandChange::Create(name, command) => {
+                info!("Creating command {} in {:?}", name, scope);
+                scope.create_command(http, command).await?;
+            }
+            CommandChange::Edit(name, id, command) => {
+                info!("Updating command {} in {:?}", name, scope);
+                scope.edit_command(http, id, command).await?;
+            }
+            CommandChange::Delete(name, id) => {
+                info!("Deleting command {} in {:?}", name, scope);
+                scope.delete_command(http, id).await?;
+            }
+        }
+    }
+    if count > 0 {
+        info!("Synced {} command changes in {:?}", count, scope);
     }
-    info!("Successfully registered slash commands");
     Ok(())
 }
 
-fn registered_commands() -> Vec<CreateCommand> {
-    vec![
-        BanTopCommand {}.register(),
-        PlayCommand {}.register(),
-        SkipCommand {}.register(),
-        StopCommand {}.register(),
-        QueueCommand {}.register(),
-        SpeedrunCommand {}.register(),
-        AddPrivateLeaderboardCommand {}.register(),
-        SetSessionCookieCommand {}.register(),
-        RollCommand {}.register(),
-    ]
+/// Reduces a serialized command to the compared fields, dropping values Discord treats as unset.
+fn normalize_command(command: Value) -> Value {
+    let mut normalized = Map::new();
+    if let Value::Object(mut fields) = command {
+        for field in COMPARED_FIELDS {
+            if let Some(value) = fields.remove(*field).and_then(strip_unset) {
+                normalized.insert(field.to_string(), value);
+            }
+        }
+    }
+    normalized
+        .entry("type")
+        .or_insert(Value::from(CHAT_INPUT_COMMAND_TYPE));
+    Value::Object(normalized)
+}
+
+fn strip_unset(value: Value) -> Option<Value> {
+    match value {
+        Value::Null | Value::Bool(false) => None,
+        // Context menu commands come back with an empty description
+        Value::String(value) if value.is_empty() => None,
+        Value::Array(values) if values.is_empty() => None,
+        Value::Object(fields) if fields.is_empty() => None,
+        Value::Array(values) => Some(Value::Array(
+            values
+                .into_iter()
+                .map(|value| strip_unset(value).unwrap_or(Value::Null))
+                .collect(),
+        )),
+        Value::Object(fields) => {
+            let fields = fields
+                .into_iter()
+                .filter_map(|(key, value)| strip_unset(value).map(|value| (key, value)))
+                .collect::<Map<String, Value>>();
+            (!fields.is_empty()).then_some(Value::Object(fields))
+        }
+        value => Some(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use serenity::all::{CommandOptionType, CreateCommandOption};
+
+    use super::*;
+
+    fn ping() -> CreateCommand {
+        CreateCommand::new("ping")
+            .description("Pings the bot")
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "message",
+                "What to reply with",
+            ))
+    }
+
+    /// The command as Discord returns it after registering `command`.
+    fn registered(command: &CreateCommand, id: u64) -> Command {
+        let mut value = serde_json::to_value(command).unwrap();
+        let fields = value.as_object_mut().unwrap();
+        fields.insert("id".into(), json!(id.to_string()));
+        fields.insert("application_id".into(), json!("1"));
+        fields.insert("version".into(), json!("1"));
+        fields.insert("type".into(), json!(CHAT_INPUT_COMMAND_TYPE));
+        fields.insert("nsfw".into(), json!(false));
+        fields.insert("default_member_permissions".into(), Value::Null);
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn summary(changes: Vec<CommandChange>) -> Vec<(&'static str, String)> {
+        changes
+            .into_iter()
+            .map(|change| match change {
+                CommandChange::Create(name, _) => ("create", name),
+                CommandChange::Edit(name, _, _) => ("edit", name),
+                CommandChange::Delete(name, _) => ("delete", name),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keeps_unchanged_commands() {
+        let changes = plan_sync(vec![registered(&ping(), 10)], vec![ping()]).unwrap();
+        assert!(summary(changes).is_empty());
+    }
+
+    #[test]
+    fn edits_changed_descriptions() {
+        let existing = registered(&ping().description("Pings"), 10);
+        let changes = plan_sync(vec![existing], vec![ping()]).unwrap();
+        assert_eq!(summary(changes), vec![("edit", "ping".to_string())]);
+    }
+
+    #[test]
+    fn edits_added_options() {
+        let desired = ping().add_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "loud",
+            "Reply in capitals",
+        ));
+        let changes = plan_sync(vec![registered(&ping(), 10)], vec![desired]).unwrap();
+        assert_eq!(summary(changes), vec![("edit", "ping".to_string())]);
+    }
+
+    #[test]
+    fn creates_new_and_deletes_removed_commands() {
+        let stale = registered(&CreateCommand::new("pong").description("Pongs"), 11);
+        let changes = plan_sync(vec![stale], vec![ping()]).unwrap();
+        assert_eq!(
+            summary(changes),
+            vec![
+                ("create", "ping".to_string()),
+                ("delete", "pong".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_fields_that_are_not_compared() {
+        let desired = serde_json::to_value(ping()).unwrap();
+        let mut existing = desired.clone();
+        let fields = existing.as_object_mut().unwrap();
+        fields.insert("id".into(), json!("10"));
+        fields.insert("guild_id".into(), json!("20"));
+        fields.insert("version".into(), json!("3"));
+        fields.insert("name_localizations".into(), json!({}));
+        fields.insert("dm_permission".into(), json!(false));
+        assert_eq!(normalize_command(existing), normalize_command(desired));
+    }
 }
@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use serenity::{all::CommandInteraction, async_trait};
+
+use crate::{
+    context::BotContext, error::BotResult, guild_config::GuildModule,
+    permissions::CommandPermissions, util::CommandRunner, CommandResponse,
+};
+
+/// Command that only groups other commands, e.g. `/aoc add` or `/music play`.
+///
+/// Nesting a group inside another group produces a Discord subcommand group.
+pub(crate) struct CommandGroup {
+    name: &'static str,
+    description: &'static str,
+    permissions: CommandPermissions,
+    module: Option<GuildModule>,
+    subcommands: Vec<Arc<dyn CommandRunner>>,
+}
+
+impl CommandGroup {
+    pub(crate) fn new(name: &'static str, description: &'static str) -> Self {
+        Self {
+            name,
+            description,
+            permissions: CommandPermissions::default(),
+            module: None,
+            subcommands: Vec::new(),
+        }
+    }
+
+    /// Permissions required for every subcommand of the group.
+    pub(crate) fn permissions(mut self, permissions: CommandPermissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    /// Module whose guild setting turns the whole group on or off.
+    #[cfg_attr(not(any(feature = "music", feature = "aoc")), allow(dead_code))]
+    pub(crate) fn module(mut self, module: GuildModule) -> Self {
+        self.module = Some(module);
+        self
+    }
+
+    pub(crate) fn subcommand(mut self, command: impl CommandRunner + 'static) -> Self {
+        self.subcommands.push(Arc::new(command));
+        self
+    }
+}
+
+#[async_trait]
+impl CommandRunner for CommandGroup {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn description(&self) -> &'static str {
+        self.description
+    }
+
+    async fn run(
+        &self,
+        _ctx: &BotContext,
+        _command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        Err(anyhow!(
+            "Command group {} must be invoked through a subcommand",
+            self.name
+        )
+        .into())
+    }
+
+    fn subcommands(&self) -> &[Arc<dyn CommandRunner>] {
+        &self.subcommands
+    }
+
+    fn permissions(&self) -> CommandPermissions {
+        self.permissions
+    }
+
+    fn module(&self) -> Option<GuildModule> {
+        self.module
+    }
+}
@@ -1,2 +1,5 @@
+pub(crate) mod components;
 pub(crate) mod create_commands;
-pub(crate) mod slash_commands;
+pub(crate) mod group;
+pub(crate) mod options;
+pub(crate) mod registry;
@@ -0,0 +1,264 @@
+use serenity::all::{
+    ChannelId, CommandDataOption, CommandDataOptionValue, CommandInteraction, CommandOptionType,
+    CreateCommandOption, RoleId, UserId,
+};
+
+use crate::{
+    error::{BotError, BotResult},
+    i18n::{localize_option, t, Locale},
+};
+
+/// Options of a command declared with [`command_options!`].
+pub(crate) trait CommandOptions: Sized {
+    /// `key` is the catalog key of the command the options belong to.
+    fn create_options(key: &str) -> Vec<CreateCommandOption>;
+    fn parse(command: &CommandInteraction, locale: Locale) -> BotResult<Self>;
+}
+
+pub(crate) enum OptionValueError {
+    Missing,
+    WrongType,
+}
+
+/// Rust types that can be read from a command option.
+pub(crate) trait OptionValue: Sized {
+    const KIND: CommandOptionType;
+    const REQUIRED: bool = true;
+
+    fn from_value(value: &CommandDataOptionValue) -> Option<Self>;
+
+    fn from_option(value: Option<&CommandDataOptionValue>) -> Result<Self, OptionValueError> {
+        Self::from_value(value.ok_or(OptionValueError::Missing)?).ok_or(OptionValueError::WrongType)
+    }
+}
+
+impl OptionValue for i64 {
+    const KIND: CommandOptionType = CommandOptionType::Integer;
+
+    fn from_value(value: &CommandDataOptionValue) -> Option<Self> {
+        value.as_i64()
+    }
+}
+
+impl OptionValue for f64 {
+    const KIND: CommandOptionType = CommandOptionType::Number;
+
+    fn from_value(value: &CommandDataOptionValue) -> Option<Self> {
+        value.as_f64()
+    }
+}
+
+impl OptionValue for bool {
+    const KIND: CommandOptionType = CommandOptionType::Boolean;
+
+    fn from_value(value: &CommandDataOptionValue) -> Option<Self> {
+        value.as_bool()
+    }
+}
+
+impl OptionValue for String {
+    const KIND: CommandOptionType = CommandOptionType::String;
+
+    fn from_value(value: &CommandDataOptionValue) -> Option<Self> {
+        value.as_str().map(str::to_string)
+    }
+}
+
+impl OptionValue for UserId {
+    const KIND: CommandOptionType = CommandOptionType::User;
+
+    fn from_value(value: &CommandDataOptionValue) -> Option<Self> {
+        value.as_user_id()
+    }
+}
+
+impl OptionValue for RoleId {
+    const KIND: CommandOptionType = CommandOptionType::Role;
+
+    fn from_value(value: &CommandDataOptionValue) -> Option<Self> {
+        value.as_role_id()
+    }
+}
+
+impl OptionValue for ChannelId {
+    const KIND: CommandOptionType = CommandOptionType::Channel;
+
+    fn from_value(value: &CommandDataOptionValue) -> Option<Self> {
+        value.as_channel_id()
+    }
+}
+
+impl<T: OptionValue> OptionValue for Option<T> {
+    const KIND: CommandOptionType = T::KIND;
+    const REQUIRED: bool = false;
+
+    fn from_value(value: &CommandDataOptionValue) -> Option<Self> {
+        T::from_value(value).map(Some)
+    }
+
+    fn from_option(value: Option<&CommandDataOptionValue>) -> Result<Self, OptionValueError> {
+        match value {
+            Some(value) => T::from_option(Some(value)).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Declaration of a single option, used both for registering and for validating it.
+pub(crate) struct OptionSpec {
+    name: &'static str,
+    description: &'static str,
+    kind: CommandOptionType,
+    required: bool,
+    autocomplete: bool,
+    range: Option<(i64, i64)>,
+    length: Option<(u16, u16)>,
+}
+
+impl OptionSpec {
+    pub(crate) fn new<T: OptionValue>(name: &'static str, description: &'static str) -> Self {
+        Self {
+            name,
+            description,
+            kind: T::KIND,
+            required: T::REQUIRED,
+            autocomplete: false,
+            range: None,
+            length: None,
+        }
+    }
+
+    pub(crate) fn autocomplete(mut self) -> Self {
+        self.autocomplete = true;
+        self
+    }
+
+    /// Inclusive range of accepted integer values.
+    #[cfg_attr(not(feature = "aoc"), allow(dead_code))]
+    pub(crate) fn range(mut self, min: i64, max: i64) -> Self {
+        self.range = Some((min, max));
+        self
+    }
+
+    /// Inclusive range of accepted string lengths in characters.
+    pub(crate) fn length(mut self, min: u16, max: u16) -> Self {
+        self.length = Some((min, max));
+        self
+    }
+
+    pub(crate) fn create(&self, key: &str) -> CreateCommandOption {
+        let mut option = CreateCommandOption::new(self.kind, self.name, self.description)
+            .required(self.required)
+            .set_autocomplete(self.autocomplete);
+        if let Some((min, max)) = self.range {
+            // Discord limits are only set when serenity can express them, parse validates anyway
+            if let (Ok(min), Ok(max)) = (u64::try_from(min), u64::try_from(max)) {
+                option = option.min_int_value(min).max_int_value(max);
+            }
+        }
+        if let Some((min, max)) = self.length {
+            option = option.min_length(min).max_length(max);
+        }
+        localize_option(option, &format!("{}.options.{}", key, self.name))
+    }
+
+    pub(crate) fn parse<T: OptionValue>(
+        &self,
+        options: &[CommandDataOption],
+        locale: Locale,
+    ) -> BotResult<T> {
+        let value = options
+            .iter()
+            .find(|option| option.name == self.name)
+            .map(|option| &option.value);
+        if let Some(value) = value {
+            self.validate(value, locale)?;
+        }
+        T::from_option(value).map_err(|err| match err {
+            OptionValueError::Missing => {
+                BotError::BadInput(t!(locale, "options.required", option = self.name))
+            }
+            OptionValueError::WrongType => {
+                BotError::BadInput(t!(locale, "options.wrong_type", option = self.name))
+            }
+        })
+    }
+
+    fn validate(&self, value: &CommandDataOptionValue, locale: Locale) -> BotResult<()> {
+        if let (Some((min, max)), Some(value)) = (self.range, value.as_i64()) {
+            if !(min..=max).contains(&value) {
+                return Err(BotError::BadInput(t!(
+                    locale,
+                    "options.out_of_range",
+                    option = self.name,
+                    min = min,
+                    max = max
+                )));
+            }
+        }
+        if let (Some((min, max)), Some(value)) = (self.length, value.as_str()) {
+            let length = value.chars().count();
+            if length < min as usize || length > max as usize {
+                return Err(BotError::BadInput(t!(
+                    locale,
+                    "options.wrong_length",
+                    option = self.name,
+                    min = min,
+                    max = max
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Declares a struct holding the options of a command and implements [`CommandOptions`] for it.
+///
+/// ```ignore
+/// command_options! {
+///     pub(crate) struct SpeedrunOptions {
+///         day: Option<i64> => (DAY_OPTION, "Speedrun for selected day").range(1, 25),
+///     }
+/// }
+/// ```
+macro_rules! command_options {
+    (
+        $vis:vis struct $name:ident {
+            $(
+                $field:ident: $ty:ty => ($option:expr, $description:expr)
+                    $(.$modifier:ident($($arg:expr),*))*
+            ),* $(,)?
+        }
+    ) => {
+        $vis struct $name {
+            $(pub(crate) $field: $ty,)*
+        }
+
+        impl $crate::commands::options::CommandOptions for $name {
+            fn create_options(key: &str) -> Vec<serenity::all::CreateCommandOption> {
+                vec![$(
+                    $crate::commands::options::OptionSpec::new::<$ty>($option, $description)
+                        $(.$modifier($($arg),*))*
+                        .create(key),
+                )*]
+            }
+
+            fn parse(
+                command: &serenity::all::CommandInteraction,
+                locale: $crate::i18n::Locale,
+            ) -> $crate::error::BotResult<Self> {
+                let options = $crate::util::command_options(command);
+                Ok(Self {
+                    $($field: $crate::commands::options::OptionSpec::new::<$ty>(
+                        $option,
+                        $description,
+                    )
+                    $(.$modifier($($arg),*))*
+                    .parse::<$ty>(options, locale)?,)*
+                })
+            }
+        }
+    };
+}
+
+pub(crate) use command_options;
@@ -0,0 +1,182 @@
+use std::sync::Arc;
+
+use serenity::{
+    all::{CommandDataOptionValue, CommandInteraction, CreateCommand},
+    prelude::TypeMapKey,
+};
+use tracing::{info, warn};
+
+use crate::{commands::components::ComponentRunner, util::CommandRunner};
+
+pub(crate) struct CommandRegistryHandle;
+impl TypeMapKey for CommandRegistryHandle {
+    type Value = Arc<CommandRegistry>;
+}
+
+/// Single source of truth for every command the bot knows about.
+///
+/// Modules contribute their commands at startup, registration with Discord and
+/// interaction dispatch both read from here.
+#[derive(Default)]
+pub(crate) struct CommandRegistry {
+    commands: Vec<Arc<dyn CommandRunner>>,
+    components: Vec<Arc<dyn ComponentRunner>>,
+}
+
+impl CommandRegistry {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn register(&mut self, command: impl CommandRunner + 'static) -> &mut Self {
+        let name = command.name();
+        if let Some(position) = self.commands.iter().position(|c| c.name() == name) {
+            warn!("Command {} registered twice, replacing previous one", name);
+            self.commands.remove(position);
+        }
+        info!("Command registered: {}", name);
+        self.commands.push(Arc::new(command));
+        self
+    }
+
+    #[cfg_attr(not(feature = "music"), allow(dead_code))]
+    pub(crate) fn register_component(
+        &mut self,
+        component: impl ComponentRunner + 'static,
+    ) -> &mut Self {
+        let namespace = component.namespace();
+        if self.get_component(namespace).is_some() {
+            warn!(
+                "Component namespace {} registered twice, replacing previous one",
+                namespace
+            );
+            self.components.retain(|c| c.namespace() != namespace);
+        }
+        info!("Component namespace registered: {}", namespace);
+        self.components.push(Arc::new(component));
+        self
+    }
+
+    pub(crate) fn get_component(&self, namespace: &str) -> Option<Arc<dyn ComponentRunner>> {
+        self.components
+            .iter()
+            .find(|c| c.namespace() == namespace)
+            .cloned()
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<Arc<dyn CommandRunner>> {
+        self.commands.iter().find(|c| c.name() == name).cloned()
+    }
+
+    /// Finds the command that should handle the interaction, descending into subcommands until
+    /// the invoked leaf command is reached.
+    pub(crate) fn resolve(&self, command: &CommandInteraction) -> Option<Arc<dyn CommandRunner>> {
+        self.resolve_path(command)?.pop()
+    }
+
+    /// Same as [`Self::resolve`], but returns every command on the way to the leaf, starting with
+    /// the top level one.
+    pub(crate) fn resolve_path(
+        &self,
+        command: &CommandInteraction,
+    ) -> Option<Vec<Arc<dyn CommandRunner>>> {
+        let path = self.find_path(&invoked_path(command))?;
+        match path.last() {
+            Some(leaf) if leaf.subcommands().is_empty() => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Looks up commands by their names, e.g. `["music", "stop"]`.
+    pub(crate) fn find_path(&self, names: &[&str]) -> Option<Vec<Arc<dyn CommandRunner>>> {
+        let (first, rest) = names.split_first()?;
+        let mut path = vec![self.get(first)?];
+        for name in rest {
+            let next = path
+                .last()?
+                .subcommands()
+                .iter()
+                .find(|subcommand| subcommand.name() == *name)?
+                .clone();
+            path.push(next);
+        }
+        Some(path)
+    }
+
+    /// Every command and subcommand path, e.g. `"aoc"` and `"aoc speedrun"`.
+    pub(crate) fn command_paths(&self) -> Vec<String> {
+        fn collect(prefix: &str, command: &Arc<dyn CommandRunner>, paths: &mut Vec<String>) {
+            let path = if prefix.is_empty() {
+                command.name().to_string()
+            } else {
+                format!("{} {}", prefix, command.name())
+            };
+            for subcommand in command.subcommands() {
+                collect(&path, subcommand, paths);
+            }
+            paths.push(path);
+        }
+        let mut paths = Vec::new();
+        for command in self.commands.iter() {
+            collect("", command, &mut paths);
+        }
+        paths.sort();
+        paths
+    }
+
+    /// Every command that can be invoked together with the commands on the way to it, e.g.
+    /// `[aoc, speedrun]`, sorted by path. Groups are left out since they only hold subcommands.
+    pub(crate) fn leaf_paths(&self) -> Vec<Vec<Arc<dyn CommandRunner>>> {
+        fn collect(
+            path: Vec<Arc<dyn CommandRunner>>,
+            paths: &mut Vec<Vec<Arc<dyn CommandRunner>>>,
+        ) {
+            let subcommands = path.last().map(|last| last.subcommands().to_vec());
+            match subcommands {
+                Some(subcommands) if !subcommands.is_empty() => {
+                    for subcommand in subcommands {
+                        let mut nested = path.clone();
+                        nested.push(subcommand);
+                        collect(nested, paths);
+                    }
+                }
+                _ => paths.push(path),
+            }
+        }
+        let mut paths = Vec::new();
+        for command in self.commands.iter() {
+            collect(vec![command.clone()], &mut paths);
+        }
+        paths.sort_by_key(|path| command_path_name(path));
+        paths
+    }
+
+    pub(crate) fn create_commands(&self) -> Vec<CreateCommand> {
+        self.commands.iter().map(|c| c.register()).collect()
+    }
+}
+
+/// Names of the invoked command and its subcommands, e.g. `["aoc", "speedrun"]`.
+pub(crate) fn invoked_path(command: &CommandInteraction) -> Vec<&str> {
+    let mut path = vec![command.data.name.as_str()];
+    let mut options = command.data.options.as_slice();
+    while let Some(option) = options.first() {
+        match &option.value {
+            CommandDataOptionValue::SubCommand(nested)
+            | CommandDataOptionValue::SubCommandGroup(nested) => {
+                path.push(option.name.as_str());
+                options = nested;
+            }
+            _ => break,
+        }
+    }
+    path
+}
+
+/// Joins the names of resolved commands, e.g. `"aoc speedrun"`.
+pub(crate) fn command_path_name(path: &[Arc<dyn CommandRunner>]) -> String {
+    path.iter()
+        .map(|command| command.name())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
@@ -0,0 +1,398 @@
+use std::{
+    collections::HashSet, env, fs, io::ErrorKind, net::SocketAddr, path::PathBuf, sync::Arc,
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serenity::{all::UserId, prelude::TypeMapKey, utils::validate_token};
+use tracing::level_filters::LevelFilter;
+
+use crate::{commands::create_commands::RegistrationMode, guild_config::GuildModule};
+
+const CONFIG_PATH_ENV: &str = "CONFIG_PATH";
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+const DEFAULT_DATABASE_NAME: &str = "papa_klement";
+const DEFAULT_LOG_LEVEL: &str = "warn";
+const DEFAULT_YT_DLP_PATH: &str = "yt-dlp";
+const CACHE_DIR_NAME: &str = "songbird_cache";
+
+/// Layout of `config.toml`, every value can also be set through the env variable noted next to
+/// it.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    /// DISCORD_TOKEN
+    discord_token: Option<String>,
+    /// LOG_LEVEL
+    log_level: Option<String>,
+    /// LOG_FORMAT
+    log_format: Option<String>,
+    /// OWNER_IDS, comma separated
+    owner_ids: Option<Vec<u64>>,
+    /// ENABLED_MODULES, comma separated
+    enabled_modules: Option<Vec<String>>,
+    /// COMMAND_REGISTRATION
+    command_registration: Option<String>,
+    database: DatabaseSection,
+    music: MusicSection,
+    metrics: MetricsSection,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DatabaseSection {
+    /// MONGO_URL
+    url: Option<String>,
+    /// MONGODB_NAME
+    name: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MusicSection {
+    /// CACHE_DIR
+    cache_dir: Option<PathBuf>,
+    /// YT_DLP_PATH
+    yt_dlp_path: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MetricsSection {
+    /// METRICS_ADDRESS
+    address: Option<String>,
+}
+
+/// How log lines are written to stdout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LogFormat {
+    Pretty,
+    Json,
+}
+
+impl LogFormat {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "pretty" => Some(Self::Pretty),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) struct BotConfigHandle;
+impl TypeMapKey for BotConfigHandle {
+    type Value = Arc<BotConfig>;
+}
+
+/// Validated bot configuration, loaded once on startup.
+#[derive(Clone, Debug)]
+pub(crate) struct BotConfig {
+    pub(crate) discord_token: String,
+    pub(crate) log_level: LevelFilter,
+    pub(crate) log_format: LogFormat,
+    pub(crate) owner_ids: HashSet<UserId>,
+    pub(crate) enabled_modules: HashSet<GuildModule>,
+    pub(crate) command_registration: RegistrationMode,
+    pub(crate) database_url: String,
+    pub(crate) database_name: String,
+    pub(crate) cache_dir: PathBuf,
+    pub(crate) yt_dlp_path: String,
+    /// Address of the `/healthz` and `/metrics` listener, disabled when unset.
+    pub(crate) metrics_address: Option<SocketAddr>,
+}
+
+impl BotConfig {
+    /// Reads the config file from `CONFIG_PATH` or `config.toml` and applies env overrides.
+    /// A missing default config file is fine as long as the env covers the required values.
+    pub(crate) fn load() -> Result<Self> {
+        let (path, explicit) = match env::var(CONFIG_PATH_ENV) {
+            Ok(path) => (path, true),
+            Err(_) => (DEFAULT_CONFIG_PATH.to_string(), false),
+        };
+        let file = match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str::<ConfigFile>(&contents)
+                .with_context(|| format!("Invalid config file {}", path))?,
+            Err(e) if e.kind() == ErrorKind::NotFound && !explicit => ConfigFile::default(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read config file {}", path))
+            }
+        };
+        Self::from_sources(file, |name| env::var(name).ok())
+    }
+
+    /// Env values take precedence over the file. Every invalid value is reported, not just the
+    /// first one.
+    fn from_sources(file: ConfigFile, env: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut errors = Vec::new();
+
+        let discord_token = env("DISCORD_TOKEN")
+            .or(file.discord_token)
+            .unwrap_or_default();
+        if discord_token.is_empty() {
+            errors.push("discord_token (DISCORD_TOKEN) is required".to_string());
+        } else if validate_token(&discord_token).is_err() {
+            errors.push("discord_token (DISCORD_TOKEN) is not a valid bot token".to_string());
+        }
+
+        let log_level = env("LOG_LEVEL")
+            .or(file.log_level)
+            .unwrap_or_else(|| DEFAULT_LOG_LEVEL.to_string());
+        let log_level = log_level.parse::<LevelFilter>().unwrap_or_else(|_| {
+            errors.push(format!(
+                "log_level (LOG_LEVEL) must be one of off, error, warn, info, debug or trace, got {}",
+                log_level
+            ));
+            LevelFilter::WARN
+        });
+
+        let log_format = match env("LOG_FORMAT").or(file.log_format) {
+            Some(format) => LogFormat::from_name(&format).unwrap_or_else(|| {
+                errors.push(format!(
+                    "log_format (LOG_FORMAT) must be pretty or json, got {}",
+                    format
+                ));
+                LogFormat::Pretty
+            }),
+            None => LogFormat::Pretty,
+        };
+
+        let owner_ids = match env("OWNER_IDS") {
+            Some(ids) => split_list(&ids)
+                .filter_map(|id| match id.parse::<u64>() {
+                    Ok(id) => Some(id),
+                    Err(_) => {
+                        errors.push(format!("owner_ids (OWNER_IDS) contains invalid ID {}", id));
+                        None
+                    }
+                })
+                .collect(),
+            None => file.owner_ids.unwrap_or_default(),
+        };
+        let owner_ids = owner_ids
+            .into_iter()
+            .filter_map(|id| {
+                if id == 0 {
+                    errors.push("owner_ids (OWNER_IDS) cannot contain 0".to_string());
+                }
+                (id != 0).then(|| UserId::new(id))
+            })
+            .collect();
+
+        let enabled_modules = match env("ENABLED_MODULES") {
+            Some(modules) => Some(split_list(&modules).map(str::to_string).collect()),
+            None => file.enabled_modules,
+        };
+        let enabled_modules = match enabled_modules {
+            Some(modules) => modules
+                .iter()
+                .filter_map(|module| {
+                    let parsed = GuildModule::from_name(module);
+                    if parsed.is_none() {
+                        errors.push(format!(
+                            "enabled_modules (ENABLED_MODULES) contains unknown module {}, known modules are {}",
+                            module,
+                            GuildModule::ALL
+                                .iter()
+                                .map(GuildModule::to_string)
+                                .collect::<Vec<String>>()
+                                .join(", ")
+                        ));
+                    }
+                    parsed
+                })
+                .collect(),
+            None => GuildModule::ALL.iter().copied().collect(),
+        };
+
+        let command_registration = match env("COMMAND_REGISTRATION").or(file.command_registration) {
+            Some(mode) => RegistrationMode::from_name(&mode).unwrap_or_else(|| {
+                errors.push(format!(
+                    "command_registration (COMMAND_REGISTRATION) must be global or guild, got {}",
+                    mode
+                ));
+                RegistrationMode::Guild
+            }),
+            None => RegistrationMode::Guild,
+        };
+
+        let database_url = env("MONGO_URL").or(file.database.url).unwrap_or_default();
+        if database_url.is_empty() {
+            errors.push("database.url (MONGO_URL) is required".to_string());
+        } else if !database_url.starts_with("mongodb://")
+            && !database_url.starts_with("mongodb+srv://")
+        {
+            errors.push(
+                "database.url (MONGO_URL) must start with mongodb:// or mongodb+srv://".to_string(),
+            );
+        }
+
+        let database_name = env("MONGODB_NAME")
+            .or(file.database.name)
+            .unwrap_or_else(|| DEFAULT_DATABASE_NAME.to_string());
+        if database_name.is_empty()
+            || database_name.len() > 63
+            || database_name.contains(['/', '\\', '.', ' ', '"', '$'])
+        {
+            errors.push(format!(
+                "database.name (MONGODB_NAME) is not a valid MongoDB database name: {:?}",
+                database_name
+            ));
+        }
+
+        let cache_dir = match env("CACHE_DIR").map(PathBuf::from).or(file.music.cache_dir) {
+            Some(cache_dir) => cache_dir,
+            None => match env("HOME") {
+                Some(home) => PathBuf::from(home).join(CACHE_DIR_NAME),
+                None => {
+                    errors.push(
+                        "music.cache_dir (CACHE_DIR) is required when HOME is not set".to_string(),
+                    );
+                    PathBuf::new()
+                }
+            },
+        };
+
+        let yt_dlp_path = env("YT_DLP_PATH")
+            .or(file.music.yt_dlp_path)
+            .unwrap_or_else(|| DEFAULT_YT_DLP_PATH.to_string());
+        if yt_dlp_path.trim().is_empty() {
+            errors.push("music.yt_dlp_path (YT_DLP_PATH) cannot be empty".to_string());
+        }
+
+        let metrics_address = env("METRICS_ADDRESS")
+            .or(file.metrics.address)
+            .filter(|address| !address.is_empty())
+            .and_then(|address| match address.parse::<SocketAddr>() {
+                Ok(address) => Some(address),
+                Err(_) => {
+                    errors.push(format!(
+                        "metrics.address (METRICS_ADDRESS) must be an address like 127.0.0.1:9100, got {}",
+                        address
+                    ));
+                    None
+                }
+            });
+
+        if !errors.is_empty() {
+            bail!("Invalid configuration:\n  {}", errors.join("\n  "));
+        }
+        Ok(Self {
+            discord_token,
+            log_level,
+            log_format,
+            owner_ids,
+            enabled_modules,
+            command_registration,
+            database_url,
+            database_name,
+            cache_dir,
+            yt_dlp_path,
+            metrics_address,
+        })
+    }
+
+    pub(crate) fn is_module_enabled(&self, module: GuildModule) -> bool {
+        self.enabled_modules.contains(&module)
+    }
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+#[cfg(test)]
+impl BotConfig {
+    /// Configuration with every module enabled, nothing in it is ever connected to.
+    pub(crate) fn test() -> Self {
+        Self {
+            discord_token: String::new(),
+            log_level: LevelFilter::OFF,
+            log_format: LogFormat::Pretty,
+            owner_ids: HashSet::new(),
+            enabled_modules: GuildModule::ALL.iter().copied().collect(),
+            command_registration: RegistrationMode::Guild,
+            database_url: "mongodb://localhost:27017".to_string(),
+            database_name: "papa_klement_test".to_string(),
+            cache_dir: env::temp_dir().join(CACHE_DIR_NAME),
+            yt_dlp_path: DEFAULT_YT_DLP_PATH.to_string(),
+            metrics_address: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    // Structurally valid, not a real token
+    #[cfg(feature = "music")]
+    const TOKEN: &str = "MTAwMDAwMDAwMDAwMDAwMDAw.GAAAAA.AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+
+    fn load(file: &str, env: &[(&str, &str)]) -> Result<BotConfig> {
+        let env = env
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect::<HashMap<String, String>>();
+        BotConfig::from_sources(toml::from_str(file)?, |name| env.get(name).cloned())
+    }
+
+    #[test]
+    #[cfg(feature = "music")]
+    fn env_overrides_file() {
+        let config = load(
+            r#"
+            discord_token = "invalid"
+            owner_ids = [1, 2]
+            enabled_modules = ["music"]
+
+            [database]
+            url = "mongodb://db"
+            name = "from_file"
+            "#,
+            &[
+                ("DISCORD_TOKEN", TOKEN),
+                ("MONGODB_NAME", "from_env"),
+                ("LOG_FORMAT", "json"),
+                ("HOME", "/home/bot"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.database_name, "from_env");
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(config.owner_ids.len(), 2);
+        assert!(config.is_module_enabled(GuildModule::Music));
+        #[cfg(feature = "aoc")]
+        assert!(!config.is_module_enabled(GuildModule::Aoc));
+        assert_eq!(config.cache_dir, PathBuf::from("/home/bot/songbird_cache"));
+    }
+
+    #[test]
+    fn reports_every_invalid_value() {
+        let err = load(
+            "log_level = \"loud\"",
+            &[
+                ("ENABLED_MODULES", "music, karaoke"),
+                ("METRICS_ADDRESS", "localhost"),
+                ("HOME", "/home/bot"),
+            ],
+        )
+        .err()
+        .unwrap()
+        .to_string();
+        assert!(err.contains("DISCORD_TOKEN"), "{}", err);
+        assert!(err.contains("MONGO_URL"), "{}", err);
+        assert!(err.contains("LOG_LEVEL"), "{}", err);
+        assert!(err.contains("karaoke"), "{}", err);
+        assert!(err.contains("METRICS_ADDRESS"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(load("discord_tokn = \"typo\"", &[]).is_err());
+    }
+}
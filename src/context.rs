@@ -0,0 +1,88 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use serenity::{
+    all::{ActivityData, Cache, Context, Http, OnlineStatus, ShardMessenger},
+    prelude::{RwLock, TypeMap},
+};
+#[cfg(feature = "music")]
+use songbird::{serenity::SongbirdKey, Songbird};
+
+use crate::i18n::Locale;
+
+/// The parts of serenity's [`Context`] used by commands and components.
+///
+/// Unlike [`Context`] it can be built without a gateway connection, which is what lets commands
+/// run in tests.
+#[derive(Clone)]
+pub(crate) struct BotContext {
+    pub(crate) data: Arc<RwLock<TypeMap>>,
+    pub(crate) http: Arc<Http>,
+    pub(crate) cache: Arc<Cache>,
+    /// Language of replies to the interaction being handled.
+    pub(crate) locale: Locale,
+    /// Set once the interaction being handled has a deferred response.
+    deferred: Arc<AtomicBool>,
+    shard: Option<ShardMessenger>,
+}
+
+impl BotContext {
+    /// Context that is not connected to a shard, presence updates are ignored.
+    pub(crate) fn detached(data: Arc<RwLock<TypeMap>>, http: Arc<Http>, cache: Arc<Cache>) -> Self {
+        Self {
+            data,
+            http,
+            cache,
+            locale: Locale::default(),
+            deferred: Arc::default(),
+            shard: None,
+        }
+    }
+
+    pub(crate) fn with_locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    pub(crate) fn mark_deferred(&self) {
+        self.deferred.store(true, Ordering::SeqCst);
+    }
+
+    pub(crate) fn is_deferred(&self) -> bool {
+        self.deferred.load(Ordering::SeqCst)
+    }
+
+    #[cfg(feature = "music")]
+    pub(crate) async fn songbird(&self) -> Option<Arc<Songbird>> {
+        self.data.read().await.get::<SongbirdKey>().cloned()
+    }
+
+    #[cfg_attr(not(feature = "music"), allow(dead_code))]
+    pub(crate) fn set_activity(&self, activity: Option<ActivityData>) {
+        if let Some(shard) = self.shard.as_ref() {
+            shard.set_activity(activity);
+        }
+    }
+
+    #[cfg_attr(not(feature = "music"), allow(dead_code))]
+    pub(crate) fn set_presence(&self, activity: Option<ActivityData>, status: OnlineStatus) {
+        if let Some(shard) = self.shard.as_ref() {
+            shard.set_presence(activity, status);
+        }
+    }
+}
+
+impl From<&Context> for BotContext {
+    fn from(ctx: &Context) -> Self {
+        Self {
+            data: ctx.data.clone(),
+            http: ctx.http.clone(),
+            cache: ctx.cache.clone(),
+            locale: Locale::default(),
+            deferred: Arc::default(),
+            shard: Some(ctx.shard.clone()),
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use chrono::Utc;
+use mongodb::{
+    bson::doc,
+    error::{ErrorKind, WriteFailure},
+    options::FindOneAndUpdateOptions,
+    Collection, Database,
+};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{GuildId, UserId},
+    async_trait,
+    prelude::TypeMapKey,
+};
+use tracing::info;
+
+use crate::{
+    context::BotContext,
+    scheduler::{Job, Schedule},
+    util::retrieve_cooldown_service,
+};
+
+const COOLDOWNS_COLLECTION: &str = "cooldowns";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum CooldownBucket {
+    #[cfg_attr(not(feature = "music"), allow(dead_code))]
+    User,
+    #[cfg_attr(not(feature = "aoc"), allow(dead_code))]
+    Guild,
+    #[cfg_attr(not(feature = "banaj"), allow(dead_code))]
+    Global,
+}
+
+/// Allows `uses` invocations per `per` in the given bucket.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Cooldown {
+    pub(crate) bucket: CooldownBucket,
+    pub(crate) uses: u32,
+    pub(crate) per: Duration,
+}
+
+impl Cooldown {
+    #[cfg_attr(
+        not(any(feature = "music", feature = "aoc", feature = "banaj")),
+        allow(dead_code)
+    )]
+    pub(crate) const fn new(bucket: CooldownBucket, uses: u32, per: Duration) -> Self {
+        Self { bucket, uses, per }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct CooldownRecord {
+    #[serde(rename = "_id")]
+    id: String,
+    window_start: i64,
+    uses: u32,
+    /// End of the window, records past it are removed by [`CooldownCleanupJob`].
+    #[serde(default)]
+    expires_at: Option<i64>,
+}
+
+pub(crate) struct CooldownServiceHandle;
+impl TypeMapKey for CooldownServiceHandle {
+    type Value = Arc<CooldownService>;
+}
+
+/// Fixed window rate limiter stored in Mongo so limits survive restarts.
+pub(crate) struct CooldownService {
+    db_handle: Database,
+}
+
+impl CooldownService {
+    pub(crate) fn new(db_handle: Database) -> Self {
+        Self { db_handle }
+    }
+
+    fn get_collection(&self) -> Collection<CooldownRecord> {
+        self.db_handle.collection(COOLDOWNS_COLLECTION)
+    }
+
+    fn bucket_id(
+        key: &str,
+        cooldown: &Cooldown,
+        guild_id: Option<GuildId>,
+        user_id: UserId,
+    ) -> String {
+        match cooldown.bucket {
+            CooldownBucket::User => format!("{}:user:{}", key, user_id.get()),
+            CooldownBucket::Guild => match guild_id {
+                Some(guild_id) => format!("{}:guild:{}", key, guild_id.get()),
+                // Outside of a guild the user is the closest thing to a guild bucket
+                None => format!("{}:user:{}", key, user_id.get()),
+            },
+            CooldownBucket::Global => format!("{}:global", key),
+        }
+    }
+
+    /// Takes a use from the bucket in a single update: a new window is started if the current
+    /// one expired, otherwise the use is counted as long as the window is not full. Returns the
+    /// remaining wait when the window is full.
+    async fn acquire_one(
+        &self,
+        id: &str,
+        cooldown: &Cooldown,
+        now: i64,
+    ) -> Result<Option<Duration>> {
+        let per = cooldown.per.as_secs() as i64;
+        let expired = doc! {"$lte": ["$window_start", now - per]};
+        let result = self
+            .get_collection()
+            .find_one_and_update(
+                doc! {
+                    "_id": id,
+                    "$or": [
+                        {"window_start": {"$lte": now - per}},
+                        {"uses": {"$lt": cooldown.uses}},
+                    ],
+                },
+                vec![doc! {"$set": {
+                    "window_start": {"$cond": [&expired, now, "$window_start"]},
+                    "uses": {"$cond": [&expired, 1, {"$add": ["$uses", 1]}]},
+                    "expires_at": {"$cond": [&expired, now + per, "$expires_at"]},
+                }}],
+                FindOneAndUpdateOptions::builder().upsert(true).build(),
+            )
+            .await;
+        match result {
+            Ok(_) => Ok(None),
+            // The upsert only inserts when the filter found nothing, so a duplicate key means the
+            // window exists and is full
+            Err(e) if is_duplicate_key(&e) => {
+                let record = self
+                    .get_collection()
+                    .find_one(doc! {"_id": id}, None)
+                    .await?;
+                Ok(record.and_then(|record| {
+                    remaining_wait(record.window_start, record.uses, cooldown, now)
+                }))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Gives back a use taken by [`Self::acquire`].
+    async fn release_one(&self, id: &str) -> Result<()> {
+        self.get_collection()
+            .update_one(
+                doc! {"_id": id, "uses": {"$gt": 0}},
+                doc! {"$inc": {"uses": -1}},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Takes a use from every bucket unless one of the cooldowns is exhausted, in which case the
+    /// uses taken so far are given back and the remaining wait is returned.
+    pub(crate) async fn acquire(
+        &self,
+        key: &str,
+        cooldowns: &[Cooldown],
+        guild_id: Option<GuildId>,
+        user_id: UserId,
+    ) -> Result<Option<Duration>> {
+        let now = Utc::now().timestamp();
+        for (index, cooldown) in cooldowns.iter().enumerate() {
+            let id = Self::bucket_id(key, cooldown, guild_id, user_id);
+            if let Some(remaining) = self.acquire_one(&id, cooldown, now).await? {
+                self.release(key, &cooldowns[..index], guild_id, user_id)
+                    .await?;
+                return Ok(Some(remaining));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Gives back the uses taken by [`Self::acquire`], e.g. when the command failed.
+    pub(crate) async fn release(
+        &self,
+        key: &str,
+        cooldowns: &[Cooldown],
+        guild_id: Option<GuildId>,
+        user_id: UserId,
+    ) -> Result<()> {
+        for cooldown in cooldowns {
+            self.release_one(&Self::bucket_id(key, cooldown, guild_id, user_id))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Remaining wait of a window that started at `window_start` and counted `uses`, `None` when
+/// another use fits.
+fn remaining_wait(window_start: i64, uses: u32, cooldown: &Cooldown, now: i64) -> Option<Duration> {
+    let window_end = window_start + cooldown.per.as_secs() as i64;
+    (now < window_end && uses >= cooldown.uses)
+        .then(|| Duration::from_secs((window_end - now) as u64))
+}
+
+fn is_duplicate_key(err: &mongodb::error::Error) -> bool {
+    const DUPLICATE_KEY: i32 = 11000;
+    match err.kind.as_ref() {
+        ErrorKind::Command(err) => err.code == DUPLICATE_KEY,
+        ErrorKind::Write(WriteFailure::WriteError(err)) => err.code == DUPLICATE_KEY,
+        _ => false,
+    }
+}
+
+/// Removes expired cooldown windows once a day.
+pub(crate) struct CooldownCleanupJob;
+
+#[async_trait]
+impl Job for CooldownCleanupJob {
+    fn name(&self) -> &'static str {
+        "cooldown_cleanup"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::cron("0 0 4 * * *").expect("Cleanup schedule must be valid")
+    }
+
+    async fn run(&self, ctx: &BotContext) -> Result<()> {
+        let service = retrieve_cooldown_service(ctx.data.clone()).await?;
+        let deleted = service
+            .get_collection()
+            .delete_many(doc! {"expires_at": {"$lt": Utc::now().timestamp()}}, None)
+            .await?;
+        info!("Removed {} expired cooldowns", deleted.deleted_count);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Cooldown = Cooldown::new(CooldownBucket::User, 3, Duration::from_secs(60));
+
+    #[test]
+    fn allows_uses_up_to_the_limit() {
+        assert_eq!(remaining_wait(1000, 0, &COOLDOWN, 1010), None);
+        assert_eq!(remaining_wait(1000, 2, &COOLDOWN, 1010), None);
+        assert_eq!(
+            remaining_wait(1000, 3, &COOLDOWN, 1010),
+            Some(Duration::from_secs(50))
+        );
+    }
+
+    #[test]
+    fn expires_windows() {
+        assert_eq!(
+            remaining_wait(1000, 3, &COOLDOWN, 1059),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(remaining_wait(1000, 3, &COOLDOWN, 1060), None);
+        assert_eq!(remaining_wait(1000, 10, &COOLDOWN, 2000), None);
+    }
+}
@@ -1,28 +1,30 @@
-use std::env;
-
+use anyhow::{Context, Result};
 use mongodb::{options::ClientOptions, Database};
-use songbird::typemap::TypeMapKey;
+#[cfg(any(feature = "bans", feature = "banaj"))]
+use serde::{Deserialize, Serialize};
+use serenity::prelude::TypeMapKey;
 
-pub(crate) const MONGODB_NAME: &str = "papa_klement";
+use crate::config::BotConfig;
 
 pub(crate) struct MongoDatabaseHandle;
 impl TypeMapKey for MongoDatabaseHandle {
     type Value = Database;
 }
 
-pub(crate) async fn init_database() -> Database {
-    let mut mongo_client_options =
-        ClientOptions::parse(env::var("MONGO_URL").expect("MONGO_URL is required!"))
-            .await
-            .unwrap();
+/// User ID as stored in ban records.
+#[cfg(any(feature = "bans", feature = "banaj"))]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct BanRecordUser(pub(crate) i64);
+
+pub(crate) async fn init_database(config: &BotConfig) -> Result<Database> {
+    let mut mongo_client_options = ClientOptions::parse(&config.database_url)
+        .await
+        .context("Invalid database.url (MONGO_URL)")?;
     if mongo_client_options.app_name.is_none() {
         mongo_client_options.app_name = Some("Papa_Klement".to_string());
     }
-    let mongo_client = mongodb::Client::with_options(mongo_client_options).expect("Mongo client");
+    let mongo_client = mongodb::Client::with_options(mongo_client_options)
+        .context("Failed to create Mongo client")?;
 
-    mongo_client.database(
-        &env::var("MONGODB_NAME")
-            .ok()
-            .unwrap_or(MONGODB_NAME.to_string()),
-    )
+    Ok(mongo_client.database(&config.database_name))
 }
@@ -0,0 +1,90 @@
+use std::fmt::Display;
+
+use rand::{distributions::Alphanumeric, Rng};
+use tracing::error;
+
+use crate::i18n::{t, Locale};
+
+const CORRELATION_ID_LENGTH: usize = 8;
+
+pub(crate) type BotResult<T> = std::result::Result<T, BotError>;
+
+/// Error returned from commands and components.
+///
+/// User-facing variants are replied to as is, everything else is [`BotError::Internal`] and only
+/// shows a correlation ID that can be looked up in the log.
+///
+/// Intentionally does not implement [`std::error::Error`] so that any error can be converted with
+/// `?` into [`BotError::Internal`].
+#[derive(Debug)]
+pub(crate) enum BotError {
+    BadInput(String),
+    #[cfg_attr(not(feature = "music"), allow(dead_code))]
+    NotInVoice,
+    MissingPermission(String),
+    NotFound(String),
+    Internal(anyhow::Error),
+}
+
+impl BotError {
+    pub(crate) fn bad_input(message: impl Into<String>) -> Self {
+        Self::BadInput(message.into())
+    }
+
+    pub(crate) fn not_found(message: impl Into<String>) -> Self {
+        Self::NotFound(message.into())
+    }
+
+    /// Short name of the variant, used as a metrics label.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Self::BadInput(_) => "bad_input",
+            Self::NotInVoice => "not_in_voice",
+            Self::MissingPermission(_) => "missing_permission",
+            Self::NotFound(_) => "not_found",
+            Self::Internal(_) => "internal",
+        }
+    }
+
+    /// Logs the error if needed and returns the message shown to the user.
+    pub(crate) fn reply(&self, locale: Locale, context: &str) -> String {
+        match self {
+            Self::Internal(err) => {
+                let correlation_id = correlation_id();
+                error!(
+                    "[{}] Error handling {}: {:#?}",
+                    correlation_id, context, err
+                );
+                t!(locale, "errors.internal", id = correlation_id)
+            }
+            Self::NotInVoice => t!(locale, "errors.not_in_voice"),
+            user_facing => user_facing.to_string(),
+        }
+    }
+}
+
+impl Display for BotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadInput(message) => write!(f, "{}", message),
+            Self::NotInVoice => write!(f, "You have to be connected to a voice channel"),
+            Self::MissingPermission(message) => write!(f, "{}", message),
+            Self::NotFound(message) => write!(f, "{}", message),
+            Self::Internal(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl<E: Into<anyhow::Error>> From<E> for BotError {
+    fn from(err: E) -> Self {
+        Self::Internal(err.into())
+    }
+}
+
+fn correlation_id() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(CORRELATION_ID_LENGTH)
+        .map(char::from)
+        .collect()
+}
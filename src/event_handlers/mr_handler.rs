@@ -1,55 +1,187 @@
+use std::{sync::Arc, time::Instant};
+
 use anyhow::Result;
-use log::{error, warn};
 use serenity::{
     all::{
-        CommandInteraction, Context, CreateInteractionResponse, CreateInteractionResponseFollowup,
-        CreateInteractionResponseMessage, EventHandler, GuildId, GuildMemberUpdateEvent,
-        Interaction, Member, Message, Ready, User,
+        CommandInteraction, ComponentInteraction, Context, CreateAutocompleteResponse,
+        CreateInteractionResponse, CreateInteractionResponseMessage, EventHandler, Guild, GuildId,
+        GuildMemberUpdateEvent, Interaction, Member, Message, Ready, User,
     },
     async_trait,
 };
+use tracing::{error, info, info_span, warn, Instrument, Span};
 
 use crate::{
-    commands::{create_commands::register_slash_commands, slash_commands::SlashCommands},
+    commands::{
+        components::{ComponentId, ComponentResponse},
+        create_commands::{register_global_commands, register_guild_commands},
+        registry::command_path_name,
+    },
+    context::BotContext,
+    error::BotError,
+    i18n::{interaction_locale, t},
+    modules::Modules,
+    permissions::check_permissions,
+    util::{
+        format_duration, retrieve_command_registry, retrieve_cooldown_service,
+        retrieve_guild_config_service, retrieve_metrics,
+    },
     CommandResponse,
 };
 
 // Name credits to Fabian Benc
-pub(crate) struct MrHandler;
+pub(crate) struct MrHandler {
+    modules: Arc<Modules>,
+}
+
+/// Span covering everything done for one interaction, `command` is replaced with the full
+/// command path once it is resolved.
+fn interaction_span(interaction: &Interaction) -> Span {
+    match interaction {
+        Interaction::Command(command) | Interaction::Autocomplete(command) => info_span!(
+            "interaction",
+            kind = if matches!(interaction, Interaction::Command(_)) {
+                "command"
+            } else {
+                "autocomplete"
+            },
+            interaction_id = %command.id,
+            guild_id = command.guild_id.map(|guild_id| guild_id.get()),
+            user_id = %command.user.id,
+            command = %command.data.name,
+        ),
+        Interaction::Component(component) => info_span!(
+            "interaction",
+            kind = "component",
+            interaction_id = %component.id,
+            guild_id = component.guild_id.map(|guild_id| guild_id.get()),
+            user_id = %component.user.id,
+            command = %component.data.custom_id,
+        ),
+        _ => info_span!("interaction", kind = "other", interaction_id = %interaction.id()),
+    }
+}
 
 impl MrHandler {
+    pub(crate) fn new(modules: Arc<Modules>) -> Self {
+        Self { modules }
+    }
+
     async fn handle_application_command(
         &self,
-        ctx: &Context,
+        ctx: &BotContext,
         command: CommandInteraction,
     ) -> Result<()> {
-        let slash_command = command
-            .data
-            .name
-            .as_str()
-            .parse::<SlashCommands>()?
-            .get_command();
-
-        let response = match slash_command.run(ctx, &command).await {
-            Ok(c) => c,
-            Err(err) => {
-                error!("Error handling slash command: {:#?}", err);
-                CommandResponse::new(format!("Error: {:#?}", err), false, false)
+        let path = retrieve_command_registry(ctx.data.clone())
+            .await?
+            .resolve_path(&command)
+            .ok_or_else(|| anyhow::anyhow!("Unknown command: {}", command.data.name))?;
+        let slash_command = path
+            .last()
+            .ok_or_else(|| anyhow::anyhow!("Unknown command: {}", command.data.name))?;
+        let path_name = command_path_name(&path);
+        Span::current().record("command", path_name.as_str());
+        let metrics = retrieve_metrics(ctx.data.clone()).await?;
+
+        if let Some(denied) = check_permissions(
+            ctx,
+            command.guild_id,
+            command.member.as_deref(),
+            command.user.id,
+            &path,
+        )
+        .await?
+        {
+            metrics.command_rejected(&path_name, "denied");
+            return self
+                .respond_ephemeral(
+                    ctx,
+                    &command,
+                    BotError::MissingPermission(denied).reply(ctx.locale, &path_name),
+                )
+                .await;
+        }
+
+        if let (Some(guild_id), Some(module)) = (
+            command.guild_id,
+            path.iter().find_map(|command| command.module()),
+        ) {
+            if !retrieve_guild_config_service(ctx.data.clone())
+                .await?
+                .is_enabled(guild_id, module)
+                .await?
+            {
+                metrics.command_rejected(&path_name, "disabled");
+                return self
+                    .respond_ephemeral(
+                        ctx,
+                        &command,
+                        t!(ctx.locale, "commands.module_disabled", module = module),
+                    )
+                    .await;
             }
-        };
+        }
 
-        if slash_command.has_deferred_response() {
-            command
-                .create_followup(
-                    &ctx.http,
-                    CreateInteractionResponseFollowup::new().content(response.content),
+        let cooldowns = slash_command.cooldowns();
+        let cooldown_service = retrieve_cooldown_service(ctx.data.clone()).await?;
+        if let Some(remaining) = cooldown_service
+            .acquire(&path_name, &cooldowns, command.guild_id, command.user.id)
+            .await?
+        {
+            metrics.command_rejected(&path_name, "cooldown");
+            return self
+                .respond_ephemeral(
+                    ctx,
+                    &command,
+                    t!(
+                        ctx.locale,
+                        "commands.cooldown",
+                        remaining = format_duration(remaining)
+                    ),
                 )
-                .await?;
+                .await;
+        }
+
+        let started = Instant::now();
+        let result = slash_command.run(ctx, &command).await;
+        metrics.observe_command(&path_name, started.elapsed(), &result);
+        // Failed invocations do not count against the cooldown
+        if result.is_err() {
+            if let Err(e) = cooldown_service
+                .release(&path_name, &cooldowns, command.guild_id, command.user.id)
+                .await
+            {
+                error!("Failed to release cooldown of {}: {:?}", path_name, e);
+            }
+        }
+        let response = match result {
+            Ok(c) => c,
+            Err(err) => CommandResponse::new(
+                err.reply(ctx.locale, &format!("/{}", path_name)),
+                true,
+                false,
+            ),
+        };
+
+        // Commands can fail before they get to defer, those still need an initial response
+        if slash_command.has_deferred_response() && ctx.is_deferred() {
+            // The first followup replaces the deferred response and keeps its visibility, so an
+            // ephemeral reply has to replace the public "thinking" message with a new one
+            if response.ephemeral {
+                if let Err(e) = command.delete_response(&ctx.http).await {
+                    warn!(
+                        "Failed to delete deferred response of {}: {:?}",
+                        path_name, e
+                    );
+                }
+            }
+            for followup in response.into_followups() {
+                command.create_followup(&ctx.http, followup).await?;
+            }
         } else {
-            let message = CreateInteractionResponseMessage::new()
-                .content(response.content)
-                .ephemeral(response.ephemeral);
-            let interaction_response = if response.is_deferred {
+            let is_deferred = response.is_deferred;
+            let (message, followups) = response.into_messages();
+            let interaction_response = if is_deferred {
                 CreateInteractionResponse::Defer(message)
             } else {
                 CreateInteractionResponse::Message(message)
@@ -57,6 +189,93 @@ impl MrHandler {
             command
                 .create_response(&ctx.http, interaction_response)
                 .await?;
+            for followup in followups {
+                command.create_followup(&ctx.http, followup).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn respond_ephemeral(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+        content: String,
+    ) -> Result<()> {
+        command
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn handle_autocomplete(
+        &self,
+        ctx: &BotContext,
+        command: CommandInteraction,
+    ) -> Result<()> {
+        let slash_command = retrieve_command_registry(ctx.data.clone())
+            .await?
+            .resolve(&command)
+            .ok_or_else(|| anyhow::anyhow!("Unknown command: {}", command.data.name))?;
+
+        let choices = slash_command.autocomplete(ctx, &command).await?;
+        command
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::Autocomplete(
+                    CreateAutocompleteResponse::new().set_choices(choices),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn handle_component(
+        &self,
+        ctx: &BotContext,
+        component: ComponentInteraction,
+    ) -> Result<()> {
+        let id = ComponentId::parse(&component.data.custom_id).ok_or_else(|| {
+            anyhow::anyhow!("Invalid component custom ID: {}", component.data.custom_id)
+        })?;
+        let runner = retrieve_command_registry(ctx.data.clone())
+            .await?
+            .get_component(&id.namespace)
+            .ok_or_else(|| anyhow::anyhow!("Unknown component namespace: {}", id.namespace))?;
+
+        let (interaction_response, followups) = match runner.run(ctx, &component, &id).await {
+            Ok(ComponentResponse::UpdateMessage(response)) => {
+                let (message, followups) = response.into_messages();
+                (CreateInteractionResponse::UpdateMessage(message), followups)
+            }
+            Ok(ComponentResponse::Message(response)) => {
+                let (message, followups) = response.into_messages();
+                (CreateInteractionResponse::Message(message), followups)
+            }
+            Ok(ComponentResponse::Acknowledge) => {
+                (CreateInteractionResponse::Acknowledge, Vec::new())
+            }
+            Err(err) => (
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(err.reply(ctx.locale, &format!("component {}", id)))
+                        .ephemeral(true),
+                ),
+                Vec::new(),
+            ),
+        };
+        component
+            .create_response(&ctx.http, interaction_response)
+            .await?;
+        for followup in followups {
+            component.create_followup(&ctx.http, followup).await?;
         }
         Ok(())
     }
@@ -65,12 +284,44 @@ impl MrHandler {
 #[async_trait]
 impl EventHandler for MrHandler {
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
-        if let Interaction::Command(command) = interaction {
-            match self.handle_application_command(&ctx, command).await {
-                Ok(_) => {}
-                Err(e) => error!("Application command error: {}", e),
+        let span = interaction_span(&interaction);
+        let ctx = BotContext::from(&ctx);
+        let ctx = match &interaction {
+            Interaction::Command(command) | Interaction::Autocomplete(command) => {
+                let locale = interaction_locale(&ctx, command.guild_id, &command.locale).await;
+                ctx.with_locale(locale)
+            }
+            Interaction::Component(component) => {
+                let locale = interaction_locale(&ctx, component.guild_id, &component.locale).await;
+                ctx.with_locale(locale)
+            }
+            _ => ctx,
+        };
+        async move {
+            match interaction {
+                Interaction::Command(command) => {
+                    match self.handle_application_command(&ctx, command).await {
+                        Ok(_) => {}
+                        Err(e) => error!("Application command error: {}", e),
+                    }
+                }
+                Interaction::Autocomplete(command) => {
+                    match self.handle_autocomplete(&ctx, command).await {
+                        Ok(_) => {}
+                        Err(e) => error!("Autocomplete error: {}", e),
+                    }
+                }
+                Interaction::Component(component) => {
+                    match self.handle_component(&ctx, component).await {
+                        Ok(_) => {}
+                        Err(e) => error!("Component interaction error: {}", e),
+                    }
+                }
+                _ => {}
             }
         }
+        .instrument(span)
+        .await
     }
 
     async fn guild_member_update(
@@ -78,54 +329,99 @@ impl EventHandler for MrHandler {
         ctx: Context,
         _old: Option<Member>,
         new: Option<Member>,
-        _event: GuildMemberUpdateEvent,
+        event: GuildMemberUpdateEvent,
     ) {
-        if let Some(new) = new {
-            match self.save_member_roles_on_update(&ctx, &new).await {
-                Ok(_) => (),
-                Err(e) => error!("Guild member update error: {}", e),
-            };
-        } else {
-            warn!("Guild Member Update called without new member in update");
-        }
+        let span = info_span!(
+            "guild_member_update",
+            guild_id = %event.guild_id,
+            user_id = %event.user.id,
+        );
+        let Some(new) = new else {
+            span.in_scope(|| warn!("Guild Member Update called without new member in update"));
+            return;
+        };
+        self.modules
+            .dispatch("Guild member update", |module| {
+                module.guild_member_update(&ctx, &new)
+            })
+            .instrument(span)
+            .await
     }
 
-    async fn guild_member_addition(&self, ctx: Context, mut new: Member) {
-        match self.grant_roles_and_nickname(&ctx, &mut new).await {
-            Ok(_) => (),
-            Err(e) => error!("Guild member addition error: {}", e),
-        };
+    async fn guild_member_addition(&self, ctx: Context, new: Member) {
+        let span = info_span!(
+            "guild_member_addition",
+            guild_id = %new.guild_id,
+            user_id = %new.user.id,
+        );
+        self.modules
+            .dispatch("Guild member addition", |module| {
+                module.guild_member_addition(&ctx, &new)
+            })
+            .instrument(span)
+            .await
     }
+
     async fn guild_ban_addition(&self, ctx: Context, guild_id: GuildId, banned_user: User) {
-        match self.unban(&ctx, &guild_id, &banned_user).await {
-            Ok(_) => (),
-            Err(e) => error!("Guild ban addition error: {}", e),
-        };
+        let span = info_span!(
+            "guild_ban_addition",
+            guild_id = %guild_id,
+            user_id = %banned_user.id,
+        );
+        self.modules
+            .dispatch("Guild ban addition", |module| {
+                module.guild_ban_addition(&ctx, guild_id, &banned_user)
+            })
+            .instrument(span)
+            .await
     }
 
-    async fn cache_ready(&self, ctx: Context, _guilds: Vec<GuildId>) {
-        log::info!("Cache ready");
-        match self.save_roles_on_startup(&ctx).await {
-            Ok(_) => (),
-            Err(e) => error!("Save roles on startup error: {}", e),
-        };
+    async fn cache_ready(&self, ctx: Context, guilds: Vec<GuildId>) {
+        let span = info_span!("cache_ready", guilds = guilds.len());
+        span.in_scope(|| info!("Cache ready"));
+        self.modules
+            .dispatch("Cache ready", |module| module.cache_ready(&ctx, &guilds))
+            .instrument(span)
+            .await
     }
 
     async fn message(&self, ctx: Context, message: Message) {
-        match self.banaj_matijosa(&ctx, &message).await {
-            Ok(_) => (),
-            Err(e) => error!("Banaj Matijoša error: {}", e),
-        };
+        let span = info_span!(
+            "message",
+            guild_id = message.guild_id.map(|guild_id| guild_id.get()),
+            user_id = %message.author.id,
+            message_id = %message.id,
+        );
+        self.modules
+            .dispatch("Message", |module| module.message(&ctx, &message))
+            .instrument(span)
+            .await
     }
 
     async fn ready(&self, ctx: Context, ready: Ready) {
-        log::info!("Ready");
-        println!("Bot started");
-        match register_slash_commands(&ctx, &ready).await {
-            Ok(_) => {}
-            Err(e) => {
-                error!("Ready error: {:#?}", e);
-            }
-        };
+        let span = info_span!("ready", user_id = %ready.user.id, guilds = ready.guilds.len());
+        async move {
+            info!("Bot started");
+            match register_global_commands(&BotContext::from(&ctx)).await {
+                Ok(_) => {}
+                Err(e) => {
+                    error!("Ready error: {:#?}", e);
+                }
+            };
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn guild_create(&self, ctx: Context, guild: Guild, _is_new: Option<bool>) {
+        let span = info_span!("guild_create", guild_id = %guild.id);
+        async move {
+            match register_guild_commands(&BotContext::from(&ctx), guild.id).await {
+                Ok(_) => {}
+                Err(e) => error!("Guild create error: {:#?}", e),
+            };
+        }
+        .instrument(span)
+        .await
     }
 }
@@ -0,0 +1,687 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use mongodb::{
+    bson::{doc, Document},
+    options::ReplaceOptions,
+    Collection, Database,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serenity::{
+    all::{AutocompleteChoice, CommandInteraction, CreateCommandOption, GuildId, Permissions},
+    async_trait,
+    prelude::TypeMapKey,
+    utils::MessageBuilder,
+};
+use tokio::sync::RwLock;
+use tracing::info;
+
+#[cfg(feature = "aoc")]
+use crate::aoc::AocConfig;
+#[cfg(feature = "banaj")]
+use crate::banaj_matijosa::BanajMatijosaConfig;
+#[cfg(feature = "music")]
+use crate::music::MusicConfig;
+#[cfg(feature = "roles")]
+use crate::roles::RolesConfig;
+#[cfg(feature = "bans")]
+use crate::unban::UnbanConfig;
+use crate::{
+    commands::{
+        group::CommandGroup,
+        options::{command_options, CommandOptions},
+        registry::CommandRegistry,
+    },
+    context::BotContext,
+    error::{BotError, BotResult},
+    i18n::{t, Locale},
+    permissions::CommandPermissions,
+    util::{
+        format_duration, retrieve_guild_config_service, truncate_chars, CommandRunner,
+        MakeCommandResponse,
+    },
+    CommandResponse,
+};
+
+const GUILD_CONFIG_COLLECTION: &str = "guild_config";
+
+const SETTING_OPTION: &str = "setting";
+const VALUE_OPTION: &str = "value";
+
+/// Parts of the bot that can be turned off per guild. Modules left out of the build by their
+/// cargo feature are never enabled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum GuildModule {
+    #[cfg(feature = "music")]
+    Music,
+    #[cfg(feature = "aoc")]
+    Aoc,
+    #[cfg(feature = "banaj")]
+    BanajMatijosa,
+    #[cfg(feature = "bans")]
+    Unban,
+    #[cfg(feature = "roles")]
+    Roles,
+}
+
+impl GuildModule {
+    /// Modules compiled into the bot.
+    pub(crate) const ALL: &'static [Self] = &[
+        #[cfg(feature = "music")]
+        Self::Music,
+        #[cfg(feature = "aoc")]
+        Self::Aoc,
+        #[cfg(feature = "banaj")]
+        Self::BanajMatijosa,
+        #[cfg(feature = "bans")]
+        Self::Unban,
+        #[cfg(feature = "roles")]
+        Self::Roles,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "music")]
+            Self::Music => "music",
+            #[cfg(feature = "aoc")]
+            Self::Aoc => "aoc",
+            #[cfg(feature = "banaj")]
+            Self::BanajMatijosa => "banaj_matijosa",
+            #[cfg(feature = "bans")]
+            Self::Unban => "unban",
+            #[cfg(feature = "roles")]
+            Self::Roles => "roles",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|module| module.name().eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+impl fmt::Display for GuildModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct GuildConfig {
+    #[serde(rename = "_id")]
+    guild_id: i64,
+    /// Used when the bot does not speak the user's language and for messages outside of
+    /// interactions.
+    #[serde(default)]
+    pub(crate) language: Locale,
+    #[cfg(feature = "music")]
+    #[serde(default)]
+    pub(crate) music: MusicConfig,
+    #[cfg(feature = "aoc")]
+    #[serde(default)]
+    pub(crate) aoc: AocConfig,
+    #[cfg(feature = "banaj")]
+    #[serde(default)]
+    pub(crate) banaj_matijosa: BanajMatijosaConfig,
+    #[cfg(feature = "bans")]
+    #[serde(default)]
+    pub(crate) unban: UnbanConfig,
+    #[cfg(feature = "roles")]
+    #[serde(default)]
+    pub(crate) roles: RolesConfig,
+    /// Settings of modules left out of this build, kept so saving does not drop them.
+    #[serde(flatten)]
+    other: Document,
+}
+
+impl GuildConfig {
+    /// Configuration of a guild that never changed any setting.
+    pub(crate) fn new(guild_id: GuildId) -> Self {
+        Self {
+            guild_id: guild_id.get() as i64,
+            language: Locale::default(),
+            #[cfg(feature = "music")]
+            music: MusicConfig::default(),
+            #[cfg(feature = "aoc")]
+            aoc: AocConfig::default(),
+            #[cfg(feature = "banaj")]
+            banaj_matijosa: BanajMatijosaConfig::default_for(guild_id),
+            #[cfg(feature = "bans")]
+            unban: UnbanConfig::default(),
+            #[cfg(feature = "roles")]
+            roles: RolesConfig::default(),
+            other: Document::new(),
+        }
+    }
+
+    pub(crate) fn is_enabled(&self, module: GuildModule) -> bool {
+        match module {
+            #[cfg(feature = "music")]
+            GuildModule::Music => self.music.enabled,
+            #[cfg(feature = "aoc")]
+            GuildModule::Aoc => self.aoc.enabled,
+            #[cfg(feature = "banaj")]
+            GuildModule::BanajMatijosa => self.banaj_matijosa.enabled,
+            #[cfg(feature = "bans")]
+            GuildModule::Unban => self.unban.enabled,
+            #[cfg(feature = "roles")]
+            GuildModule::Roles => self.roles.enabled,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    fn get(&self, setting: &Setting) -> Result<Value> {
+        serde_json::to_value(self)?
+            .pointer(&setting.pointer())
+            .cloned()
+            .ok_or_else(|| anyhow!("Setting {} missing from guild config", setting.key))
+    }
+
+    /// Copy of the config with `setting` replaced, the value is checked against the schema.
+    fn with(&self, setting: &Setting, value: Value) -> Result<Self> {
+        let mut config = serde_json::to_value(self)?;
+        *config
+            .pointer_mut(&setting.pointer())
+            .ok_or_else(|| anyhow!("Setting {} missing from guild config", setting.key))? = value;
+        Ok(serde_json::from_value(config)?)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum SettingKind {
+    Language,
+    #[cfg_attr(
+        not(any(
+            feature = "music",
+            feature = "aoc",
+            feature = "banaj",
+            feature = "bans"
+        )),
+        allow(dead_code)
+    )]
+    Toggle,
+    #[cfg_attr(not(feature = "music"), allow(dead_code))]
+    Seconds,
+    #[cfg_attr(not(feature = "banaj"), allow(dead_code))]
+    Text,
+    #[cfg_attr(not(feature = "banaj"), allow(dead_code))]
+    User,
+    #[cfg_attr(not(feature = "banaj"), allow(dead_code))]
+    Users,
+}
+
+/// Descriptions are in the catalog under `config.settings.<key>`.
+struct Setting {
+    key: &'static str,
+    kind: SettingKind,
+}
+
+impl Setting {
+    const fn new(key: &'static str, kind: SettingKind) -> Self {
+        Self { key, kind }
+    }
+
+    fn find(key: &str, locale: Locale) -> BotResult<&'static Self> {
+        let key = key.trim();
+        SETTINGS
+            .iter()
+            .find(|setting| setting.key.eq_ignore_ascii_case(key))
+            .ok_or_else(|| BotError::not_found(t!(locale, "config.unknown_setting", setting = key)))
+    }
+
+    fn description(&self, locale: Locale) -> String {
+        t!(locale, &format!("config.settings.{}", self.key))
+    }
+
+    fn pointer(&self) -> String {
+        format!("/{}", self.key.replace('.', "/"))
+    }
+
+    fn parse(&self, input: &str, locale: Locale) -> BotResult<Value> {
+        let input = input.trim();
+        let invalid = |expected: &str| {
+            BotError::BadInput(t!(
+                locale,
+                "config.invalid_value",
+                setting = self.key,
+                expected = t!(locale, &format!("config.expects.{}", expected))
+            ))
+        };
+        match self.kind {
+            SettingKind::Language => Locale::from_name(input)
+                .map(|locale| Value::from(locale.code()))
+                .ok_or_else(|| invalid("language")),
+            SettingKind::Toggle => match input.to_lowercase().as_str() {
+                "on" | "true" | "yes" | "enable" | "enabled" => Ok(Value::Bool(true)),
+                "off" | "false" | "no" | "disable" | "disabled" => Ok(Value::Bool(false)),
+                _ => Err(invalid("toggle")),
+            },
+            SettingKind::Seconds => input
+                .parse::<u64>()
+                .map(Value::from)
+                .map_err(|_| invalid("seconds")),
+            SettingKind::Text if input.is_empty() => Err(invalid("text")),
+            SettingKind::Text => Ok(Value::from(input)),
+            SettingKind::User if input.eq_ignore_ascii_case("none") => Ok(Value::Null),
+            SettingKind::User => parse_user_id(input)
+                .map(Value::from)
+                .ok_or_else(|| invalid("user")),
+            SettingKind::Users if input.eq_ignore_ascii_case("none") => Ok(Value::Array(vec![])),
+            SettingKind::Users => input
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|id| !id.is_empty())
+                .map(|id| parse_user_id(id).map(Value::from))
+                .collect::<Option<Vec<Value>>>()
+                .map(Value::Array)
+                .ok_or_else(|| invalid("users")),
+        }
+    }
+
+    fn format(&self, value: &Value) -> String {
+        match (self.kind, value) {
+            (SettingKind::Toggle, Value::Bool(true)) => "on".to_string(),
+            (SettingKind::Toggle, Value::Bool(false)) => "off".to_string(),
+            (SettingKind::Seconds, Value::Number(seconds)) => {
+                format_duration(Duration::from_secs(seconds.as_u64().unwrap_or_default()))
+            }
+            (SettingKind::Language | SettingKind::Text, Value::String(text)) => text.clone(),
+            (SettingKind::User, Value::Number(id)) => format!("<@{}>", id),
+            (SettingKind::Users, Value::Array(ids)) if !ids.is_empty() => ids
+                .iter()
+                .map(|id| format!("<@{}>", id))
+                .collect::<Vec<String>>()
+                .join(", "),
+            _ => "none".to_string(),
+        }
+    }
+}
+
+const SETTINGS: &[Setting] = &[
+    Setting::new("language", SettingKind::Language),
+    #[cfg(feature = "music")]
+    Setting::new("music.enabled", SettingKind::Toggle),
+    #[cfg(feature = "music")]
+    Setting::new("music.disconnect_after", SettingKind::Seconds),
+    #[cfg(feature = "aoc")]
+    Setting::new("aoc.enabled", SettingKind::Toggle),
+    #[cfg(feature = "banaj")]
+    Setting::new("banaj_matijosa.enabled", SettingKind::Toggle),
+    #[cfg(feature = "banaj")]
+    Setting::new("banaj_matijosa.target_user", SettingKind::User),
+    #[cfg(feature = "banaj")]
+    Setting::new("banaj_matijosa.emoji", SettingKind::Text),
+    #[cfg(feature = "banaj")]
+    Setting::new("banaj_matijosa.allowed_members", SettingKind::Users),
+    #[cfg(feature = "bans")]
+    Setting::new("unban.enabled", SettingKind::Toggle),
+    #[cfg(feature = "bans")]
+    Setting::new("unban.send_invite", SettingKind::Toggle),
+    #[cfg(feature = "roles")]
+    Setting::new("roles.enabled", SettingKind::Toggle),
+];
+
+/// Accepts raw IDs as well as user mentions.
+fn parse_user_id(input: &str) -> Option<i64> {
+    let id = input
+        .trim_start_matches("<@")
+        .trim_start_matches('!')
+        .trim_end_matches('>');
+    match id.parse::<i64>() {
+        Ok(id) if id > 0 => Some(id),
+        _ => None,
+    }
+}
+
+pub(crate) struct GuildConfigServiceHandle;
+impl TypeMapKey for GuildConfigServiceHandle {
+    type Value = Arc<GuildConfigService>;
+}
+
+/// Loads guild configs from Mongo and keeps them cached, every message and command reads them.
+pub(crate) struct GuildConfigService {
+    db_handle: Database,
+    /// Modules enabled in the bot config, a guild can only turn these on.
+    enabled_modules: HashSet<GuildModule>,
+    cache: RwLock<HashMap<GuildId, Arc<GuildConfig>>>,
+}
+
+impl GuildConfigService {
+    pub(crate) fn new(db_handle: Database, enabled_modules: HashSet<GuildModule>) -> Self {
+        Self {
+            db_handle,
+            enabled_modules,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) async fn is_enabled(&self, guild_id: GuildId, module: GuildModule) -> Result<bool> {
+        Ok(self.enabled_modules.contains(&module) && self.get(guild_id).await?.is_enabled(module))
+    }
+
+    fn get_collection(&self) -> Collection<GuildConfig> {
+        self.db_handle.collection(GUILD_CONFIG_COLLECTION)
+    }
+
+    pub(crate) async fn get(&self, guild_id: GuildId) -> Result<Arc<GuildConfig>> {
+        if let Some(config) = self.cache.read().await.get(&guild_id) {
+            return Ok(config.clone());
+        }
+        let config = Arc::new(
+            self.get_collection()
+                .find_one(doc! {"_id": guild_id.get() as i64}, None)
+                .await?
+                .unwrap_or_else(|| GuildConfig::new(guild_id)),
+        );
+        self.cache.write().await.insert(guild_id, config.clone());
+        Ok(config)
+    }
+
+    pub(crate) async fn save(&self, config: GuildConfig) -> Result<Arc<GuildConfig>> {
+        self.get_collection()
+            .replace_one(
+                doc! {"_id": config.guild_id},
+                &config,
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        info!("Saved guild config: {:#?}", config);
+        let config = Arc::new(config);
+        self.cache
+            .write()
+            .await
+            .insert(GuildId::new(config.guild_id as u64), config.clone());
+        Ok(config)
+    }
+
+    pub(crate) async fn reset(&self, guild_id: GuildId) -> Result<()> {
+        self.get_collection()
+            .delete_one(doc! {"_id": guild_id.get() as i64}, None)
+            .await?;
+        self.cache.write().await.remove(&guild_id);
+        Ok(())
+    }
+}
+
+pub(crate) fn register_commands(registry: &mut CommandRegistry) {
+    registry.register(
+        CommandGroup::new("config", "Configure the bot for this server")
+            .permissions(CommandPermissions {
+                default_member_permissions: Some(Permissions::MANAGE_GUILD),
+                ..Default::default()
+            })
+            .subcommand(ConfigGetCommand)
+            .subcommand(ConfigSetCommand)
+            .subcommand(ConfigResetCommand),
+    );
+}
+
+command_options! {
+    struct GetOptions {
+        setting: Option<String> => (SETTING_OPTION, "Setting, shows all when empty").autocomplete(),
+    }
+}
+
+command_options! {
+    struct SetOptions {
+        setting: String => (SETTING_OPTION, "Setting").autocomplete(),
+        value: String => (VALUE_OPTION, "New value"),
+    }
+}
+
+command_options! {
+    struct ResetOptions {
+        setting: Option<String> => (SETTING_OPTION, "Setting, resets all when empty")
+            .autocomplete(),
+    }
+}
+
+fn autocomplete_setting(ctx: &BotContext, command: &CommandInteraction) -> Vec<AutocompleteChoice> {
+    let input = match command.data.autocomplete() {
+        Some(option) if option.name == SETTING_OPTION => option.value.to_lowercase(),
+        _ => return Vec::new(),
+    };
+    SETTINGS
+        .iter()
+        .filter(|setting| setting.key.contains(input.trim()))
+        .map(|setting| {
+            AutocompleteChoice::new(
+                truncate_chars(
+                    &format!("{}: {}", setting.key, setting.description(ctx.locale)),
+                    100,
+                ),
+                setting.key,
+            )
+        })
+        .collect()
+}
+
+fn require_guild(ctx: &BotContext, command: &CommandInteraction) -> BotResult<GuildId> {
+    command
+        .guild_id
+        .ok_or_else(|| BotError::bad_input(t!(ctx.locale, "errors.guild_only")))
+}
+
+fn render_settings(config: &GuildConfig, settings: &[&Setting]) -> Result<String> {
+    let mut builder = MessageBuilder::new();
+    for setting in settings {
+        builder
+            .push_mono(setting.key)
+            .push_line(format!(": {}", setting.format(&config.get(setting)?)));
+    }
+    Ok(builder.build())
+}
+
+pub(crate) struct ConfigGetCommand;
+impl MakeCommandResponse for ConfigGetCommand {}
+
+#[async_trait]
+impl CommandRunner for ConfigGetCommand {
+    fn name(&self) -> &'static str {
+        "get"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show the configuration of this server"
+    }
+
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        GetOptions::create_options(key)
+    }
+
+    async fn autocomplete(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> Result<Vec<AutocompleteChoice>> {
+        Ok(autocomplete_setting(ctx, command))
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let guild_id = require_guild(ctx, command)?;
+        let options = GetOptions::parse(command, ctx.locale)?;
+        let settings = match options.setting.as_deref() {
+            Some(key) => vec![Setting::find(key, ctx.locale)?],
+            None => SETTINGS.iter().collect(),
+        };
+        let config = retrieve_guild_config_service(ctx.data.clone())
+            .await?
+            .get(guild_id)
+            .await?;
+        Ok(self.make_response(render_settings(&config, &settings)?, true))
+    }
+}
+
+pub(crate) struct ConfigSetCommand;
+impl MakeCommandResponse for ConfigSetCommand {}
+
+#[async_trait]
+impl CommandRunner for ConfigSetCommand {
+    fn name(&self) -> &'static str {
+        "set"
+    }
+
+    fn description(&self) -> &'static str {
+        "Change a setting for this server"
+    }
+
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        SetOptions::create_options(key)
+    }
+
+    async fn autocomplete(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> Result<Vec<AutocompleteChoice>> {
+        Ok(autocomplete_setting(ctx, command))
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let guild_id = require_guild(ctx, command)?;
+        let options = SetOptions::parse(command, ctx.locale)?;
+        let setting = Setting::find(&options.setting, ctx.locale)?;
+        let value = setting.parse(&options.value, ctx.locale)?;
+        let config_service = retrieve_guild_config_service(ctx.data.clone()).await?;
+        let config = config_service.get(guild_id).await?.with(setting, value)?;
+        let config = config_service.save(config).await?;
+        Ok(self.make_response(render_settings(&config, &[setting])?, true))
+    }
+}
+
+pub(crate) struct ConfigResetCommand;
+impl MakeCommandResponse for ConfigResetCommand {}
+
+#[async_trait]
+impl CommandRunner for ConfigResetCommand {
+    fn name(&self) -> &'static str {
+        "reset"
+    }
+
+    fn description(&self) -> &'static str {
+        "Restore the default of a setting or of the whole configuration"
+    }
+
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        ResetOptions::create_options(key)
+    }
+
+    async fn autocomplete(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> Result<Vec<AutocompleteChoice>> {
+        Ok(autocomplete_setting(ctx, command))
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let guild_id = require_guild(ctx, command)?;
+        let options = ResetOptions::parse(command, ctx.locale)?;
+        let config_service = retrieve_guild_config_service(ctx.data.clone()).await?;
+        match options.setting.as_deref() {
+            Some(key) => {
+                let setting = Setting::find(key, ctx.locale)?;
+                let default = GuildConfig::new(guild_id).get(setting)?;
+                let config = config_service.get(guild_id).await?.with(setting, default)?;
+                let config = config_service.save(config).await?;
+                Ok(self.make_response(render_settings(&config, &[setting])?, true))
+            }
+            None => {
+                config_service.reset(guild_id).await?;
+                Ok(self.make_response(t!(ctx.locale, "config.reset"), true))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{InteractionBuilder, TestHarness};
+
+    #[cfg(all(feature = "music", feature = "banaj"))]
+    #[test]
+    fn updates_settings_through_the_schema() {
+        let config = GuildConfig::new(GuildId::new(1));
+        let setting = Setting::find("music.disconnect_after", Locale::En).unwrap();
+        let config = config
+            .with(setting, setting.parse("90", Locale::En).unwrap())
+            .unwrap();
+        assert_eq!(config.music.disconnect_after, 90);
+
+        let setting = Setting::find("banaj_matijosa.allowed_members", Locale::En).unwrap();
+        let config = config
+            .with(setting, setting.parse("<@12>, 34", Locale::En).unwrap())
+            .unwrap();
+        assert_eq!(config.banaj_matijosa.allowed_members, [12, 34]);
+        assert_eq!(
+            setting.format(&config.get(setting).unwrap()),
+            "<@12>, <@34>"
+        );
+    }
+
+    #[test]
+    fn keeps_settings_of_modules_left_out() {
+        let stored = doc! {
+            "_id": 1_i64,
+            "language": "hr",
+            "karaoke": {"enabled": true},
+        };
+        let config: GuildConfig = mongodb::bson::from_document(stored).unwrap();
+        assert_eq!(config.language, Locale::Hr);
+        let saved = mongodb::bson::to_document(&config).unwrap();
+        assert_eq!(
+            saved.get_document("karaoke").unwrap(),
+            &doc! {"enabled": true}
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        let parse = |key: &str, input: &str| {
+            Setting::find(key, Locale::En)
+                .unwrap()
+                .parse(input, Locale::En)
+        };
+        #[cfg(feature = "music")]
+        assert!(parse("music.enabled", "maybe").is_err());
+        #[cfg(feature = "music")]
+        assert!(parse("music.disconnect_after", "-1").is_err());
+        #[cfg(feature = "banaj")]
+        assert!(parse("banaj_matijosa.target_user", "someone").is_err());
+        assert!(parse("language", "de").is_err());
+        assert_eq!(parse("language", "HR").unwrap(), Value::from("hr"));
+    }
+
+    #[tokio::test]
+    async fn set_rejects_unknown_setting() {
+        let harness = TestHarness::new().await;
+        let command = InteractionBuilder::new("config")
+            .subcommand("set")
+            .option(SETTING_OPTION, "music.volume")
+            .option(VALUE_OPTION, "11")
+            .build();
+        let err = harness.dispatch(&command).await.err().unwrap();
+        assert!(matches!(err, BotError::NotFound(_)), "{}", err);
+    }
+}
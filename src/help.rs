@@ -0,0 +1,449 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use serenity::{
+    all::{
+        AutocompleteChoice, CommandInteraction, CommandOption, CommandOptionType, CommandType,
+        CreateCommandOption,
+    },
+    async_trait,
+    utils::MessageBuilder,
+};
+
+use crate::{
+    commands::{
+        options::{command_options, CommandOptions},
+        registry::{command_path_name, CommandRegistry},
+    },
+    context::BotContext,
+    cooldowns::{Cooldown, CooldownBucket},
+    error::{BotError, BotResult},
+    i18n::{command_key, command_text, t, Locale},
+    permissions::CommandAccess,
+    util::{
+        format_duration, retrieve_command_registry, retrieve_guild_config_service, truncate_chars,
+        CommandRunner, MakeCommandResponse,
+    },
+    CommandResponse,
+};
+
+const COMMAND_OPTION: &str = "command";
+
+pub(crate) fn register_commands(registry: &mut CommandRegistry) {
+    registry.register(HelpCommand);
+}
+
+command_options! {
+    struct HelpOptions {
+        command: Option<String> => (COMMAND_OPTION, "Command to show the usage of").autocomplete(),
+    }
+}
+
+/// A command and every command on the way to it, described from its definition so the help can
+/// not get out of date.
+struct CommandHelp<'a> {
+    locale: Locale,
+    path: &'a [Arc<dyn CommandRunner>],
+}
+
+impl<'a> CommandHelp<'a> {
+    fn new(locale: Locale, path: &'a [Arc<dyn CommandRunner>]) -> Self {
+        Self { locale, path }
+    }
+
+    fn command(&self) -> &Arc<dyn CommandRunner> {
+        self.path.last().expect("Command path must not be empty")
+    }
+
+    /// Catalog key of the command, e.g. `aoc.speedrun`.
+    fn key(&self) -> String {
+        self.path
+            .iter()
+            .map(|command| command_key(command.name()))
+            .collect::<Vec<String>>()
+            .join(".")
+    }
+
+    /// Path as the user sees it in Discord, e.g. `aoc speedrun`.
+    fn name(&self) -> String {
+        (1..=self.path.len())
+            .map(|length| {
+                let help = Self::new(self.locale, &self.path[..length]);
+                command_text(self.locale, &help.key(), "name")
+                    .unwrap_or(help.command().name())
+                    .to_string()
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    fn description(&self) -> &str {
+        command_text(self.locale, &self.key(), "description")
+            .unwrap_or(self.command().description())
+    }
+
+    /// Slash commands are invoked with a leading `/`, context menu commands by their name.
+    fn invocation(&self) -> String {
+        match self.kind_label() {
+            Some(kind) => format!("{} ({})", self.name(), kind),
+            None => format!("/{}", self.name()),
+        }
+    }
+
+    fn kind_label(&self) -> Option<String> {
+        match self.path.first()?.kind() {
+            CommandType::User => Some(t!(self.locale, "help.kinds.user")),
+            CommandType::Message => Some(t!(self.locale, "help.kinds.message")),
+            _ => None,
+        }
+    }
+
+    /// Options as registered with Discord, read back from the builders.
+    fn options(&self) -> Vec<CommandOption> {
+        if self.path[0].kind() != CommandType::ChatInput {
+            return Vec::new();
+        }
+        self.command()
+            .options(&self.key())
+            .into_iter()
+            .filter_map(|option: CreateCommandOption| {
+                serde_json::to_value(option)
+                    .and_then(serde_json::from_value::<CommandOption>)
+                    .ok()
+            })
+            .filter(|option| {
+                !matches!(
+                    option.kind,
+                    CommandOptionType::SubCommand | CommandOptionType::SubCommandGroup
+                )
+            })
+            .collect()
+    }
+
+    fn option_name<'o>(&self, option: &'o CommandOption) -> &'o str {
+        command_text(
+            self.locale,
+            &format!("{}.options.{}", self.key(), option.name),
+            "name",
+        )
+        .unwrap_or(option.name.as_str())
+    }
+
+    fn option_description<'o>(&self, option: &'o CommandOption) -> &'o str {
+        command_text(
+            self.locale,
+            &format!("{}.options.{}", self.key(), option.name),
+            "description",
+        )
+        .unwrap_or(option.description.as_str())
+    }
+
+    fn usage(&self) -> String {
+        let mut usage = self.invocation();
+        for option in self.options() {
+            let name = self.option_name(&option);
+            if option.required {
+                usage.push_str(&format!(" <{}>", name));
+            } else {
+                usage.push_str(&format!(" [{}]", name));
+            }
+        }
+        usage
+    }
+
+    fn option_line(&self, option: &CommandOption) -> String {
+        let kind = match option.kind {
+            CommandOptionType::String => "string",
+            CommandOptionType::Integer => "integer",
+            CommandOptionType::Number => "number",
+            CommandOptionType::Boolean => "boolean",
+            CommandOptionType::User => "user",
+            CommandOptionType::Channel => "channel",
+            CommandOptionType::Role => "role",
+            CommandOptionType::Mentionable => "mentionable",
+            CommandOptionType::Attachment => "attachment",
+            _ => "other",
+        };
+        let mut details = vec![t!(self.locale, &format!("help.types.{}", kind))];
+        if let Some(range) = bounds(option.min_value.as_ref(), option.max_value.as_ref()) {
+            details.push(range);
+        }
+        if let Some(range) = bounds(option.min_length.as_ref(), option.max_length.as_ref()) {
+            details.push(t!(self.locale, "help.characters", range = range));
+        }
+        if !option.required {
+            details.push(t!(self.locale, "help.optional"));
+        }
+        format!(
+            "`{}` ({}): {}",
+            self.option_name(option),
+            details.join(", "),
+            self.option_description(option)
+        )
+    }
+
+    fn cooldown_line(&self, cooldown: &Cooldown) -> String {
+        let bucket = match cooldown.bucket {
+            CooldownBucket::User => "user",
+            CooldownBucket::Guild => "guild",
+            CooldownBucket::Global => "global",
+        };
+        t!(
+            self.locale,
+            "help.cooldown",
+            uses = cooldown.uses,
+            per = format_duration(cooldown.per),
+            bucket = t!(self.locale, &format!("help.buckets.{}", bucket))
+        )
+    }
+
+    /// Detailed usage of an invocable command.
+    fn render(&self) -> String {
+        let mut builder = MessageBuilder::new();
+        builder
+            .push_bold_line(self.invocation())
+            .push_line(self.description());
+        if self.path[0].kind() == CommandType::ChatInput {
+            builder.push_line(t!(self.locale, "help.usage", usage = self.usage()));
+        }
+        let options = self.options();
+        if !options.is_empty() {
+            builder.push_line(t!(self.locale, "help.options"));
+            for option in options.iter() {
+                builder.push_quote_line(self.option_line(option));
+            }
+        }
+        if self
+            .path
+            .iter()
+            .any(|command| command.permissions().owner_only)
+        {
+            builder.push_line(t!(self.locale, "help.owner_only"));
+        }
+        for permissions in self
+            .path
+            .iter()
+            .filter_map(|command| command.permissions().default_member_permissions)
+        {
+            builder.push_line(t!(
+                self.locale,
+                "help.permissions",
+                permissions = permissions
+            ));
+        }
+        for cooldown in self.command().cooldowns().iter() {
+            builder.push_line(self.cooldown_line(cooldown));
+        }
+        builder.build()
+    }
+
+    fn summary_line(&self) -> String {
+        format!("`{}`: {}", self.invocation(), self.description())
+    }
+}
+
+/// Formats a minimum and maximum, e.g. `1-25` or `≥ 2`.
+fn bounds<T: std::fmt::Display>(min: Option<&T>, max: Option<&T>) -> Option<String> {
+    match (min, max) {
+        (Some(min), Some(max)) => Some(format!("{}-{}", min, max)),
+        (Some(min), None) => Some(format!("≥ {}", min)),
+        (None, Some(max)) => Some(format!("≤ {}", max)),
+        (None, None) => None,
+    }
+}
+
+/// Lists commands, with a heading for slash commands and one for context menu commands.
+fn render_list(locale: Locale, paths: &[Vec<Arc<dyn CommandRunner>>]) -> String {
+    let (slash, context_menu): (Vec<_>, Vec<_>) = paths
+        .iter()
+        .map(|path| CommandHelp::new(locale, path))
+        .partition(|help| help.kind_label().is_none());
+    let mut builder = MessageBuilder::new();
+    for (title, commands) in [
+        (t!(locale, "help.commands"), slash),
+        (t!(locale, "help.context_menu"), context_menu),
+    ] {
+        if commands.is_empty() {
+            continue;
+        }
+        builder.push_bold_line(title);
+        for help in commands.iter() {
+            builder.push_quote_line(help.summary_line());
+        }
+    }
+    builder.push_italic_line(t!(locale, "help.details"));
+    builder.build()
+}
+
+/// Commands the user can run where the help was asked for, hiding commands of disabled modules
+/// and commands the user has no permission for.
+async fn available_paths(
+    ctx: &BotContext,
+    command: &CommandInteraction,
+) -> Result<Vec<Vec<Arc<dyn CommandRunner>>>> {
+    let registry = retrieve_command_registry(ctx.data.clone()).await?;
+    let config_service = retrieve_guild_config_service(ctx.data.clone()).await?;
+    let access = CommandAccess::load(
+        ctx,
+        command.guild_id,
+        command.member.as_deref(),
+        command.user.id,
+    )
+    .await?;
+    let mut available = Vec::new();
+    for path in registry.leaf_paths() {
+        if let (Some(guild_id), Some(module)) = (
+            command.guild_id,
+            path.iter().find_map(|command| command.module()),
+        ) {
+            if !config_service.is_enabled(guild_id, module).await? {
+                continue;
+            }
+        }
+        if access.denied(&path).is_none() {
+            available.push(path);
+        }
+    }
+    Ok(available)
+}
+
+/// Matches the English path or the path in the user's language, ignoring case and a leading `/`.
+fn matches_path(locale: Locale, path: &[Arc<dyn CommandRunner>], input: &str) -> bool {
+    let input = input
+        .trim_start_matches(|c: char| c.is_whitespace() || c == '/')
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    let english = command_path_name(path);
+    let localized = CommandHelp::new(locale, path).name();
+    [english, localized].iter().any(|name| {
+        let name = name.to_lowercase();
+        let input = input.to_lowercase();
+        name == input || name.starts_with(&format!("{} ", input))
+    })
+}
+
+pub(crate) struct HelpCommand;
+impl MakeCommandResponse for HelpCommand {}
+
+#[async_trait]
+impl CommandRunner for HelpCommand {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn description(&self) -> &'static str {
+        "Lists the commands you can use, or shows how to use one"
+    }
+
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        HelpOptions::create_options(key)
+    }
+
+    async fn autocomplete(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> Result<Vec<AutocompleteChoice>> {
+        let input = match command.data.autocomplete() {
+            Some(option) if option.name == COMMAND_OPTION => option.value.to_lowercase(),
+            _ => return Ok(Vec::new()),
+        };
+        Ok(available_paths(ctx, command)
+            .await?
+            .iter()
+            .filter_map(|path| {
+                let name = CommandHelp::new(ctx.locale, path).name();
+                let english = command_path_name(path);
+                (name.to_lowercase().contains(input.trim()) || english.contains(input.trim()))
+                    .then(|| AutocompleteChoice::new(truncate_chars(&name, 100), english))
+            })
+            .take(25)
+            .collect())
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let available = available_paths(ctx, command).await?;
+        let Some(input) = HelpOptions::parse(command, ctx.locale)?.command else {
+            return Ok(self.make_response(render_list(ctx.locale, &available), true));
+        };
+        let matching = available
+            .into_iter()
+            .filter(|path| matches_path(ctx.locale, path, &input))
+            .collect::<Vec<_>>();
+        match matching.as_slice() {
+            [] => Err(BotError::not_found(t!(
+                ctx.locale,
+                "help.unknown_command",
+                command = input
+            ))),
+            [path] => Ok(self.make_response(CommandHelp::new(ctx.locale, path).render(), true)),
+            // A group, e.g. `/help aoc`, lists its subcommands
+            paths => Ok(self.make_response(render_list(ctx.locale, paths), true)),
+        }
+    }
+}
+
+#[cfg(all(test, any(feature = "aoc", feature = "music")))]
+mod tests {
+    use super::*;
+    #[cfg(feature = "aoc")]
+    use crate::testing::{InteractionBuilder, TestHarness};
+    use crate::{config::BotConfig, modules::Modules};
+
+    fn find(names: &[&str]) -> Vec<Arc<dyn CommandRunner>> {
+        crate::build_command_registry(&Modules::new(&BotConfig::test()))
+            .find_path(names)
+            .unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "aoc")]
+    fn renders_command_details() {
+        let path = find(&["aoc", "speedrun"]);
+        assert_eq!(
+            CommandHelp::new(Locale::En, &path).render(),
+            "**/aoc speedrun**\n\
+             AoC Speedrun\n\
+             Usage: `/aoc speedrun [day] [year] [leaderboard_id]`\n\
+             Options:\n\
+             > `day` (integer, 1-25, optional): Speedrun for selected day\n\
+             > `year` (integer, optional): Speedrun for selected year\n\
+             > `leaderboard_id` (integer, optional): Speedrun for selected leaderboard\n"
+        );
+        let path = find(&["aoc", "add"]);
+        let rendered = CommandHelp::new(Locale::Hr, &path).render();
+        assert!(rendered.starts_with("**/aoc dodaj**\n"), "{}", rendered);
+        assert!(
+            rendered.contains("1× svakih 15m po serveru"),
+            "{}",
+            rendered
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "music")]
+    fn matches_localized_paths() {
+        let path = find(&["music", "play"]);
+        assert!(matches_path(Locale::En, &path, "/music play"));
+        assert!(matches_path(Locale::En, &path, "music"));
+        assert!(matches_path(Locale::Hr, &path, "Glazba sviraj"));
+        assert!(!matches_path(Locale::En, &path, "mus"));
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "aoc")]
+    async fn hides_commands_the_user_cannot_run() {
+        let harness = TestHarness::new().await;
+        let command = InteractionBuilder::new("help").without_guild().build();
+        let response = harness.dispatch(&command).await.unwrap();
+        let content = &response.content;
+        assert!(content.contains("`/aoc speedrun`"), "{}", content);
+        assert!(!content.contains("/aoc add"), "{}", content);
+        assert!(!content.contains("/config"), "{}", content);
+    }
+}
@@ -0,0 +1,331 @@
+use std::{collections::HashMap, fmt, sync::OnceLock};
+
+use serde::{Deserialize, Serialize};
+use serenity::all::{CommandType, CreateCommand, CreateCommandOption, GuildId};
+use toml::{Table, Value};
+use tracing::{error, warn};
+
+use crate::{context::BotContext, util::retrieve_guild_config_service};
+
+/// Languages the bot replies in.
+///
+/// Every user-facing reply lives in the `messages` table of `locales/<code>.toml`. Command names
+/// and descriptions are written in English next to the commands, the `commands` table holds
+/// their Discord localizations.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Locale {
+    #[default]
+    En,
+    Hr,
+}
+
+impl Locale {
+    pub(crate) const ALL: &'static [Self] = &[Self::En, Self::Hr];
+
+    pub(crate) fn code(self) -> &'static str {
+        match self {
+            Self::En => "en",
+            Self::Hr => "hr",
+        }
+    }
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|locale| locale.code().eq_ignore_ascii_case(name.trim()))
+    }
+
+    /// Discord sends locales like `en-US` or `hr`.
+    pub(crate) fn from_discord(locale: &str) -> Option<Self> {
+        Self::from_name(locale.split('-').next()?)
+    }
+
+    /// Discord locales commands are localized for, English is the default language of commands.
+    fn discord_locales(self) -> &'static [&'static str] {
+        match self {
+            Self::En => &[],
+            Self::Hr => &["hr"],
+        }
+    }
+
+    fn catalog_source(self) -> &'static str {
+        match self {
+            Self::En => include_str!("../locales/en.toml"),
+            Self::Hr => include_str!("../locales/hr.toml"),
+        }
+    }
+}
+
+impl fmt::Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// Flattened catalogs, e.g. `messages.music.skipped`.
+fn catalogs() -> &'static HashMap<Locale, HashMap<String, String>> {
+    static CATALOGS: OnceLock<HashMap<Locale, HashMap<String, String>>> = OnceLock::new();
+    CATALOGS.get_or_init(|| {
+        Locale::ALL
+            .iter()
+            .map(|locale| {
+                let mut entries = HashMap::new();
+                match locale.catalog_source().parse::<Table>() {
+                    Ok(table) => flatten("", table, &mut entries),
+                    Err(e) => error!("Invalid catalog for {}: {}", locale, e),
+                }
+                (*locale, entries)
+            })
+            .collect()
+    })
+}
+
+fn flatten(prefix: &str, table: Table, entries: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            Value::String(text) => {
+                entries.insert(key, text);
+            }
+            Value::Table(table) => flatten(&key, table, entries),
+            other => warn!("Ignoring catalog entry {} = {}", key, other),
+        }
+    }
+}
+
+fn lookup(locale: Locale, key: &str) -> Option<&'static str> {
+    catalogs().get(&locale)?.get(key).map(String::as_str)
+}
+
+/// Looks up `messages.<key>` and fills in `{name}` placeholders, falling back to English and
+/// then to the key itself. Use [`t!`] instead of calling this directly.
+pub(crate) fn translate(
+    locale: Locale,
+    key: &str,
+    args: &[(&str, &(dyn fmt::Display + Sync))],
+) -> String {
+    let key = format!("messages.{}", key);
+    let template = lookup(locale, &key)
+        .or_else(|| lookup(Locale::En, &key))
+        .unwrap_or_else(|| {
+            warn!("Missing translation for {}", key);
+            &key
+        });
+    args.iter()
+        .fold(template.to_string(), |message, (name, value)| {
+            message.replace(&format!("{{{}}}", name), &value.to_string())
+        })
+}
+
+/// Translates a message, e.g. `t!(ctx.locale, "music.skipped", title = title)`.
+macro_rules! t {
+    ($locale:expr, $key:expr $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::translate(
+            $locale,
+            $key,
+            &[$((stringify!($name), &$value as &(dyn std::fmt::Display + Sync))),*],
+        )
+    };
+}
+
+pub(crate) use t;
+
+/// Catalog key of a command name, context menu commands like "Ban history" become `ban_history`.
+pub(crate) fn command_key(name: &str) -> String {
+    name.to_lowercase().replace(' ', "_")
+}
+
+/// Adds the localized name and description of `commands.<key>` for every other language.
+///
+/// Context menu commands only have a name, their description is only shown by `/help`.
+pub(crate) fn localize_command(
+    mut command: CreateCommand,
+    key: &str,
+    kind: CommandType,
+) -> CreateCommand {
+    for (locale, field, text) in localizations(key) {
+        command = match field {
+            "name" => command.name_localized(locale, text),
+            _ if kind == CommandType::ChatInput => command.description_localized(locale, text),
+            _ => command,
+        };
+    }
+    command
+}
+
+/// Same as [`localize_command`] for options and subcommands.
+pub(crate) fn localize_option(mut option: CreateCommandOption, key: &str) -> CreateCommandOption {
+    for (locale, field, text) in localizations(key) {
+        option = match field {
+            "name" => option.name_localized(locale, text),
+            _ => option.description_localized(locale, text),
+        };
+    }
+    option
+}
+
+/// Localized `name` or `description` of `commands.<key>`, `None` when the command is only
+/// written in English.
+pub(crate) fn command_text(locale: Locale, key: &str, field: &str) -> Option<&'static str> {
+    lookup(locale, &format!("commands.{}.{}", key, field))
+}
+
+fn localizations(key: &str) -> Vec<(&'static str, &'static str, &'static str)> {
+    let mut localizations = Vec::new();
+    for locale in Locale::ALL {
+        for field in ["name", "description"] {
+            if let Some(text) = lookup(*locale, &format!("commands.{}.{}", key, field)) {
+                for discord_locale in locale.discord_locales() {
+                    localizations.push((*discord_locale, field, text));
+                }
+            }
+        }
+    }
+    localizations
+}
+
+/// Language of messages that do not answer an interaction, e.g. DMs and channel messages.
+pub(crate) async fn guild_locale(ctx: &BotContext, guild_id: Option<GuildId>) -> Locale {
+    let Some(guild_id) = guild_id else {
+        return Locale::default();
+    };
+    match retrieve_guild_config_service(ctx.data.clone()).await {
+        Ok(service) => match service.get(guild_id).await {
+            Ok(config) => config.language,
+            Err(e) => {
+                error!("Failed to load guild config for language: {:?}", e);
+                Locale::default()
+            }
+        },
+        Err(e) => {
+            error!("{:?}", e);
+            Locale::default()
+        }
+    }
+}
+
+/// The user's Discord language when the bot speaks it, otherwise the guild's language.
+pub(crate) async fn interaction_locale(
+    ctx: &BotContext,
+    guild_id: Option<GuildId>,
+    locale: &str,
+) -> Locale {
+    match Locale::from_discord(locale) {
+        Some(locale) => locale,
+        None => guild_locale(ctx, guild_id).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::{config::BotConfig, modules::Modules};
+
+    fn keys(locale: Locale, prefix: &str) -> HashSet<&'static str> {
+        catalogs()[&locale]
+            .keys()
+            .map(String::as_str)
+            .filter(|key| key.starts_with(prefix))
+            .collect()
+    }
+
+    fn placeholders(text: &str) -> Vec<&str> {
+        let mut placeholders = text
+            .split('{')
+            .skip(1)
+            .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+            .collect::<Vec<&str>>();
+        placeholders.sort();
+        placeholders
+    }
+
+    #[test]
+    fn catalogs_have_the_same_messages() {
+        let english = keys(Locale::En, "messages.");
+        assert!(!english.is_empty());
+        for locale in Locale::ALL {
+            let translated = keys(*locale, "messages.");
+            assert_eq!(
+                english
+                    .symmetric_difference(&translated)
+                    .collect::<Vec<_>>(),
+                Vec::<&&str>::new(),
+                "{}",
+                locale
+            );
+            for key in &english {
+                assert_eq!(
+                    placeholders(lookup(Locale::En, key).unwrap()),
+                    placeholders(lookup(*locale, key).unwrap()),
+                    "{} in {}",
+                    key,
+                    locale
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn translates_with_arguments() {
+        assert_eq!(
+            t!(Locale::Hr, "errors.internal", id = "abc"),
+            "Nešto je pošlo po zlu, pokušaj ponovno kasnije. (ID greške: `abc`)"
+        );
+        assert_eq!(
+            translate(Locale::En, "missing.key", &[]),
+            "messages.missing.key"
+        );
+        assert_eq!(Locale::from_discord("en-GB"), Some(Locale::En));
+        assert_eq!(Locale::from_discord("de"), None);
+    }
+
+    /// Walks a serialized command and its options, `top_level` tells command types apart from
+    /// option types.
+    fn check_localized(
+        value: &serde_json::Value,
+        top_level: bool,
+        missing: &mut Vec<String>,
+        invalid: &mut Vec<String>,
+    ) {
+        let name = value["name"].as_str().unwrap_or_default();
+        let slash = !top_level || value["type"].as_u64() == Some(1);
+        match value["name_localizations"]["hr"].as_str() {
+            Some(localized) => {
+                let valid = localized.chars().count() <= 32
+                    && localized
+                        .chars()
+                        .all(|c| c == '-' || c == '_' || c.is_lowercase() || c.is_numeric());
+                if slash && !valid {
+                    invalid.push(format!("{} = {}", name, localized));
+                }
+            }
+            None => missing.push(format!("{} name", name)),
+        }
+        if slash && value["description_localizations"]["hr"].as_str().is_none() {
+            missing.push(format!("{} description", name));
+        }
+        for option in value["options"].as_array().into_iter().flatten() {
+            check_localized(option, false, missing, invalid);
+        }
+    }
+
+    #[test]
+    fn every_command_is_localized() {
+        let registry = crate::build_command_registry(&Modules::new(&BotConfig::test()));
+        let (mut missing, mut invalid) = (Vec::new(), Vec::new());
+        for command in registry.create_commands() {
+            let value = serde_json::to_value(command).unwrap();
+            check_localized(&value, true, &mut missing, &mut invalid);
+        }
+        assert!(missing.is_empty(), "Missing localizations: {:#?}", missing);
+        assert!(invalid.is_empty(), "Invalid command names: {:#?}", invalid);
+    }
+}
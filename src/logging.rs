@@ -0,0 +1,21 @@
+use anyhow::{anyhow, Result};
+use tracing_subscriber::{fmt, EnvFilter};
+
+use crate::config::{BotConfig, LogFormat};
+
+/// Installs the global subscriber, `RUST_LOG` takes precedence over the configured level.
+/// Records from crates still using `log` are forwarded as well.
+pub(crate) fn init_logging(config: &BotConfig) -> Result<()> {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(config.log_level.to_string()));
+    let builder = fmt().with_env_filter(filter);
+    match config.log_format {
+        LogFormat::Pretty => builder.try_init(),
+        LogFormat::Json => builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .try_init(),
+    }
+    .map_err(|e| anyhow!("Failed to initialize logging: {}", e))
+}
@@ -1,84 +1,332 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
-use anyhow::Result;
-use aoc::start_aoc_auto_fetch;
+use anyhow::{Context, Result};
+use botadmin::StartedAt;
 use client::init_serenity_client;
+use commands::registry::{CommandRegistry, CommandRegistryHandle};
+use config::{BotConfig, BotConfigHandle};
+use context::BotContext;
+use cooldowns::{CooldownService, CooldownServiceHandle};
 use database::{init_database, MongoDatabaseHandle};
-use event_handlers::mr_handler::MrHandler;
-use music::{QueuedDisconnect, SaveHandler};
-use songbird::typemap::TypeMapKey;
-use tokio::sync::RwLock;
+use guild_config::{GuildConfigService, GuildConfigServiceHandle};
+use logging::init_logging;
+use metrics::{Metrics, MetricsHandle, MetricsServer};
+use modules::{Modules, ModulesHandle};
+use mongodb::Database;
+#[cfg(feature = "music")]
+use music::leave_all_calls;
+use permissions::OwnerIds;
+use scheduler::{Scheduler, SchedulerHandle};
+use serenity::{
+    all::{
+        CreateActionRow, CreateAttachment, CreateEmbed, CreateInteractionResponseFollowup,
+        CreateInteractionResponseMessage,
+    },
+    prelude::TypeMap,
+};
+use shutdown::{wait_for_signal, Shutdown, ShutdownHandle};
+#[cfg(feature = "music")]
+use songbird::serenity::SongbirdKey;
+use tracing::{error, info, info_span, Instrument};
+use util::split_message;
 
+#[cfg(feature = "aoc")]
 mod aoc;
+#[cfg(feature = "banaj")]
 mod banaj_matijosa;
+#[cfg(feature = "bans")]
 mod bantop;
+mod botadmin;
 mod client;
 mod commands;
+mod config;
+mod context;
+mod cooldowns;
 mod database;
+mod error;
 mod event_handlers;
+mod guild_config;
+mod help;
+mod i18n;
+mod logging;
+mod metrics;
+mod modules;
+#[cfg(feature = "music")]
 mod music;
+mod permissions;
+mod quotes;
+mod reminders;
+#[cfg(feature = "roles")]
 mod roles;
+mod scheduler;
+mod shutdown;
+#[cfg(test)]
+mod testing;
+#[cfg(feature = "bans")]
 mod unban;
 mod util;
 
 pub const UNDERSCOREBANS: &str = "_bans";
 const DISCORD_MESSAGE_MAX_LENGTH: usize = 2000;
 
-pub(crate) struct SaveHandlerHandle;
-impl TypeMapKey for SaveHandlerHandle {
-    type Value = Arc<SaveHandler>;
-}
-
 #[derive(Clone, Debug)]
 pub(crate) struct CommandResponse {
     content: String,
     ephemeral: bool,
     is_deferred: bool,
+    components: Vec<CreateActionRow>,
+    embeds: Vec<CreateEmbed>,
+    attachments: Vec<CreateAttachment>,
 }
 
 impl CommandResponse {
-    pub(crate) fn new(mut content: String, ephemeral: bool, is_deferred: bool) -> Self {
-        if content.len() > DISCORD_MESSAGE_MAX_LENGTH {
-            content.truncate(DISCORD_MESSAGE_MAX_LENGTH);
-        }
+    pub(crate) fn new(content: String, ephemeral: bool, is_deferred: bool) -> Self {
         Self {
             content,
             ephemeral,
             is_deferred,
+            components: Vec::new(),
+            embeds: Vec::new(),
+            attachments: Vec::new(),
         }
     }
+
+    #[cfg_attr(not(feature = "music"), allow(dead_code))]
+    pub(crate) fn components(mut self, components: Vec<CreateActionRow>) -> Self {
+        self.components = components;
+        self
+    }
+
+    pub(crate) fn embed(mut self, embed: CreateEmbed) -> Self {
+        self.embeds.push(embed);
+        self
+    }
+
+    // No command sends files yet
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) fn attachment(mut self, attachment: CreateAttachment) -> Self {
+        self.attachments.push(attachment);
+        self
+    }
+
+    /// Splits the content into message sized pages. The first page is sent together with
+    /// embeds, attachments and components, the rest as plain followups.
+    fn split_pages(&mut self) -> (Option<String>, Vec<CreateInteractionResponseFollowup>) {
+        let mut pages = split_message(&self.content, DISCORD_MESSAGE_MAX_LENGTH).into_iter();
+        let first = pages.next();
+        let followups = pages
+            .map(|page| {
+                CreateInteractionResponseFollowup::new()
+                    .content(page)
+                    .ephemeral(self.ephemeral)
+            })
+            .collect();
+        (first, followups)
+    }
+
+    pub(crate) fn into_messages(
+        mut self,
+    ) -> (
+        CreateInteractionResponseMessage,
+        Vec<CreateInteractionResponseFollowup>,
+    ) {
+        let (first, followups) = self.split_pages();
+        let mut message = CreateInteractionResponseMessage::new()
+            .embeds(self.embeds)
+            .components(self.components)
+            .add_files(self.attachments)
+            .ephemeral(self.ephemeral);
+        // Discord rejects empty content, which is valid when the response only carries embeds
+        if let Some(first) = first {
+            message = message.content(first);
+        }
+        (message, followups)
+    }
+
+    pub(crate) fn into_followups(mut self) -> Vec<CreateInteractionResponseFollowup> {
+        let (first, mut followups) = self.split_pages();
+        let mut followup = CreateInteractionResponseFollowup::new()
+            .embeds(self.embeds)
+            .components(self.components)
+            .add_files(self.attachments)
+            .ephemeral(self.ephemeral);
+        if let Some(first) = first {
+            followup = followup.content(first);
+        }
+        followups.insert(0, followup);
+        followups
+    }
+}
+
+fn build_command_registry(modules: &Modules) -> CommandRegistry {
+    let mut command_registry = CommandRegistry::new();
+    modules.register_commands(&mut command_registry);
+    command_registry
 }
 
-struct ReqwestClient;
-impl TypeMapKey for ReqwestClient {
-    type Value = reqwest::Client;
+fn build_scheduler(modules: &Modules, mongo_database: Database) -> Scheduler {
+    let mut scheduler = Scheduler::new(mongo_database);
+    modules.register_jobs(&mut scheduler);
+    scheduler
+}
+
+fn insert_data(
+    data: &mut TypeMap,
+    config: Arc<BotConfig>,
+    mongo_database: Database,
+    modules: Arc<Modules>,
+    scheduler: Arc<Scheduler>,
+    metrics: Arc<Metrics>,
+    shutdown: Shutdown,
+) -> Result<()> {
+    data.insert::<MongoDatabaseHandle>(mongo_database.clone());
+    data.insert::<CommandRegistryHandle>(Arc::new(build_command_registry(&modules)));
+    data.insert::<SchedulerHandle>(scheduler);
+    data.insert::<OwnerIds>(Arc::new(config.owner_ids.clone()));
+    data.insert::<CooldownServiceHandle>(Arc::new(CooldownService::new(mongo_database.clone())));
+    data.insert::<GuildConfigServiceHandle>(Arc::new(GuildConfigService::new(
+        mongo_database,
+        config.enabled_modules.clone(),
+    )));
+    data.insert::<BotConfigHandle>(config);
+    data.insert::<MetricsHandle>(metrics);
+    data.insert::<ShutdownHandle>(shutdown);
+    data.insert::<StartedAt>(Instant::now());
+    modules.init_data(data)?;
+    data.insert::<ModulesHandle>(modules);
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    dotenvy::dotenv().expect(".env file not found");
+    match dotenvy::dotenv() {
+        Ok(_) => {}
+        Err(e) if e.not_found() => {}
+        Err(e) => return Err(e).context("Failed to load .env file"),
+    }
+    let config = Arc::new(BotConfig::load()?);
 
-    pretty_env_logger::env_logger::init_from_env(
-        pretty_env_logger::env_logger::Env::new().default_filter_or("warn"),
-    );
+    init_logging(&config)?;
 
-    let mongo_database = init_database().await;
+    let mongo_database = init_database(&config).await?;
 
-    let mut client = init_serenity_client(vec![MrHandler]).await;
+    let modules = Arc::new(Modules::new(&config));
+    info!("Enabled modules: {}", modules.names().join(", "));
+    let mut client = init_serenity_client(&config.discord_token, modules.clone()).await;
+    let shutdown = Shutdown::new();
+    let metrics = Arc::new(Metrics::new());
+    let scheduler = Arc::new(build_scheduler(&modules, mongo_database.clone()));
 
     {
         let mut lock = client.data.write().await;
-        lock.insert::<SaveHandlerHandle>(Arc::new(SaveHandler::new(mongo_database.clone())));
-        lock.insert::<MongoDatabaseHandle>(mongo_database.clone());
-        lock.insert::<QueuedDisconnect>(Arc::new(RwLock::new(QueuedDisconnect::new())));
-        lock.insert::<ReqwestClient>(reqwest::Client::new());
+        insert_data(
+            &mut lock,
+            config.clone(),
+            mongo_database.clone(),
+            modules.clone(),
+            scheduler.clone(),
+            metrics.clone(),
+            shutdown.clone(),
+        )?;
+    }
+    scheduler.start(
+        BotContext::detached(
+            client.data.clone(),
+            client.http.clone(),
+            client.cache.clone(),
+        ),
+        &shutdown,
+    );
+    #[cfg(feature = "music")]
+    let songbird = client.data.read().await.get::<SongbirdKey>().cloned();
 
-        tokio::spawn(start_aoc_auto_fetch(mongo_database));
+    if let Some(address) = config.metrics_address {
+        let server = MetricsServer {
+            metrics,
+            shard_manager: client.shard_manager.clone(),
+            database: mongo_database,
+            #[cfg(feature = "music")]
+            songbird: songbird.clone(),
+        };
+        let metrics_shutdown = shutdown.clone();
+        shutdown.spawn(
+            async move {
+                if let Err(e) = server.serve(address, metrics_shutdown).await {
+                    error!("Metrics server failed: {:?}", e);
+                }
+            }
+            .instrument(info_span!("metrics_server", %address)),
+        );
     }
 
+    let shard_manager = client.shard_manager.clone();
+    let signal_shutdown = shutdown.clone();
+    tokio::spawn(
+        async move {
+            if let Err(e) = wait_for_signal().await {
+                error!("Failed to listen for shutdown signals: {:?}", e);
+                return;
+            }
+            info!("Shutting down");
+            signal_shutdown.trigger();
+            #[cfg(feature = "music")]
+            if let Some(songbird) = songbird {
+                leave_all_calls(&songbird).await;
+            }
+            shard_manager.shutdown_all().await;
+        }
+        .instrument(info_span!("shutdown")),
+    );
+
     if let Err(err) = client.start().await {
-        println!("Client error: {:?}", err);
+        error!("Client error: {:?}", err);
     }
 
+    shutdown.trigger();
+    shutdown.wait_for_tasks().await;
+    info!("Shutdown complete");
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    fn attachment_names(message: Value) -> Vec<String> {
+        message["attachments"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|attachment| attachment["filename"].as_str().map(str::to_string))
+            .collect()
+    }
+
+    fn response() -> CommandResponse {
+        CommandResponse::new("x".repeat(DISCORD_MESSAGE_MAX_LENGTH + 1), true, false)
+            .attachment(CreateAttachment::bytes("report", "report.txt"))
+    }
+
+    #[test]
+    fn attaches_files_to_the_first_message() {
+        let (message, followups) = response().into_messages();
+        assert_eq!(
+            attachment_names(serde_json::to_value(message).unwrap()),
+            ["report.txt"]
+        );
+        assert_eq!(followups.len(), 1);
+        assert!(attachment_names(serde_json::to_value(&followups[0]).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn attaches_files_to_the_first_followup() {
+        let followups = response().into_followups();
+        assert_eq!(followups.len(), 2);
+        let first = serde_json::to_value(&followups[0]).unwrap();
+        assert_eq!(attachment_names(first.clone()), ["report.txt"]);
+        assert_eq!(first["flags"], 64);
+        assert!(attachment_names(serde_json::to_value(&followups[1]).unwrap()).is_empty());
+    }
+}
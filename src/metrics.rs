@@ -0,0 +1,318 @@
+use std::{convert::Infallible, net::SocketAddr, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use mongodb::{bson::doc, Database};
+use prometheus::{
+    exponential_buckets, histogram_opts, opts, Encoder, HistogramVec, IntCounterVec, IntGauge,
+    IntGaugeVec, Registry, TextEncoder,
+};
+use serde_json::json;
+use serenity::{gateway::ConnectionStage, gateway::ShardManager, prelude::TypeMapKey};
+#[cfg(feature = "music")]
+use songbird::Songbird;
+use tokio::time::timeout;
+use tracing::{error, info};
+
+use crate::{error::BotResult, shutdown::Shutdown};
+
+const NAMESPACE: &str = "papa_klement";
+const DATABASE_PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub(crate) struct MetricsHandle;
+impl TypeMapKey for MetricsHandle {
+    type Value = Arc<Metrics>;
+}
+
+pub(crate) struct Metrics {
+    registry: Registry,
+    command_invocations: IntCounterVec,
+    command_duration: HistogramVec,
+    command_errors: IntCounterVec,
+    voice_calls: IntGauge,
+    queue_length: IntGaugeVec,
+    saved_file_lookups: IntCounterVec,
+    download_duration: HistogramVec,
+    aoc_fetches: IntCounterVec,
+    job_runs: IntCounterVec,
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Self {
+        let registry = Registry::new_custom(Some(NAMESPACE.to_string()), None)
+            .expect("Metrics namespace must be valid");
+        let metrics = Self {
+            command_invocations: IntCounterVec::new(
+                opts!(
+                    "command_invocations_total",
+                    "Command invocations by outcome"
+                ),
+                &["command", "outcome"],
+            )
+            .expect("Metric must be valid"),
+            command_duration: HistogramVec::new(
+                histogram_opts!("command_duration_seconds", "Time spent running commands"),
+                &["command"],
+            )
+            .expect("Metric must be valid"),
+            command_errors: IntCounterVec::new(
+                opts!("command_errors_total", "Command errors by kind"),
+                &["command", "kind"],
+            )
+            .expect("Metric must be valid"),
+            voice_calls: IntGauge::new("voice_calls_active", "Connected voice calls")
+                .expect("Metric must be valid"),
+            queue_length: IntGaugeVec::new(
+                opts!("queue_length", "Queued tracks per guild"),
+                &["guild"],
+            )
+            .expect("Metric must be valid"),
+            saved_file_lookups: IntCounterVec::new(
+                opts!(
+                    "saved_file_lookups_total",
+                    "Lookups of downloaded tracks by result"
+                ),
+                &["result"],
+            )
+            .expect("Metric must be valid"),
+            download_duration: HistogramVec::new(
+                histogram_opts!(
+                    "download_duration_seconds",
+                    "Duration of yt-dlp downloads",
+                    exponential_buckets(1.0, 2.0, 10).expect("Buckets must be valid")
+                ),
+                &["outcome"],
+            )
+            .expect("Metric must be valid"),
+            aoc_fetches: IntCounterVec::new(
+                opts!("aoc_fetches_total", "AoC leaderboard fetches by outcome"),
+                &["outcome"],
+            )
+            .expect("Metric must be valid"),
+            job_runs: IntCounterVec::new(
+                opts!("job_runs_total", "Scheduled job runs by outcome"),
+                &["job", "outcome"],
+            )
+            .expect("Metric must be valid"),
+            registry,
+        };
+        for collector in [
+            Box::new(metrics.command_invocations.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(metrics.command_duration.clone()),
+            Box::new(metrics.command_errors.clone()),
+            Box::new(metrics.voice_calls.clone()),
+            Box::new(metrics.queue_length.clone()),
+            Box::new(metrics.saved_file_lookups.clone()),
+            Box::new(metrics.download_duration.clone()),
+            Box::new(metrics.aoc_fetches.clone()),
+            Box::new(metrics.job_runs.clone()),
+        ] {
+            metrics
+                .registry
+                .register(collector)
+                .expect("Metric must only be registered once");
+        }
+        metrics
+    }
+
+    /// Counts an invocation that was answered without running the command, e.g. on cooldown.
+    pub(crate) fn command_rejected(&self, command: &str, outcome: &str) {
+        self.command_invocations
+            .with_label_values(&[command, outcome])
+            .inc();
+    }
+
+    pub(crate) fn observe_command<T>(
+        &self,
+        command: &str,
+        duration: Duration,
+        result: &BotResult<T>,
+    ) {
+        self.command_duration
+            .with_label_values(&[command])
+            .observe(duration.as_secs_f64());
+        let outcome = match result {
+            Ok(_) => "ok",
+            Err(err) => {
+                self.command_errors
+                    .with_label_values(&[command, err.kind()])
+                    .inc();
+                "error"
+            }
+        };
+        self.command_invocations
+            .with_label_values(&[command, outcome])
+            .inc();
+    }
+
+    #[cfg(feature = "music")]
+    pub(crate) fn saved_file_lookup(&self, hit: bool) {
+        let result = if hit { "hit" } else { "miss" };
+        self.saved_file_lookups.with_label_values(&[result]).inc();
+    }
+
+    #[cfg(feature = "music")]
+    pub(crate) fn observe_download(&self, duration: Duration, outcome: &str) {
+        self.download_duration
+            .with_label_values(&[outcome])
+            .observe(duration.as_secs_f64());
+    }
+
+    #[cfg(feature = "aoc")]
+    pub(crate) fn aoc_fetch(&self, outcome: &str) {
+        self.aoc_fetches.with_label_values(&[outcome]).inc();
+    }
+
+    pub(crate) fn job_run(&self, job: &str, outcome: &str) {
+        self.job_runs.with_label_values(&[job, outcome]).inc();
+    }
+
+    /// Voice metrics are read from songbird at scrape time.
+    #[cfg(feature = "music")]
+    async fn observe_voice(&self, songbird: &Songbird) {
+        self.queue_length.reset();
+        let mut calls = 0;
+        let all_calls = songbird.iter().collect::<Vec<_>>();
+        for (guild_id, call) in all_calls {
+            let call = call.lock().await;
+            if call.current_channel().is_some() {
+                calls += 1;
+            }
+            self.queue_length
+                .with_label_values(&[&guild_id.0.to_string()])
+                .set(call.queue().len() as i64);
+        }
+        self.voice_calls.set(calls);
+    }
+
+    fn render(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+/// What the HTTP listener needs to answer health checks and scrapes.
+pub(crate) struct MetricsServer {
+    pub(crate) metrics: Arc<Metrics>,
+    pub(crate) shard_manager: Arc<ShardManager>,
+    pub(crate) database: Database,
+    #[cfg(feature = "music")]
+    pub(crate) songbird: Option<Arc<Songbird>>,
+}
+
+impl MetricsServer {
+    /// Serves `/healthz` and `/metrics` until shutdown.
+    pub(crate) async fn serve(self, address: SocketAddr, shutdown: Shutdown) -> Result<()> {
+        let server = Arc::new(self);
+        let make_service = make_service_fn(move |_| {
+            let server = server.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let server = server.clone();
+                    async move { Ok::<_, Infallible>(server.handle(request).await) }
+                }))
+            }
+        });
+        info!("Serving metrics on {}", address);
+        Server::try_bind(&address)?
+            .serve(make_service)
+            .with_graceful_shutdown(async move { shutdown.cancelled().await })
+            .await?;
+        Ok(())
+    }
+
+    async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        let response = match (request.method(), request.uri().path()) {
+            (&Method::GET, "/healthz") => self.health().await,
+            (&Method::GET, "/metrics") => self.metrics().await,
+            _ => Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty()),
+        };
+        response.unwrap_or_else(|e| {
+            error!("Failed to build metrics response: {:?}", e);
+            Response::new(Body::empty())
+        })
+    }
+
+    async fn health(&self) -> hyper::http::Result<Response<Body>> {
+        let gateway = {
+            let runners = self.shard_manager.runners.lock().await;
+            !runners.is_empty()
+                && runners
+                    .values()
+                    .all(|runner| runner.stage == ConnectionStage::Connected)
+        };
+        let database = matches!(
+            timeout(
+                DATABASE_PING_TIMEOUT,
+                self.database.run_command(doc! {"ping": 1}, None)
+            )
+            .await,
+            Ok(Ok(_))
+        );
+        let status = if gateway && database {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        Response::builder()
+            .status(status)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                json!({"gateway": gateway, "database": database}).to_string(),
+            ))
+    }
+
+    async fn metrics(&self) -> hyper::http::Result<Response<Body>> {
+        #[cfg(feature = "music")]
+        if let Some(songbird) = self.songbird.as_deref() {
+            self.metrics.observe_voice(songbird).await;
+        }
+        match self.metrics.render() {
+            Ok(metrics) => Response::builder()
+                .header(CONTENT_TYPE, TextEncoder::new().format_type())
+                .body(Body::from(metrics)),
+            Err(e) => {
+                error!("Failed to render metrics: {:?}", e);
+                Response::builder()
+                    .status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .body(Body::empty())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::BotError;
+
+    #[test]
+    fn renders_command_outcomes() {
+        let metrics = Metrics::new();
+        metrics.observe_command("play", Duration::from_millis(5), &Ok(()));
+        metrics.observe_command::<()>("play", Duration::from_millis(5), &Err(BotError::NotInVoice));
+        metrics.command_rejected("play", "cooldown");
+        #[cfg(feature = "music")]
+        metrics.saved_file_lookup(true);
+
+        let rendered = metrics.render().unwrap();
+        assert!(rendered
+            .contains(r#"papa_klement_command_invocations_total{command="play",outcome="ok"} 1"#));
+        assert!(rendered.contains(
+            r#"papa_klement_command_invocations_total{command="play",outcome="cooldown"} 1"#
+        ));
+        assert!(rendered.contains(
+            r#"papa_klement_command_errors_total{command="play",kind="not_in_voice"} 1"#
+        ));
+        #[cfg(feature = "music")]
+        assert!(rendered.contains(r#"papa_klement_saved_file_lookups_total{result="hit"} 1"#));
+        assert!(rendered.contains("papa_klement_voice_calls_active 0"));
+    }
+}
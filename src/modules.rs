@@ -0,0 +1,260 @@
+use std::{panic::AssertUnwindSafe, sync::Arc};
+
+use anyhow::{Context as _, Result};
+use serenity::{
+    all::{ClientBuilder, Context, GatewayIntents, GuildId, Member, Message, User},
+    async_trait,
+    futures::{
+        future::{join_all, BoxFuture},
+        FutureExt,
+    },
+    prelude::{TypeMap, TypeMapKey},
+};
+use tracing::{error, info_span, Instrument};
+
+use crate::{
+    commands::registry::CommandRegistry, config::BotConfig, cooldowns::CooldownCleanupJob,
+    guild_config::GuildModule, scheduler::Scheduler,
+};
+
+/// A part of the bot that brings its own commands, gateway event hooks, intents, background jobs
+/// and shared data. Everything has a no-op default, so a module only implements what it uses.
+#[async_trait]
+pub(crate) trait Module: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Module that `enabled_modules` turns on or off, modules without one are always on.
+    fn guild_module(&self) -> Option<GuildModule> {
+        None
+    }
+
+    /// Gateway intents the module's event hooks need.
+    fn intents(&self) -> GatewayIntents {
+        GatewayIntents::empty()
+    }
+
+    fn init_client(&self, builder: ClientBuilder) -> ClientBuilder {
+        builder
+    }
+
+    /// Inserts the module's shared state, the core entries such as the database and config are
+    /// already present. Runs once on startup, an error stops the bot.
+    fn init_data(&self, _data: &mut TypeMap) -> Result<()> {
+        Ok(())
+    }
+
+    fn register_commands(&self, _registry: &mut CommandRegistry) {}
+
+    fn register_jobs(&self, _scheduler: &mut Scheduler) {}
+
+    async fn message(&self, _ctx: &Context, _message: &Message) -> Result<()> {
+        Ok(())
+    }
+
+    async fn guild_member_update(&self, _ctx: &Context, _member: &Member) -> Result<()> {
+        Ok(())
+    }
+
+    async fn guild_member_addition(&self, _ctx: &Context, _member: &Member) -> Result<()> {
+        Ok(())
+    }
+
+    async fn guild_ban_addition(
+        &self,
+        _ctx: &Context,
+        _guild_id: GuildId,
+        _banned_user: &User,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    async fn cache_ready(&self, _ctx: &Context, _guilds: &[GuildId]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Commands and jobs that are part of every build.
+struct CoreModule;
+
+impl Module for CoreModule {
+    fn name(&self) -> &'static str {
+        "core"
+    }
+
+    fn register_commands(&self, registry: &mut CommandRegistry) {
+        crate::permissions::register_commands(registry);
+        crate::quotes::register_commands(registry);
+        crate::guild_config::register_commands(registry);
+        crate::help::register_commands(registry);
+        crate::scheduler::register_commands(registry);
+        crate::reminders::register_commands(registry);
+        crate::botadmin::register_commands(registry);
+    }
+
+    fn register_jobs(&self, scheduler: &mut Scheduler) {
+        scheduler.register(CooldownCleanupJob);
+        crate::reminders::register_jobs(scheduler);
+    }
+}
+
+/// The modules that are compiled in and enabled, in the order their commands are registered.
+pub(crate) struct Modules {
+    modules: Vec<Arc<dyn Module>>,
+}
+
+impl Modules {
+    pub(crate) fn new(config: &BotConfig) -> Self {
+        let modules: Vec<Arc<dyn Module>> = vec![
+            #[cfg(feature = "bans")]
+            Arc::new(crate::bantop::BantopModule),
+            #[cfg(feature = "music")]
+            Arc::new(crate::music::MusicModule),
+            #[cfg(feature = "aoc")]
+            Arc::new(crate::aoc::AocModule),
+            Arc::new(CoreModule),
+            #[cfg(feature = "bans")]
+            Arc::new(crate::unban::UnbanModule),
+            #[cfg(feature = "roles")]
+            Arc::new(crate::roles::RolesModule),
+            #[cfg(feature = "banaj")]
+            Arc::new(crate::banaj_matijosa::BanajMatijosaModule),
+        ];
+        Self {
+            modules: modules
+                .into_iter()
+                .filter(|module| {
+                    module
+                        .guild_module()
+                        .is_none_or(|guild_module| config.is_module_enabled(guild_module))
+                })
+                .collect(),
+        }
+    }
+
+    pub(crate) fn names(&self) -> Vec<&'static str> {
+        self.modules.iter().map(|module| module.name()).collect()
+    }
+
+    /// Guilds are always needed to register commands, the rest comes from the modules.
+    pub(crate) fn intents(&self) -> GatewayIntents {
+        self.modules
+            .iter()
+            .fold(GatewayIntents::GUILDS, |intents, module| {
+                intents | module.intents()
+            })
+    }
+
+    pub(crate) fn init_client(&self, builder: ClientBuilder) -> ClientBuilder {
+        self.modules
+            .iter()
+            .fold(builder, |builder, module| module.init_client(builder))
+    }
+
+    pub(crate) fn init_data(&self, data: &mut TypeMap) -> Result<()> {
+        for module in &self.modules {
+            module
+                .init_data(data)
+                .with_context(|| format!("Failed to initialize module {}", module.name()))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn register_commands(&self, registry: &mut CommandRegistry) {
+        for module in &self.modules {
+            module.register_commands(registry);
+        }
+    }
+
+    pub(crate) fn register_jobs(&self, scheduler: &mut Scheduler) {
+        for module in &self.modules {
+            module.register_jobs(scheduler);
+        }
+    }
+
+    /// Runs an event hook of every module concurrently. A hook that fails or panics is logged
+    /// and does not stop the other modules from handling the event.
+    pub(crate) async fn dispatch<'a, F>(&'a self, event: &'static str, hook: F)
+    where
+        F: Fn(&'a dyn Module) -> BoxFuture<'a, Result<()>>,
+    {
+        let runs: Vec<_> = self
+            .modules
+            .iter()
+            .map(|module| {
+                let name = module.name();
+                let span = info_span!("module", module = name);
+                let run = AssertUnwindSafe(hook(module.as_ref())).catch_unwind();
+                async move {
+                    match run.await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => error!("{} error in module {}: {:?}", event, name, e),
+                        Err(_) => error!("{} panicked in module {}", event, name),
+                    }
+                }
+                .instrument(span)
+            })
+            .collect();
+        join_all(runs).await;
+    }
+}
+
+pub(crate) struct ModulesHandle;
+
+impl TypeMapKey for ModulesHandle {
+    type Value = Arc<Modules>;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    struct TestModule(&'static str);
+
+    impl Module for TestModule {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+    }
+
+    #[tokio::test]
+    async fn failing_modules_do_not_block_others() {
+        let modules = Modules {
+            modules: vec![
+                Arc::new(TestModule("fails")),
+                Arc::new(TestModule("panics")),
+                Arc::new(TestModule("works")),
+            ],
+        };
+        let handled = AtomicUsize::new(0);
+        modules
+            .dispatch("Test", |module| {
+                let handled = &handled;
+                async move {
+                    match module.name() {
+                        "fails" => anyhow::bail!("failed"),
+                        "panics" => panic!("panicked"),
+                        _ => {
+                            handled.fetch_add(1, Ordering::SeqCst);
+                            Ok(())
+                        }
+                    }
+                }
+                .boxed()
+            })
+            .await;
+        assert_eq!(handled.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn skips_disabled_modules() {
+        let mut config = BotConfig::test();
+        config.enabled_modules.clear();
+        let modules = Modules::new(&config);
+        assert!(modules.names().contains(&"core"));
+        assert!(!modules.names().contains(&"music"));
+        assert!(!modules.names().contains(&"aoc"));
+        assert!(!modules.names().contains(&"roles"));
+    }
+}
@@ -1,50 +1,88 @@
 use std::{
     collections::{HashMap, HashSet},
-    env,
     fmt::Display,
+    fs, io,
+    path::PathBuf,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context as _, Result};
 use chrono::{DateTime, Utc};
-use log::{error, info, warn};
-use mongodb::{bson::doc, Collection, Database};
-use once_cell::sync::Lazy;
+use mongodb::{bson::doc, options::FindOptions, Collection, Database};
 use serde::{Deserialize, Serialize};
 use serenity::{
     all::{
-        ActivityData, CommandInteraction, CommandOptionType, CreateCommand, CreateCommandOption,
+        ActivityData, AutocompleteChoice, ButtonStyle, ClientBuilder, CommandInteraction,
+        CommandType, ComponentInteraction, CreateActionRow, CreateButton, CreateCommandOption,
+        CreateEmbed, CreateEmbedAuthor, GatewayIntents, Message, ResolvedTarget,
     },
     async_trait,
+    futures::TryStreamExt,
     model::{
-        prelude::{ChannelId, ChannelType, GuildId},
+        prelude::{ChannelId, GuildId},
         user::OnlineStatus,
     },
-    prelude::{Context, Mutex, RwLock, TypeMapKey},
+    prelude::{Mutex, RwLock, TypeMap, TypeMapKey},
     utils::MessageBuilder,
 };
 use sha2::{Digest, Sha256};
 use songbird::{
     input::{AuxMetadata, Input, YoutubeDl},
-    Call, CoreEvent, Event, EventContext, EventHandler,
+    Call, CoreEvent, Event, EventContext, EventHandler, SerenityInit, Songbird,
 };
-use tokio::{process::Command, task::JoinHandle};
+use tokio::process::Command;
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
 
 use crate::{
-    commands::slash_commands::SlashCommands,
-    util::{defer_response, retrieve_save_handler, CommandRunner, MakeCommandResponse},
-    CommandResponse, ReqwestClient,
+    commands::{
+        components::{ComponentId, ComponentResponse, ComponentRunner},
+        group::CommandGroup,
+        options::{command_options, CommandOptions},
+        registry::CommandRegistry,
+    },
+    config::BotConfigHandle,
+    context::BotContext,
+    cooldowns::{Cooldown, CooldownBucket},
+    database::MongoDatabaseHandle,
+    error::{BotError, BotResult},
+    guild_config::GuildModule,
+    i18n::{t, Locale},
+    metrics::{Metrics, MetricsHandle},
+    modules::Module,
+    permissions::check_permissions,
+    scheduler::{Backoff, Job, Schedule, Scheduler},
+    shutdown::{Shutdown, ShutdownHandle},
+    util::{
+        defer_response, format_duration, retrieve_command_registry, retrieve_guild_config_service,
+        retrieve_save_handler, truncate_chars, CommandRunner, MakeCommandResponse,
+    },
+    CommandResponse,
 };
 
 const QUERY: &str = "search";
-static HOME: Lazy<String> =
-    Lazy::new(|| env::var("HOME").expect("HOME environment variable is required!"));
 
 const CACHED_AUDIO_COLLECTION: &str = "cached_audio";
 const DISCONNECT_AFTER: u64 = 5 * 60;
+const AUTOCOMPLETE_MAX_CHOICES: usize = 25;
+const AUTOCOMPLETE_MAX_LENGTH: usize = 100;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct MusicConfig {
+    pub(crate) enabled: bool,
+    /// Seconds to stay in an idle voice channel.
+    pub(crate) disconnect_after: u64,
+}
 
-type InvalidCommandUsage = CommandResponse;
+impl Default for MusicConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            disconnect_after: DISCONNECT_AFTER,
+        }
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 struct CachedAudioRecord {
@@ -61,12 +99,20 @@ impl TypeMapKey for AuxMetadataExt {
     type Value = AuxMetadata;
 }
 
+/// Span of the interaction that queued the track, so playback can be traced back to it.
+struct TrackSpan;
+impl TypeMapKey for TrackSpan {
+    type Value = Span;
+}
+
 struct TrackStartEventHandler {
-    context: Context,
+    context: BotContext,
 }
 
+/// Guilds whose voice call is left once it stays idle until the deadline, left by
+/// [`IdleDisconnectJob`].
 pub(crate) struct QueuedDisconnect {
-    queue: HashMap<GuildId, JoinHandle<()>>,
+    queue: HashMap<GuildId, Instant>,
 }
 
 impl QueuedDisconnect {
@@ -76,48 +122,94 @@ impl QueuedDisconnect {
         }
     }
 
-    pub(crate) fn insert_handle(
-        &mut self,
-        guild_id: GuildId,
-        songbird_handler: Arc<Mutex<Call>>,
-        disconnect_after_secs: Option<u64>,
-    ) {
-        println!("INSERTING DISCONNECT HANDLE");
-        if self.queue.get(&guild_id).is_some() {
-            println!("{:#?}", self.queue);
-            self.remove_handle(&guild_id);
-        }
-        self.queue.insert(
-            guild_id,
-            self.make_disconnect_queue_handle(
-                disconnect_after_secs.unwrap_or(DISCONNECT_AFTER),
-                songbird_handler,
-            ),
-        );
+    pub(crate) fn insert_handle(&mut self, guild_id: GuildId, disconnect_after_secs: Option<u64>) {
+        let after = Duration::from_secs(disconnect_after_secs.unwrap_or(DISCONNECT_AFTER));
+        debug!(%guild_id, after_secs = after.as_secs(), "Queueing disconnect");
+        self.queue.insert(guild_id, Instant::now() + after);
     }
 
-    // WARN: self.queue should shrink in some cases to release memory
     pub(crate) fn remove_handle(&mut self, guild_id: &GuildId) {
-        if let Some(handle) = self.queue.remove(guild_id) {
-            println!("REMOVED DISCONNECT HANDLE");
-            handle.abort();
+        if self.queue.remove(guild_id).is_some() {
+            debug!(%guild_id, "Removed queued disconnect");
         }
     }
 
-    fn make_disconnect_queue_handle(
-        &self,
-        disconnect_after_secs: u64,
-        songbird_handler: Arc<Mutex<Call>>,
-    ) -> JoinHandle<()> {
-        tokio::spawn(async move {
-            tokio::time::sleep(std::time::Duration::from_secs(disconnect_after_secs)).await;
-            {
-                let mut lock = songbird_handler.lock().await;
+    /// Removes and returns the guilds whose deadline has passed.
+    fn take_due(&mut self, now: Instant) -> Vec<GuildId> {
+        let due = self
+            .queue
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(guild_id, _)| *guild_id)
+            .collect::<Vec<_>>();
+        for guild_id in due.iter() {
+            self.queue.remove(guild_id);
+        }
+        due
+    }
+}
+
+/// Leaves voice calls that were idle for the guild's `music.disconnect_after`.
+pub(crate) struct IdleDisconnectJob;
+
+#[async_trait]
+impl Job for IdleDisconnectJob {
+    fn name(&self) -> &'static str {
+        "music_idle_disconnect"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::every(Duration::from_secs(5))
+    }
+
+    fn backoff(&self) -> Backoff {
+        Backoff {
+            initial: Duration::from_secs(5),
+            max: Duration::from_secs(60),
+        }
+    }
+
+    fn persistent(&self) -> bool {
+        false
+    }
+
+    async fn run(&self, ctx: &BotContext) -> Result<()> {
+        let queued_disconnects = ctx
+            .data
+            .read()
+            .await
+            .get::<QueuedDisconnect>()
+            .ok_or_else(|| anyhow!("Failed to retrieve QueuedDisconnect from data"))?
+            .clone();
+        let due = queued_disconnects.write().await.take_due(Instant::now());
+        if due.is_empty() {
+            return Ok(());
+        }
+        let songbird = ctx
+            .songbird()
+            .await
+            .ok_or_else(|| anyhow!("Songbird is not registered"))?;
+        for guild_id in due {
+            let Some(call) = songbird.get(guild_id) else {
+                continue;
+            };
+            let span = info_span!("queued_disconnect", guild_id = %guild_id);
+            async {
+                let mut lock = call.lock().await;
+                // A track queued in the meantime keeps the bot in the channel
+                if !lock.queue().is_empty() {
+                    return;
+                }
                 if let Err(e) = lock.leave().await {
                     error!("Disconnect failed: {:?}", e);
+                } else {
+                    info!("Disconnected after inactivity");
                 }
             }
-        })
+            .instrument(span)
+            .await;
+        }
+        Ok(())
     }
 }
 
@@ -130,8 +222,12 @@ impl EventHandler for TrackStartEventHandler {
     async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
         if let EventContext::Track(track) = ctx {
             if let Some((_track_state, track_handle)) = track.first() {
-                let (title, metadata) = {
+                let (title, metadata, span) = {
                     let handle_lock = track_handle.typemap().read().await;
+                    let span = handle_lock
+                        .get::<TrackSpan>()
+                        .cloned()
+                        .unwrap_or_else(Span::none);
                     let metadata = handle_lock.get::<AuxMetadataExt>().cloned();
                     let title = if let Some(metadata) = metadata.as_ref() {
                         metadata
@@ -142,11 +238,12 @@ impl EventHandler for TrackStartEventHandler {
                         "TITLE NOT FOUND".to_string()
                     };
 
-                    (title, metadata)
+                    (title, metadata, span)
                 };
                 if title == "TITLE NOT FOUND" {
-                    warn!("Set TITLE NOT FOUND for track: {:?}", metadata);
+                    warn!(parent: &span, "Set TITLE NOT FOUND for track: {:?}", metadata);
                 }
+                info!(parent: &span, track_id = %track_handle.uuid(), "Track started: {}", title);
                 self.context
                     .set_activity(Some(ActivityData::playing(title)));
             }
@@ -158,13 +255,34 @@ impl EventHandler for TrackStartEventHandler {
 struct TrackEndEventHandler {
     guild_id: GuildId,
     call_handler: Arc<Mutex<Call>>,
-    context: Context,
+    context: BotContext,
+}
+
+impl TrackEndEventHandler {
+    async fn disconnect_after(&self) -> Result<u64> {
+        Ok(retrieve_guild_config_service(self.context.data.clone())
+            .await?
+            .get(self.guild_id)
+            .await?
+            .music
+            .disconnect_after)
+    }
 }
 
 #[async_trait]
 impl EventHandler for TrackEndEventHandler {
     async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
-        if let EventContext::Track(_track) = ctx {
+        if let EventContext::Track(track) = ctx {
+            for (state, track_handle) in track.iter() {
+                let span = track_handle
+                    .typemap()
+                    .read()
+                    .await
+                    .get::<TrackSpan>()
+                    .cloned()
+                    .unwrap_or_else(Span::none);
+                info!(parent: &span, track_id = %track_handle.uuid(), "Track ended: {:?}", state.playing);
+            }
             let is_empty = {
                 let lock = self.call_handler.lock().await;
                 lock.queue().is_empty()
@@ -179,9 +297,16 @@ impl EventHandler for TrackEndEventHandler {
                     .get::<QueuedDisconnect>()
                     .expect("QueuedDisconnect must be present!")
                     .clone();
+                let disconnect_after = match self.disconnect_after().await {
+                    Ok(disconnect_after) => Some(disconnect_after),
+                    Err(e) => {
+                        error!("Failed to load guild config: {:#?}", e);
+                        None
+                    }
+                };
                 {
                     let mut lock = queued_disconnects.write().await;
-                    lock.insert_handle(self.guild_id, self.call_handler.clone(), None);
+                    lock.insert_handle(self.guild_id, disconnect_after);
                 }
             }
         }
@@ -191,7 +316,7 @@ impl EventHandler for TrackEndEventHandler {
 
 struct DriverDisconnectHandler {
     guild_id: GuildId,
-    context: Context,
+    context: BotContext,
     call_handler: Arc<Mutex<Call>>,
 }
 
@@ -223,19 +348,87 @@ impl EventHandler for DriverDisconnectHandler {
     }
 }
 
+fn escape_regex(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub(crate) struct SaveHandlerHandle;
+impl TypeMapKey for SaveHandlerHandle {
+    type Value = Arc<SaveHandler>;
+}
+
+struct ReqwestClient;
+impl TypeMapKey for ReqwestClient {
+    type Value = reqwest::Client;
+}
+
 pub(crate) struct SaveHandler {
-    save_queue: RwLock<HashSet<String>>,
+    /// Hashes of the URLs being downloaded, with the title of the track or its URL.
+    save_queue: RwLock<HashMap<String, String>>,
     db_handle: Database,
     hasher: RwLock<Sha256>,
+    cache_dir: PathBuf,
+    /// Leaked once on startup, songbird only accepts a static program name.
+    yt_dlp_path: &'static str,
+    shutdown: Shutdown,
+    metrics: Arc<Metrics>,
 }
 
 impl SaveHandler {
-    pub(crate) fn new(db_handle: Database) -> Self {
+    pub(crate) fn new(
+        db_handle: Database,
+        cache_dir: PathBuf,
+        yt_dlp_path: &'static str,
+        shutdown: Shutdown,
+        metrics: Arc<Metrics>,
+    ) -> Self {
         Self {
-            save_queue: RwLock::new(HashSet::new()),
+            save_queue: RwLock::new(HashMap::new()),
             db_handle,
             hasher: RwLock::new(Sha256::new()),
+            cache_dir,
+            yt_dlp_path,
+            shutdown,
+            metrics,
+        }
+    }
+
+    /// Tracks being downloaded.
+    pub(crate) async fn queued(&self) -> Vec<String> {
+        let mut queued = self
+            .save_queue
+            .read()
+            .await
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        queued.sort();
+        queued
+    }
+
+    /// Number of downloaded files and their size in bytes.
+    pub(crate) async fn cache_size(&self) -> Result<(usize, u64)> {
+        let mut entries = tokio::fs::read_dir(&self.cache_dir).await?;
+        let (mut files, mut bytes) = (0, 0);
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if metadata.is_file() {
+                files += 1;
+                bytes += metadata.len();
+            }
         }
+        Ok((files, bytes))
+    }
+
+    fn cache_path(&self, id: &str) -> PathBuf {
+        self.cache_dir.join(id)
     }
 
     fn get_collection(&self) -> Collection<CachedAudioRecord> {
@@ -249,6 +442,12 @@ impl SaveHandler {
     }
 
     async fn get_saved_file(&self, query: &str) -> Result<Option<CachedAudioRecord>> {
+        let saved_file = self.find_saved_file(query).await?;
+        self.metrics.saved_file_lookup(saved_file.is_some());
+        Ok(saved_file)
+    }
+
+    async fn find_saved_file(&self, query: &str) -> Result<Option<CachedAudioRecord>> {
         let hash = self.get_hash(query).await?;
         let collection = self.get_collection();
         if let Some(saved_file) = collection.find_one(doc! {"_id": &hash}, None).await? {
@@ -263,6 +462,26 @@ impl SaveHandler {
         }
     }
 
+    async fn search_saved_files(&self, input: &str, limit: i64) -> Result<Vec<CachedAudioRecord>> {
+        let pattern = escape_regex(input);
+        let filter = doc! {
+            "$or": [
+                {"title": {"$regex": &pattern, "$options": "i"}},
+                {"possible_queries": {"$regex": &pattern, "$options": "i"}},
+            ]
+        };
+        let options = FindOptions::builder()
+            .sort(doc! {"date": -1})
+            .limit(limit)
+            .build();
+        Ok(self
+            .get_collection()
+            .find(filter, options)
+            .await?
+            .try_collect()
+            .await?)
+    }
+
     async fn is_url_saved(&self, url: &str) -> Result<bool> {
         let hash = self.get_hash(url).await?;
         let collection = self.get_collection();
@@ -315,16 +534,31 @@ impl SaveHandler {
         Ok(())
     }
 
+    /// Removes what yt-dlp left behind of an interrupted download.
+    async fn remove_partial_download(&self, hash: &str) {
+        for suffix in ["", ".part", ".ytdl"] {
+            let path = self.cache_path(&format!("{}{}", hash, suffix));
+            match tokio::fs::remove_file(&path).await {
+                Ok(_) => info!("Removed partial download {}", path.display()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => error!("Failed to remove {}: {:?}", path.display(), e),
+            }
+        }
+    }
+
     async fn init_save(&self, url: &str, query: &str, title: Option<&String>) -> Result<()> {
+        if self.shutdown.is_cancelled() {
+            return Err(anyhow!("Not saving {} while shutting down", url));
+        }
         if self.is_url_saved(url).await? {
-            println!("SKIPPING already saved file!");
+            info!("Skipping already saved file");
             self.try_append_new_query_to_saved(url, query).await?;
             return Ok(());
         }
         let hash = self.get_hash(url).await?;
         let contains_url = {
             let lock = self.save_queue.read().await;
-            lock.contains(&hash)
+            lock.contains_key(&hash)
         };
         if contains_url {
             return Err(anyhow!("URL already in save queue!"));
@@ -335,7 +569,10 @@ impl SaveHandler {
             );
             {
                 let mut lock = self.save_queue.write().await;
-                lock.insert(hash.to_string());
+                lock.insert(
+                    hash.to_string(),
+                    title.cloned().unwrap_or_else(|| url.to_string()),
+                );
             }
             let ytdl_args = [
                 "-f",
@@ -344,18 +581,41 @@ impl SaveHandler {
                 "--ignore-config",
                 "--no-warnings",
                 url,
-                "-o",
-                &format!("{}/songbird_cache/{}", *HOME, &hash),
             ];
-
-            let command_status = match Command::new("yt-dlp").args(ytdl_args).spawn() {
-                Ok(mut child) => child.wait().await,
+            let started = Instant::now();
+            let command_status = match Command::new(self.yt_dlp_path)
+                .args(ytdl_args)
+                .arg("-o")
+                .arg(self.cache_path(&hash))
+                .kill_on_drop(true)
+                .spawn()
+            {
+                Ok(mut child) => tokio::select! {
+                    status = child.wait() => status,
+                    _ = self.shutdown.cancelled() => {
+                        warn!("Cancelling download of {} for shutdown", url);
+                        if let Err(e) = child.kill().await {
+                            error!("Failed to kill yt-dlp: {:?}", e);
+                        }
+                        self.remove_partial_download(&hash).await;
+                        Err(io::Error::new(
+                            io::ErrorKind::Interrupted,
+                            "Download cancelled by shutdown",
+                        ))
+                    }
+                },
                 Err(e) => Err(e),
             };
+            let outcome = match &command_status {
+                Ok(status) if status.success() => "ok",
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => "cancelled",
+                _ => "error",
+            };
+            self.metrics.observe_download(started.elapsed(), outcome);
 
             let removed = {
                 let mut lock = self.save_queue.write().await;
-                lock.remove(&hash)
+                lock.remove(&hash).is_some()
             };
 
             match command_status {
@@ -372,13 +632,150 @@ impl SaveHandler {
     }
 }
 
+/// Leaves every voice channel, used on shutdown so calls do not linger until Discord times them
+/// out.
+pub(crate) async fn leave_all_calls(manager: &Songbird) {
+    let guild_ids = manager
+        .iter()
+        .map(|(guild_id, _)| guild_id)
+        .collect::<Vec<_>>();
+    for guild_id in guild_ids {
+        if let Err(e) = manager.remove(guild_id).await {
+            error!(
+                "Failed to leave voice channel in guild {}: {:?}",
+                guild_id.0, e
+            );
+        }
+    }
+}
+
+/// Stops playback and leaves the voice channel of the guild. Returns false when the bot is not in
+/// a call there.
+pub(crate) async fn leave_call(ctx: &BotContext, guild_id: GuildId) -> Result<bool> {
+    let songbird = ctx
+        .songbird()
+        .await
+        .ok_or_else(|| anyhow!("Songbird is not registered"))?;
+    let Some(call) = songbird.get(guild_id) else {
+        return Ok(false);
+    };
+    call.lock().await.queue().stop();
+    songbird.remove(guild_id).await?;
+    if let Some(queued_disconnects) = ctx.data.read().await.get::<QueuedDisconnect>() {
+        queued_disconnects.write().await.remove_handle(&guild_id);
+    }
+    ctx.set_presence(None, OnlineStatus::Online);
+    info!(%guild_id, "Left voice channel");
+    Ok(true)
+}
+
+pub(crate) struct MusicModule;
+
+impl Module for MusicModule {
+    fn name(&self) -> &'static str {
+        "music"
+    }
+
+    fn guild_module(&self) -> Option<GuildModule> {
+        Some(GuildModule::Music)
+    }
+
+    /// Voice states tell which channel to join and keep songbird's connections up to date.
+    fn intents(&self) -> GatewayIntents {
+        GatewayIntents::GUILD_VOICE_STATES
+    }
+
+    fn init_client(&self, builder: ClientBuilder) -> ClientBuilder {
+        builder.register_songbird()
+    }
+
+    fn init_data(&self, data: &mut TypeMap) -> Result<()> {
+        let config = data
+            .get::<BotConfigHandle>()
+            .expect("BotConfig must be inserted before modules")
+            .clone();
+        let database = data
+            .get::<MongoDatabaseHandle>()
+            .expect("Database must be inserted before modules")
+            .clone();
+        let shutdown = data
+            .get::<ShutdownHandle>()
+            .expect("Shutdown must be inserted before modules")
+            .clone();
+        let metrics = data
+            .get::<MetricsHandle>()
+            .expect("Metrics must be inserted before modules")
+            .clone();
+        fs::create_dir_all(&config.cache_dir).with_context(|| {
+            format!(
+                "Failed to create cache directory {}",
+                config.cache_dir.display()
+            )
+        })?;
+        data.insert::<SaveHandlerHandle>(Arc::new(SaveHandler::new(
+            database,
+            config.cache_dir.clone(),
+            Box::leak(config.yt_dlp_path.clone().into_boxed_str()),
+            shutdown,
+            metrics,
+        )));
+        data.insert::<QueuedDisconnect>(Arc::new(RwLock::new(QueuedDisconnect::new())));
+        data.insert::<ReqwestClient>(reqwest::Client::new());
+        Ok(())
+    }
+
+    fn register_commands(&self, registry: &mut CommandRegistry) {
+        registry.register(
+            CommandGroup::new("music", "Music playback")
+                .module(GuildModule::Music)
+                .subcommand(PlayCommand)
+                .subcommand(SkipCommand)
+                .subcommand(StopCommand)
+                .subcommand(QueueCommand),
+        );
+        registry.register(PlayFromMessageCommand);
+        registry.register_component(MusicControls);
+    }
+
+    fn register_jobs(&self, scheduler: &mut Scheduler) {
+        scheduler.register(IdleDisconnectJob);
+    }
+}
+
+command_options! {
+    struct PlayOptions {
+        query: String => (QUERY, "Search youtube or use direct URL").autocomplete(),
+    }
+}
+
 pub(crate) struct PlayCommand;
 impl MakeCommandResponse for PlayCommand {}
 
 impl PlayCommand {
+    fn track_embed(locale: Locale, title: &str, metadata: &AuxMetadata) -> CreateEmbed {
+        let mut embed = CreateEmbed::new().title(title);
+        if let Some(url) = metadata.source_url.as_ref() {
+            embed = embed.url(url);
+        }
+        if let Some(thumbnail) = metadata.thumbnail.as_ref() {
+            embed = embed.thumbnail(thumbnail);
+        }
+        if let Some(channel) = metadata.channel.as_ref() {
+            embed = embed.field(t!(locale, "music.channel"), channel, true);
+        }
+        if let Some(duration) = metadata.duration {
+            embed = embed.field(
+                t!(locale, "music.duration"),
+                format_duration(duration),
+                true,
+            );
+        }
+        embed
+    }
+
     fn get_members_voice_channel(
         &self,
-        ctx: &Context,
+        ctx: &BotContext,
         command: &CommandInteraction,
     ) -> Result<Option<ChannelId>> {
         if let (Some(guild_id), Some(member)) = (command.guild_id.as_ref(), command.member.as_ref())
@@ -398,14 +795,15 @@ impl PlayCommand {
 
     async fn handle_connection(
         &self,
-        ctx: &Context,
+        ctx: &BotContext,
         command: &CommandInteraction,
-    ) -> Result<(Option<InvalidCommandUsage>, Option<Arc<Mutex<Call>>>)> {
+    ) -> BotResult<Arc<Mutex<Call>>> {
         if let (Some(channel_id), Some(guild_id)) = (
             self.get_members_voice_channel(ctx, command)?,
             command.guild_id,
         ) {
-            let manager = songbird::get(ctx)
+            let manager = ctx
+                .songbird()
                 .await
                 .expect("Songbird must be registered in client")
                 .clone();
@@ -454,60 +852,102 @@ impl PlayCommand {
                     join_result
                 }
             };
-            Ok((None, Some(handler)))
+            Ok(handler)
         } else {
-            Ok((
-                Some(self.make_response("Not connected to voice channel", true)),
-                None,
-            ))
+            Err(BotError::NotInVoice)
         }
     }
-
-    fn get_query(&self, command: &CommandInteraction) -> Result<String> {
-        let query_string = command
-            .data
-            .options
-            .iter()
-            .find(|opt| opt.name == QUERY)
-            .ok_or_else(|| anyhow!("Missing query option"))?
-            .value
-            .as_str()
-            .ok_or_else(|| anyhow!("Search is empty"))?
-            .to_string();
-        // query_string.remove(query_string.len() - 1);
-        // query_string.remove(0);
-        Ok(query_string)
-    }
 }
 
 #[async_trait]
 impl CommandRunner for PlayCommand {
-    fn register(&self) -> CreateCommand {
-        info!("Command registered: {}", SlashCommands::Play.as_str());
-        let command = CreateCommand::new(SlashCommands::Play.as_str());
-        command
-            .dm_permission(false)
-            .add_option(
-                CreateCommandOption::new(
-                    CommandOptionType::String,
-                    QUERY,
-                    "Search youtube or use direct URL",
-                )
-                .required(true)
-                .channel_types(vec![ChannelType::Text]),
-            )
-            .description("Plays a track from youtube")
+    fn name(&self) -> &'static str {
+        "play"
     }
 
-    async fn run(&self, ctx: &Context, command: &CommandInteraction) -> Result<CommandResponse> {
-        defer_response(ctx, command).await?;
-        let query = self.get_query(command)?;
+    fn description(&self) -> &'static str {
+        "Plays a track from youtube"
+    }
+
+    fn cooldowns(&self) -> Vec<Cooldown> {
+        vec![Cooldown::new(
+            CooldownBucket::User,
+            5,
+            Duration::from_secs(30),
+        )]
+    }
 
-        let (early_response, handler) = self.handle_connection(ctx, command).await?;
-        if let Some(r) = early_response {
-            return Ok(r);
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        PlayOptions::create_options(key)
+    }
+
+    async fn autocomplete(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> Result<Vec<AutocompleteChoice>> {
+        let input = match command.data.autocomplete() {
+            Some(option) if option.name == QUERY => option.value,
+            _ => return Ok(Vec::new()),
+        };
+        let save_handler = retrieve_save_handler(ctx.data.clone()).await?;
+        let input_lowercase = input.to_lowercase();
+        let mut choices: Vec<(String, String)> = Vec::new();
+        for saved in save_handler
+            .search_saved_files(input, AUTOCOMPLETE_MAX_CHOICES as i64)
+            .await?
+        {
+            if let Some(title) = saved.title.as_ref() {
+                if title.to_lowercase().contains(&input_lowercase)
+                    && saved.url.len() <= AUTOCOMPLETE_MAX_LENGTH
+                {
+                    choices.push((title.clone(), saved.url.clone()));
+                }
+            }
+            for query in saved.possible_queries {
+                if query.to_lowercase().contains(&input_lowercase)
+                    && query.len() <= AUTOCOMPLETE_MAX_LENGTH
+                {
+                    choices.push((query.clone(), query));
+                }
+            }
         }
-        let handler = handler.ok_or_else(|| anyhow!("Failed to retrieve handler!\nThis value should always be Some if handled correctly!"))?;
+        let mut seen = HashSet::new();
+        Ok(choices
+            .into_iter()
+            .filter(|(_, value)| seen.insert(value.clone()))
+            .take(AUTOCOMPLETE_MAX_CHOICES)
+            .map(|(name, value)| {
+                AutocompleteChoice::new(truncate_chars(&name, AUTOCOMPLETE_MAX_LENGTH), value)
+            })
+            .collect())
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        defer_response(ctx, command).await?;
+        let PlayOptions { query } = PlayOptions::parse(command, ctx.locale)?;
+        self.enqueue(ctx, command, query).await
+    }
+
+    fn has_deferred_response(&self) -> bool {
+        true
+    }
+}
+
+impl PlayCommand {
+    /// Joins the caller's voice channel and queues the track found by `query`, either a saved
+    /// file, a URL or a youtube search.
+    async fn enqueue(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+        query: String,
+    ) -> BotResult<CommandResponse> {
+        let handler = self.handle_connection(ctx, command).await?;
 
         let save_handler = retrieve_save_handler(ctx.data.clone()).await?;
         let saved_file = save_handler.get_saved_file(&query).await?;
@@ -517,10 +957,12 @@ impl CommandRunner for PlayCommand {
         let (source, metadata) = if let Some(saved) = saved_file {
             info!("Reading file from disk!");
             let source: Input =
-                songbird::input::File::new(format!("{}/songbird_cache/{}", *HOME, saved.id)).into();
-            let mut metadata = AuxMetadata::default();
-            metadata.source_url = Some(saved.url);
-            metadata.title = saved.title;
+                songbird::input::File::new(save_handler.cache_path(&saved.id)).into();
+            let metadata = AuxMetadata {
+                source_url: Some(saved.url),
+                title: saved.title,
+                ..Default::default()
+            };
             (source, metadata)
         } else {
             info!("Searching youtube for: {}", query);
@@ -532,50 +974,59 @@ impl CommandRunner for PlayCommand {
             };
             // WARN: cannot be sure if query is actually url
             let mut source: Input = if query.starts_with("http") {
-                YoutubeDl::new(client, query.clone()).into()
+                YoutubeDl::new_ytdl_like(save_handler.yt_dlp_path, client, query.clone()).into()
             } else {
-                YoutubeDl::new_search(client, query.clone()).into()
+                YoutubeDl::new_search_ytdl_like(save_handler.yt_dlp_path, client, query.clone())
+                    .into()
             };
 
             let url = match source.aux_metadata().await?.source_url.as_ref() {
                 Some(url) => url.to_string(),
                 None => {
                     error!("Failed to retrieve url from input!");
-                    return Ok(self.make_response("Failed to retrieve url from input!", true));
+                    return Ok(self.make_response(t!(ctx.locale, "music.no_url"), true));
                 }
             };
 
             let metadata = source.aux_metadata().await?;
             let title = metadata.title.clone();
             let data = ctx.data.clone();
-            tokio::spawn(async move {
-                let save_handler = match retrieve_save_handler(data).await {
-                    Ok(sh) => sh,
-                    Err(e) => {
-                        error!(
-                            "Error retrieving save handler in save init thread: {:#?}",
-                            e
-                        );
-                        return;
+            let span = info_span!("download", url = %url);
+            save_handler.shutdown.spawn(
+                async move {
+                    let save_handler = match retrieve_save_handler(data).await {
+                        Ok(sh) => sh,
+                        Err(e) => {
+                            error!(
+                                "Error retrieving save handler in save init thread: {:#?}",
+                                e
+                            );
+                            return;
+                        }
+                    };
+                    if let Err(e) = save_handler.init_save(&url, &query, title.as_ref()).await {
+                        error!("Error while saving: {:#?}", e);
                     }
-                };
-                if let Err(e) = save_handler.init_save(&url, &query, title.as_ref()).await {
-                    error!("Error while saving: {:#?}", e);
                 }
-            });
+                .instrument(span),
+            );
             (source, metadata)
         };
 
         let title = metadata
             .title
             .clone()
-            .unwrap_or_else(|| "TITLE NOT FOUND".to_string());
+            .unwrap_or_else(|| t!(ctx.locale, "music.unknown_title"));
+        let embed = Self::track_embed(ctx.locale, &title, &metadata);
         let mut handle = handler.lock().await;
         let track_handle = handle.enqueue(source.into()).await;
+        let track_id = track_handle.uuid();
         {
             let mut track_handle_lock = track_handle.typemap().write().await;
             track_handle_lock.insert::<AuxMetadataExt>(metadata);
+            track_handle_lock.insert::<TrackSpan>(Span::current());
         }
+        info!(track_id = %track_id, "Queued track: {}", title);
 
         if handle.queue().len() == 1 {
             let queued_disconnects = ctx
@@ -592,11 +1043,83 @@ impl CommandRunner for PlayCommand {
                 }
             }
             ctx.set_activity(Some(ActivityData::playing(&title)));
-            Ok(self.make_response(format!("Now playing: {}", title), false))
+            Ok(self
+                .make_response("", false)
+                .embed(embed.author(CreateEmbedAuthor::new(t!(ctx.locale, "music.now_playing"))))
+                .components(vec![MusicControls::action_row(ctx.locale, track_id)]))
         } else {
-            Ok(self.make_response(format!("Added to queue: {}", title), false))
+            Ok(self
+                .make_response("", false)
+                .embed(embed.author(CreateEmbedAuthor::new(t!(ctx.locale, "music.queued"))))
+                .components(vec![MusicControls::action_row(ctx.locale, track_id)]))
         }
     }
+}
+
+pub(crate) struct PlayFromMessageCommand;
+impl MakeCommandResponse for PlayFromMessageCommand {}
+
+impl PlayFromMessageCommand {
+    /// Audio attachments take precedence over links in the message text.
+    fn find_query(message: &Message) -> Option<String> {
+        message
+            .attachments
+            .iter()
+            .find(|attachment| {
+                attachment
+                    .content_type
+                    .as_deref()
+                    .is_some_and(|content_type| {
+                        content_type.starts_with("audio/") || content_type.starts_with("video/")
+                    })
+            })
+            .map(|attachment| attachment.url.clone())
+            .or_else(|| {
+                message
+                    .content
+                    .split_whitespace()
+                    .map(|word| word.trim_matches(|c| c == '<' || c == '>'))
+                    .find(|word| word.starts_with("http://") || word.starts_with("https://"))
+                    .map(str::to_string)
+            })
+    }
+}
+
+#[async_trait]
+impl CommandRunner for PlayFromMessageCommand {
+    fn name(&self) -> &'static str {
+        "Play audio"
+    }
+
+    fn description(&self) -> &'static str {
+        "Plays the link or audio attachment of a message"
+    }
+
+    fn kind(&self) -> CommandType {
+        CommandType::Message
+    }
+
+    fn cooldowns(&self) -> Vec<Cooldown> {
+        PlayCommand.cooldowns()
+    }
+
+    fn module(&self) -> Option<GuildModule> {
+        Some(GuildModule::Music)
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        defer_response(ctx, command).await?;
+        let query = match command.data.target() {
+            Some(ResolvedTarget::Message(message)) => Self::find_query(message),
+            _ => None,
+        }
+        .ok_or_else(|| BotError::bad_input(t!(ctx.locale, "music.no_audio_in_message")))?;
+        PlayCommand.enqueue(ctx, command, query).await
+    }
 
     fn has_deferred_response(&self) -> bool {
         true
@@ -608,23 +1131,34 @@ impl MakeCommandResponse for SkipCommand {}
 
 #[async_trait]
 impl CommandRunner for SkipCommand {
-    fn register(&self) -> CreateCommand {
-        info!("Command registered: {}", SlashCommands::Skip.as_str());
-        let command = CreateCommand::new(SlashCommands::Skip.as_str());
-        command
-            .dm_permission(false)
-            .description("Skip current track")
+    fn name(&self) -> &'static str {
+        "skip"
     }
 
-    async fn run(&self, ctx: &Context, command: &CommandInteraction) -> Result<CommandResponse> {
+    fn description(&self) -> &'static str {
+        "Skip current track"
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
         let guild_id = match command.guild_id {
             Some(g) => g,
             None => {
-                return Ok(self.make_response("Command must be run in a guild!", true));
+                return Err(BotError::bad_input(t!(ctx.locale, "errors.guild_only")));
             }
         };
+        self.skip(ctx, guild_id).await
+    }
+}
+
+impl SkipCommand {
+    async fn skip(&self, ctx: &BotContext, guild_id: GuildId) -> BotResult<CommandResponse> {
         info!("Skip in guild: {}", guild_id.get());
-        let manager = songbird::get(ctx)
+        let manager = ctx
+            .songbird()
             .await
             .expect("Songbird must be registered in client")
             .clone();
@@ -634,27 +1168,22 @@ impl CommandRunner for SkipCommand {
             if !queue.is_empty() {
                 let current = match queue.current() {
                     Some(track) => track,
-                    None => return Err(anyhow!("Failed to retrieve current track")),
+                    None => return Err(anyhow!("Failed to retrieve current track").into()),
                 };
                 let title = {
                     let handle_lock = current.typemap().read().await;
                     let metadata = handle_lock.get::<AuxMetadataExt>();
-                    if let Some(metadata) = metadata {
-                        metadata
-                            .title
-                            .clone()
-                            .unwrap_or_else(|| "[TITLE NOT FOUND]".to_string())
-                    } else {
-                        "[TITLE NOT FOUND]".to_string()
-                    }
+                    metadata
+                        .and_then(|metadata| metadata.title.clone())
+                        .unwrap_or_else(|| t!(ctx.locale, "music.unknown_title"))
                 };
-                let _ = queue.skip()?;
-                Ok(self.make_response(format!("Skipped: {}", title), false))
+                queue.skip()?;
+                Ok(self.make_response(t!(ctx.locale, "music.skipped", title = title), false))
             } else {
-                Ok(self.make_response("There is nothing to skip!", true))
+                Ok(self.make_response(t!(ctx.locale, "music.nothing_to_skip"), true))
             }
         } else {
-            Ok(self.make_response("Failed to skip", true))
+            Ok(self.make_response(t!(ctx.locale, "music.not_playing"), true))
         }
     }
 }
@@ -664,23 +1193,34 @@ impl MakeCommandResponse for StopCommand {}
 
 #[async_trait]
 impl CommandRunner for StopCommand {
-    fn register(&self) -> CreateCommand {
-        info!("Command registered: {}", SlashCommands::Stop.as_str());
-        let command = CreateCommand::new(SlashCommands::Stop.as_str());
-        command
-            .dm_permission(false)
-            .description("Stops the bot playing tracks and disconnects it")
+    fn name(&self) -> &'static str {
+        "stop"
     }
 
-    async fn run(&self, ctx: &Context, command: &CommandInteraction) -> Result<CommandResponse> {
+    fn description(&self) -> &'static str {
+        "Stops the bot playing tracks and disconnects it"
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
         let guild_id = match command.guild_id {
             Some(g) => g,
             None => {
-                return Ok(self.make_response("Command must be run in a guild!", true));
+                return Err(BotError::bad_input(t!(ctx.locale, "errors.guild_only")));
             }
         };
+        self.stop(ctx, guild_id).await
+    }
+}
+
+impl StopCommand {
+    async fn stop(&self, ctx: &BotContext, guild_id: GuildId) -> BotResult<CommandResponse> {
         info!("Stop in guild: {}", guild_id.get());
-        let manager = songbird::get(ctx)
+        let manager = ctx
+            .songbird()
             .await
             .expect("Songbird must be registered in client")
             .clone();
@@ -691,9 +1231,107 @@ impl CommandRunner for StopCommand {
             // queue.stop();
             ctx.set_presence(None, OnlineStatus::Online);
             handler.leave().await?;
-            Ok(self.make_response("Stopping", false))
+            Ok(self.make_response(t!(ctx.locale, "music.stopped"), false))
         } else {
-            Ok(self.make_response("Failed to stop", true))
+            Ok(self.make_response(t!(ctx.locale, "music.not_playing"), true))
+        }
+    }
+}
+
+/// Skip and stop buttons attached to playback messages.
+pub(crate) struct MusicControls;
+
+impl MusicControls {
+    const NAMESPACE: &'static str = "music";
+    const SKIP: &'static str = "skip";
+    const STOP: &'static str = "stop";
+
+    /// Skip button carries the ID of the track it was created for, so a stale button does not
+    /// skip whatever is playing now.
+    fn action_row(locale: Locale, track_id: impl ToString) -> CreateActionRow {
+        CreateActionRow::Buttons(vec![
+            CreateButton::new(
+                ComponentId::new(Self::NAMESPACE, Self::SKIP)
+                    .with_state(track_id)
+                    .to_string(),
+            )
+            .label(t!(locale, "music.skip_button"))
+            .style(ButtonStyle::Secondary),
+            CreateButton::new(ComponentId::new(Self::NAMESPACE, Self::STOP).to_string())
+                .label(t!(locale, "music.stop_button"))
+                .style(ButtonStyle::Danger),
+        ])
+    }
+}
+
+#[async_trait]
+impl ComponentRunner for MusicControls {
+    fn namespace(&self) -> &'static str {
+        Self::NAMESPACE
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        interaction: &ComponentInteraction,
+        id: &ComponentId,
+    ) -> BotResult<ComponentResponse> {
+        let guild_id = interaction
+            .guild_id
+            .ok_or_else(|| anyhow!("Component must be used in a guild"))?;
+        // Buttons are shortcuts for the matching subcommands and follow the same rules
+        let path = retrieve_command_registry(ctx.data.clone())
+            .await?
+            .find_path(&["music", id.action.as_str()])
+            .ok_or_else(|| anyhow!("Unknown music action: {}", id.action))?;
+        if let Some(denied) = check_permissions(
+            ctx,
+            interaction.guild_id,
+            interaction.member.as_ref(),
+            interaction.user.id,
+            &path,
+        )
+        .await?
+        {
+            return Err(BotError::MissingPermission(denied));
+        }
+        match id.action.as_str() {
+            Self::SKIP => {
+                let current_track_id = match ctx.songbird().await {
+                    Some(manager) => match manager.get(guild_id) {
+                        Some(handler_lock) => handler_lock
+                            .lock()
+                            .await
+                            .queue()
+                            .current()
+                            .map(|track| track.uuid().to_string()),
+                        None => None,
+                    },
+                    None => None,
+                };
+                if current_track_id.as_ref() != id.state.first() {
+                    return Ok(ComponentResponse::Acknowledge);
+                }
+                Ok(ComponentResponse::Message(
+                    SkipCommand.skip(ctx, guild_id).await?,
+                ))
+            }
+            Self::STOP => {
+                let response = StopCommand.stop(ctx, guild_id).await?;
+                if response.ephemeral {
+                    Ok(ComponentResponse::Message(response))
+                } else {
+                    // Playback is over, drop the controls from the original message
+                    let response = interaction.message.embeds.iter().fold(
+                        CommandResponse::new(interaction.message.content.clone(), false, false),
+                        |response, embed| response.embed(embed.clone().into()),
+                    );
+                    Ok(ComponentResponse::UpdateMessage(
+                        response.components(Vec::new()),
+                    ))
+                }
+            }
+            action => Err(anyhow!("Unknown music action: {}", action).into()),
         }
     }
 }
@@ -719,24 +1357,28 @@ impl Display for MinutesDisplay {
 
 #[async_trait]
 impl CommandRunner for QueueCommand {
-    fn register(&self) -> CreateCommand {
-        info!("Command registered: {}", SlashCommands::Queue.as_str());
-        let command = CreateCommand::new(SlashCommands::Queue.as_str());
-        command
-            .dm_permission(false)
-            .description("Fetches current track queue.")
+    fn name(&self) -> &'static str {
+        "queue"
+    }
+
+    fn description(&self) -> &'static str {
+        "Fetches current track queue."
     }
 
-    async fn run(&self, ctx: &Context, command: &CommandInteraction) -> Result<CommandResponse> {
-        return Ok(self.make_response("Queue is currently broken", false));
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
         let guild_id = match command.guild_id {
             Some(g) => g,
             None => {
-                return Ok(self.make_response("Command must be run in a guild!", true));
+                return Err(BotError::bad_input(t!(ctx.locale, "errors.guild_only")));
             }
         };
-        info!("Stop in guild: {}", guild_id.get());
-        let manager = songbird::get(ctx)
+        info!("Queue in guild: {}", guild_id.get());
+        let manager = ctx
+            .songbird()
             .await
             .expect("Songbird must be registered in client")
             .clone();
@@ -747,7 +1389,7 @@ impl CommandRunner for QueueCommand {
             };
 
             if queue.is_empty() {
-                return Ok(self.make_response("Queue is empty", false));
+                return Ok(self.make_response(t!(ctx.locale, "music.queue_empty"), false));
             }
             let mut builder = MessageBuilder::new();
             let (current_track_position, current_track_length, title) = {
@@ -767,37 +1409,35 @@ impl CommandRunner for QueueCommand {
                 (
                     track.get_info().await?.position,
                     duration.unwrap_or(Duration::from_secs(0)),
-                    title.unwrap_or_else(|| "TITLE NOT FOUND!".to_string()),
+                    title.unwrap_or_else(|| t!(ctx.locale, "music.unknown_title")),
                 )
             };
             builder
-                .push_bold("Currently playing: ")
+                .push_bold(t!(ctx.locale, "music.currently_playing"))
                 .push(title)
                 .push_bold_line(format!(
                     " | {} / {}",
                     MinutesDisplay::from(current_track_position),
                     MinutesDisplay::from(current_track_length)
                 ));
-            error!("4");
-            error!("{:?} {:?}", current_track_length, current_track_position);
-            let mut time_until = Some(current_track_length - current_track_position);
-            error!("5");
+            debug!(
+                "Current track at {:?} of {:?}",
+                current_track_position, current_track_length
+            );
+            // Tracks read from disk have no duration, so the position can exceed the length
+            let mut time_until = Some(current_track_length.saturating_sub(current_track_position));
             for (i, track) in queue.iter().skip(1).enumerate() {
-                if builder.0.len() >= 2000 {
-                    break;
-                }
                 builder.push_bold(format!("{}. ", i + 1));
                 let metadata = {
                     let handle_lock = track.typemap().read().await;
-                    let metadata = handle_lock.get::<AuxMetadataExt>().cloned();
-                    match metadata {
-                        Some(m) => m,
-                        None => AuxMetadata::default(),
-                    }
+                    handle_lock
+                        .get::<AuxMetadataExt>()
+                        .cloned()
+                        .unwrap_or_default()
                 };
-                match metadata.title.as_ref() {
+                match metadata.title {
                     Some(title) => builder.push(title),
-                    None => builder.push("NO TITLE FOUND"),
+                    None => builder.push(t!(ctx.locale, "music.unknown_title")),
                 };
                 if let (Some(track_duration), Some(time_until)) =
                     (metadata.duration.as_ref(), time_until.as_mut())
@@ -809,18 +1449,8 @@ impl CommandRunner for QueueCommand {
                     time_until = None;
                 }
             }
-            let queue_response = if builder.0.len() >= 2000 {
-                const TOO_LONG: &str = "...\n**Queue is too long to display**";
-                let mut queue = builder.build();
-                queue.replace_range(2000 - TOO_LONG.len()..2000, TOO_LONG);
-                queue.truncate(2000);
-
-                queue
-            } else {
-                builder.build()
-            };
-            return Ok(self.make_response(queue_response, false));
+            return Ok(self.make_response(builder.build(), false));
         }
-        Ok(self.make_response("Failed retrieving queue", false))
+        Ok(self.make_response(t!(ctx.locale, "music.not_playing"), false))
     }
 }
@@ -0,0 +1,540 @@
+use std::{collections::HashSet, sync::Arc};
+
+use anyhow::Result;
+use mongodb::{bson::doc, options::FindOneAndUpdateOptions, Collection};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{
+        AutocompleteChoice, CommandInteraction, CreateCommandOption, GuildId, Member, Permissions,
+        RoleId, UserId,
+    },
+    async_trait,
+    futures::TryStreamExt,
+    prelude::TypeMapKey,
+    utils::MessageBuilder,
+};
+use tracing::info;
+
+use crate::{
+    commands::{
+        group::CommandGroup,
+        options::{command_options, CommandOptions},
+        registry::{command_path_name, CommandRegistry},
+    },
+    context::BotContext,
+    error::{BotError, BotResult},
+    i18n::{t, Locale},
+    util::{
+        retrieve_command_registry, retrieve_db_handle, truncate_chars, CommandRunner,
+        MakeCommandResponse,
+    },
+    CommandResponse,
+};
+
+const COMMAND_PERMISSIONS_COLLECTION: &str = "command_permissions";
+
+const COMMAND_OPTION: &str = "command";
+const ROLE_OPTION: &str = "role";
+const USER_OPTION: &str = "user";
+const COMMAND_DESCRIPTION: &str = "Command, e.g. \"aoc cookie\"";
+
+/// Static permission requirements of a command, checked for every command on the path to the
+/// invoked subcommand.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct CommandPermissions {
+    /// Registered with Discord for top level commands and enforced by the bot for subcommands,
+    /// which Discord does not support.
+    pub(crate) default_member_permissions: Option<Permissions>,
+    pub(crate) owner_only: bool,
+}
+
+pub(crate) struct OwnerIds;
+impl TypeMapKey for OwnerIds {
+    type Value = Arc<HashSet<UserId>>;
+}
+
+pub(crate) async fn is_owner(ctx: &BotContext, user_id: UserId) -> bool {
+    ctx.data
+        .read()
+        .await
+        .get::<OwnerIds>()
+        .map(|owners| owners.contains(&user_id))
+        .unwrap_or(false)
+}
+
+/// Guild configured allowlist for a command. A command with a non-empty rule can only be used by
+/// the listed users, members with one of the listed roles and administrators.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct CommandPermissionRule {
+    guild_id: i64,
+    command: String,
+    #[serde(default)]
+    allowed_roles: Vec<i64>,
+    #[serde(default)]
+    allowed_users: Vec<i64>,
+}
+
+impl CommandPermissionRule {
+    fn is_empty(&self) -> bool {
+        self.allowed_roles.is_empty() && self.allowed_users.is_empty()
+    }
+
+    fn allows(&self, member: &Member) -> bool {
+        self.allowed_users.contains(&(member.user.id.get() as i64))
+            || member
+                .roles
+                .iter()
+                .any(|role| self.allowed_roles.contains(&(role.get() as i64)))
+    }
+}
+
+async fn get_collection(ctx: &BotContext) -> Result<Collection<CommandPermissionRule>> {
+    Ok(retrieve_db_handle(ctx.data.clone())
+        .await?
+        .collection::<CommandPermissionRule>(COMMAND_PERMISSIONS_COLLECTION))
+}
+
+/// Checks whether the user may run the command at the end of `path`. Returns the reason when
+/// the user is denied.
+pub(crate) async fn check_permissions(
+    ctx: &BotContext,
+    guild_id: Option<GuildId>,
+    member: Option<&Member>,
+    user_id: UserId,
+    path: &[Arc<dyn CommandRunner>],
+) -> Result<Option<String>> {
+    Ok(CommandAccess::load(ctx, guild_id, member, user_id)
+        .await?
+        .denied(path))
+}
+
+/// What a user is allowed to run, loaded once so that many commands can be checked without a
+/// query for each.
+pub(crate) struct CommandAccess<'a> {
+    locale: Locale,
+    is_owner: bool,
+    member: Option<&'a Member>,
+    /// Allowlists of the guild, only loaded when the user is a member that is not an
+    /// administrator.
+    rules: Vec<CommandPermissionRule>,
+}
+
+impl<'a> CommandAccess<'a> {
+    pub(crate) async fn load(
+        ctx: &BotContext,
+        guild_id: Option<GuildId>,
+        member: Option<&'a Member>,
+        user_id: UserId,
+    ) -> Result<Self> {
+        let rules = match (guild_id, member) {
+            (Some(guild_id), Some(member)) if !is_admin(member) => {
+                get_collection(ctx)
+                    .await?
+                    .find(doc! {"guild_id": guild_id.get() as i64}, None)
+                    .await?
+                    .try_collect()
+                    .await?
+            }
+            _ => Vec::new(),
+        };
+        Ok(Self {
+            locale: ctx.locale,
+            is_owner: is_owner(ctx, user_id).await,
+            member,
+            rules,
+        })
+    }
+
+    /// Returns the reason when the user is denied the command at the end of `path`.
+    pub(crate) fn denied(&self, path: &[Arc<dyn CommandRunner>]) -> Option<String> {
+        let owner_only = path.iter().any(|command| command.permissions().owner_only);
+        if owner_only && !self.is_owner {
+            return Some(t!(self.locale, "permissions.owner_only"));
+        }
+
+        let member_permissions = self.member.and_then(|member| member.permissions);
+        let is_admin = self.member.is_some_and(is_admin);
+        for required in path
+            .iter()
+            .filter_map(|command| command.permissions().default_member_permissions)
+        {
+            let has_permissions = member_permissions
+                .map(|permissions| permissions.contains(required))
+                .unwrap_or(false);
+            if !is_admin && !has_permissions {
+                return Some(t!(
+                    self.locale,
+                    "permissions.missing_permissions",
+                    permissions = required
+                ));
+            }
+        }
+
+        let member = self.member.filter(|_| !is_admin)?;
+        let prefixes = (1..=path.len())
+            .map(|length| command_path_name(&path[..length]))
+            .collect::<Vec<String>>();
+        self.rules
+            .iter()
+            .find(|rule| {
+                prefixes.contains(&rule.command) && !rule.is_empty() && !rule.allows(member)
+            })
+            .map(|rule| t!(self.locale, "permissions.denied", command = rule.command))
+    }
+}
+
+fn is_admin(member: &Member) -> bool {
+    member
+        .permissions
+        .map(|permissions| permissions.administrator())
+        .unwrap_or(false)
+}
+
+pub(crate) fn register_commands(registry: &mut CommandRegistry) {
+    registry.register(
+        CommandGroup::new(
+            "permissions",
+            "Restrict who can use commands in this server",
+        )
+        .permissions(CommandPermissions {
+            default_member_permissions: Some(Permissions::MANAGE_GUILD),
+            ..Default::default()
+        })
+        .subcommand(PermissionsAllowCommand)
+        .subcommand(PermissionsRemoveCommand)
+        .subcommand(PermissionsResetCommand)
+        .subcommand(PermissionsShowCommand),
+    );
+}
+
+command_options! {
+    struct RuleOptions {
+        command: String => (COMMAND_OPTION, COMMAND_DESCRIPTION).autocomplete(),
+        role: Option<RoleId> => (ROLE_OPTION, "Role"),
+        user: Option<UserId> => (USER_OPTION, "User"),
+    }
+}
+
+command_options! {
+    struct ResetOptions {
+        command: String => (COMMAND_OPTION, COMMAND_DESCRIPTION).autocomplete(),
+    }
+}
+
+command_options! {
+    struct ShowOptions {
+        command: Option<String> => (COMMAND_OPTION, COMMAND_DESCRIPTION).autocomplete(),
+    }
+}
+
+async fn autocomplete_command_path(
+    ctx: &BotContext,
+    command: &CommandInteraction,
+) -> Result<Vec<AutocompleteChoice>> {
+    let input = match command.data.autocomplete() {
+        Some(option) if option.name == COMMAND_OPTION => option.value.to_lowercase(),
+        _ => return Ok(Vec::new()),
+    };
+    let registry = retrieve_command_registry(ctx.data.clone()).await?;
+    Ok(registry
+        .command_paths()
+        .into_iter()
+        .filter(|path| path.contains(input.trim()))
+        .take(25)
+        .map(|path| AutocompleteChoice::new(truncate_chars(&path, 100), path))
+        .collect())
+}
+
+/// Validates the command option against the registry and returns the normalised command path.
+///
+/// Matching ignores case since context menu commands, unlike slash commands, can have uppercase
+/// names.
+async fn resolve_command_path(ctx: &BotContext, path: &str) -> BotResult<String> {
+    let path = path
+        .trim_start_matches(|c: char| c.is_whitespace() || c == '/')
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    retrieve_command_registry(ctx.data.clone())
+        .await?
+        .command_paths()
+        .into_iter()
+        .find(|known| known.eq_ignore_ascii_case(&path))
+        .ok_or_else(|| {
+            BotError::not_found(t!(
+                ctx.locale,
+                "permissions.unknown_command",
+                command = path
+            ))
+        })
+}
+
+pub(crate) struct PermissionsAllowCommand;
+impl MakeCommandResponse for PermissionsAllowCommand {}
+
+#[async_trait]
+impl CommandRunner for PermissionsAllowCommand {
+    fn name(&self) -> &'static str {
+        "allow"
+    }
+
+    fn description(&self) -> &'static str {
+        "Allow a role or user to use a command, everyone else is denied"
+    }
+
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        RuleOptions::create_options(key)
+    }
+
+    async fn autocomplete(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> Result<Vec<AutocompleteChoice>> {
+        autocomplete_command_path(ctx, command).await
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let guild_id = command
+            .guild_id
+            .ok_or_else(|| BotError::bad_input(t!(ctx.locale, "errors.guild_only")))?;
+        let options = RuleOptions::parse(command, ctx.locale)?;
+        let path = resolve_command_path(ctx, &options.command).await?;
+        let role = options.role.map(|role| role.get() as i64);
+        let user = options.user.map(|user| user.get() as i64);
+        if role.is_none() && user.is_none() {
+            return Err(BotError::bad_input(t!(
+                ctx.locale,
+                "permissions.select_target"
+            )));
+        }
+        let mut add_to_set = doc! {};
+        if let Some(role) = role {
+            add_to_set.insert("allowed_roles", role);
+        }
+        if let Some(user) = user {
+            add_to_set.insert("allowed_users", user);
+        }
+        get_collection(ctx)
+            .await?
+            .find_one_and_update(
+                doc! {"guild_id": guild_id.get() as i64, "command": &path},
+                doc! {"$addToSet": add_to_set},
+                Some(FindOneAndUpdateOptions::builder().upsert(true).build()),
+            )
+            .await?;
+        info!("Updated permissions for /{} in guild {}", path, guild_id);
+        Ok(self.make_response(t!(ctx.locale, "permissions.updated", command = path), true))
+    }
+}
+
+pub(crate) struct PermissionsRemoveCommand;
+impl MakeCommandResponse for PermissionsRemoveCommand {}
+
+#[async_trait]
+impl CommandRunner for PermissionsRemoveCommand {
+    fn name(&self) -> &'static str {
+        "remove"
+    }
+
+    fn description(&self) -> &'static str {
+        "Remove a role or user from a command's allowlist"
+    }
+
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        RuleOptions::create_options(key)
+    }
+
+    async fn autocomplete(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> Result<Vec<AutocompleteChoice>> {
+        autocomplete_command_path(ctx, command).await
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let guild_id = command
+            .guild_id
+            .ok_or_else(|| BotError::bad_input(t!(ctx.locale, "errors.guild_only")))?;
+        let options = RuleOptions::parse(command, ctx.locale)?;
+        let path = resolve_command_path(ctx, &options.command).await?;
+        let role = options.role.map(|role| role.get() as i64);
+        let user = options.user.map(|user| user.get() as i64);
+        if role.is_none() && user.is_none() {
+            return Err(BotError::bad_input(t!(
+                ctx.locale,
+                "permissions.select_target"
+            )));
+        }
+        let mut pull = doc! {};
+        if let Some(role) = role {
+            pull.insert("allowed_roles", role);
+        }
+        if let Some(user) = user {
+            pull.insert("allowed_users", user);
+        }
+        let result = get_collection(ctx)
+            .await?
+            .update_one(
+                doc! {"guild_id": guild_id.get() as i64, "command": &path},
+                doc! {"$pull": pull},
+                None,
+            )
+            .await?;
+        if result.matched_count == 0 {
+            Ok(self.make_response(
+                t!(ctx.locale, "permissions.no_rules_for", command = path),
+                true,
+            ))
+        } else {
+            Ok(self.make_response(t!(ctx.locale, "permissions.updated", command = path), true))
+        }
+    }
+}
+
+pub(crate) struct PermissionsResetCommand;
+impl MakeCommandResponse for PermissionsResetCommand {}
+
+#[async_trait]
+impl CommandRunner for PermissionsResetCommand {
+    fn name(&self) -> &'static str {
+        "reset"
+    }
+
+    fn description(&self) -> &'static str {
+        "Remove every permission rule of a command"
+    }
+
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        ResetOptions::create_options(key)
+    }
+
+    async fn autocomplete(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> Result<Vec<AutocompleteChoice>> {
+        autocomplete_command_path(ctx, command).await
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let guild_id = command
+            .guild_id
+            .ok_or_else(|| BotError::bad_input(t!(ctx.locale, "errors.guild_only")))?;
+        let path =
+            resolve_command_path(ctx, &ResetOptions::parse(command, ctx.locale)?.command).await?;
+        get_collection(ctx)
+            .await?
+            .delete_one(
+                doc! {"guild_id": guild_id.get() as i64, "command": &path},
+                None,
+            )
+            .await?;
+        Ok(self.make_response(t!(ctx.locale, "permissions.reset", command = path), true))
+    }
+}
+
+pub(crate) struct PermissionsShowCommand;
+impl MakeCommandResponse for PermissionsShowCommand {}
+
+#[async_trait]
+impl CommandRunner for PermissionsShowCommand {
+    fn name(&self) -> &'static str {
+        "show"
+    }
+
+    fn description(&self) -> &'static str {
+        "Show permission rules of this server"
+    }
+
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        ShowOptions::create_options(key)
+    }
+
+    async fn autocomplete(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> Result<Vec<AutocompleteChoice>> {
+        autocomplete_command_path(ctx, command).await
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let guild_id = command
+            .guild_id
+            .ok_or_else(|| BotError::bad_input(t!(ctx.locale, "errors.guild_only")))?;
+        let mut filter = doc! {"guild_id": guild_id.get() as i64};
+        if let Some(path) = ShowOptions::parse(command, ctx.locale)?.command {
+            filter.insert("command", resolve_command_path(ctx, &path).await?);
+        }
+        let rules: Vec<CommandPermissionRule> = get_collection(ctx)
+            .await?
+            .find(filter, None)
+            .await?
+            .try_collect()
+            .await?;
+        let rules = rules
+            .into_iter()
+            .filter(|rule| !rule.is_empty())
+            .collect::<Vec<CommandPermissionRule>>();
+        if rules.is_empty() {
+            return Ok(self.make_response(t!(ctx.locale, "permissions.no_rules"), true));
+        }
+        let mut builder = MessageBuilder::new();
+        for rule in rules.iter() {
+            builder.push_bold_line(format!("/{}", rule.command));
+            for role in rule.allowed_roles.iter() {
+                builder.push_quote_line(format!("<@&{}>", role));
+            }
+            for user in rule.allowed_users.iter() {
+                builder.push_quote_line(format!("<@{}>", user));
+            }
+        }
+        Ok(self.make_response(builder.build(), true))
+    }
+}
+
+#[cfg(all(test, feature = "aoc", feature = "bans"))]
+mod tests {
+    use super::*;
+    use crate::testing::TestHarness;
+
+    #[tokio::test]
+    async fn resolves_registered_command_paths() {
+        let harness = TestHarness::new().await;
+        assert_eq!(
+            resolve_command_path(harness.ctx(), " /AoC  speedrun")
+                .await
+                .unwrap(),
+            "aoc speedrun"
+        );
+        assert_eq!(
+            resolve_command_path(harness.ctx(), "ban HISTORY")
+                .await
+                .unwrap(),
+            "Ban history"
+        );
+        let err = resolve_command_path(harness.ctx(), "aoc nope")
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, BotError::NotFound(_)), "{}", err);
+    }
+}
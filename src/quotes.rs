@@ -0,0 +1,120 @@
+use chrono::{DateTime, Utc};
+use mongodb::bson::doc;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{
+        CommandInteraction, CommandType, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter,
+        Message, ResolvedTarget,
+    },
+    async_trait,
+};
+use tracing::info;
+
+use crate::{
+    commands::registry::CommandRegistry,
+    context::BotContext,
+    error::{BotError, BotResult},
+    i18n::t,
+    util::{retrieve_db_handle, CommandRunner, MakeCommandResponse},
+    CommandResponse,
+};
+
+const QUOTES_COLLECTION: &str = "quotes";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Quote {
+    guild_id: i64,
+    channel_id: i64,
+    message_id: i64,
+    author_id: i64,
+    author_name: String,
+    content: String,
+    saved_by: i64,
+    timestamp: DateTime<Utc>,
+}
+
+pub(crate) fn register_commands(registry: &mut CommandRegistry) {
+    registry.register(SaveQuoteCommand);
+}
+
+pub(crate) struct SaveQuoteCommand;
+impl MakeCommandResponse for SaveQuoteCommand {}
+
+impl SaveQuoteCommand {
+    fn quote_embed(message: &Message) -> CreateEmbed {
+        let author = &message.author;
+        let mut embed_author =
+            CreateEmbedAuthor::new(author.global_name.as_ref().unwrap_or(&author.name));
+        if let Some(avatar) = author.avatar_url() {
+            embed_author = embed_author.icon_url(avatar);
+        }
+        CreateEmbed::new()
+            .author(embed_author)
+            .description(&message.content)
+            .timestamp(message.timestamp)
+    }
+}
+
+#[async_trait]
+impl CommandRunner for SaveQuoteCommand {
+    fn name(&self) -> &'static str {
+        "Save as quote"
+    }
+
+    fn description(&self) -> &'static str {
+        "Saves the message as a quote"
+    }
+
+    fn kind(&self) -> CommandType {
+        CommandType::Message
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let guild_id = command
+            .guild_id
+            .ok_or_else(|| BotError::bad_input(t!(ctx.locale, "errors.guild_only")))?;
+        let Some(ResolvedTarget::Message(message)) = command.data.target() else {
+            return Err(BotError::bad_input(t!(ctx.locale, "quotes.target_message")));
+        };
+        if message.content.trim().is_empty() {
+            return Err(BotError::bad_input(t!(ctx.locale, "quotes.no_text")));
+        }
+        let collection = retrieve_db_handle(ctx.data.clone())
+            .await?
+            .collection::<Quote>(QUOTES_COLLECTION);
+        let existing = collection
+            .find_one(
+                doc! {
+                    "guild_id": guild_id.get() as i64,
+                    "message_id": message.id.get() as i64,
+                },
+                None,
+            )
+            .await?;
+        if existing.is_some() {
+            return Err(BotError::bad_input(t!(ctx.locale, "quotes.already_saved")));
+        }
+        let quote = Quote {
+            guild_id: guild_id.get() as i64,
+            channel_id: message.channel_id.get() as i64,
+            message_id: message.id.get() as i64,
+            author_id: message.author.id.get() as i64,
+            author_name: message.author.name.clone(),
+            content: message.content.clone(),
+            saved_by: command.user.id.get() as i64,
+            timestamp: Utc::now(),
+        };
+        collection.insert_one(&quote, None).await?;
+        info!("Saved quote: {:#?}", quote);
+        let embed = Self::quote_embed(message).footer(CreateEmbedFooter::new(t!(
+            ctx.locale,
+            "quotes.saved_by",
+            user = command.user.name
+        )));
+        Ok(self.make_response("", false).embed(embed))
+    }
+}
@@ -0,0 +1,756 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use mongodb::{
+    bson::{doc, oid::ObjectId, Document},
+    options::FindOptions,
+    Collection,
+};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{
+        AutocompleteChoice, ChannelId, CommandInteraction, CreateAllowedMentions,
+        CreateCommandOption, CreateMessage, Mentionable, Permissions, UserId,
+    },
+    async_trait,
+    futures::TryStreamExt,
+    utils::MessageBuilder,
+};
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::{
+    commands::{
+        group::CommandGroup,
+        options::{command_options, CommandOptions},
+        registry::CommandRegistry,
+    },
+    context::BotContext,
+    error::{BotError, BotResult},
+    i18n::{t, Locale},
+    scheduler::{Job, Schedule, Scheduler},
+    util::{
+        format_duration, parse_duration, retrieve_db_handle, truncate_chars, CommandRunner,
+        MakeCommandResponse,
+    },
+    CommandResponse,
+};
+
+const REMINDERS_COLLECTION: &str = "reminders";
+
+const IN_OPTION: &str = "in";
+const MESSAGE_OPTION: &str = "message";
+const CHANNEL_OPTION: &str = "channel";
+const DM_OPTION: &str = "dm";
+const REPEAT_OPTION: &str = "repeat";
+const REMINDER_OPTION: &str = "reminder";
+
+const MAX_REMINDERS: u64 = 25;
+const MIN_REPEAT: Duration = Duration::from_secs(10 * 60);
+const MAX_REPEAT: Duration = Duration::from_secs(365 * DAY_SECS);
+/// Furthest a reminder can be set in the future, keeps due times far from overflowing.
+const MAX_DUE: Duration = Duration::from_secs(5 * 365 * DAY_SECS);
+const DAY_SECS: u64 = 24 * 60 * 60;
+const MAX_MESSAGE_LENGTH: u16 = 1000;
+/// How long a claimed reminder is left alone, covers the bot stopping halfway through a delivery.
+const CLAIM_LEASE: i64 = 60;
+const MAX_DELIVERY_ATTEMPTS: u32 = 5;
+/// Delay before the next delivery attempt, multiplied by the number of failed attempts.
+const RETRY_DELAY: i64 = 60;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Reminder {
+    #[serde(rename = "_id")]
+    id: ObjectId,
+    user_id: i64,
+    guild_id: Option<i64>,
+    /// Channel the reminder is posted in, sent as a direct message when missing.
+    channel_id: Option<i64>,
+    message: String,
+    /// Unix timestamp of the next delivery.
+    due_at: i64,
+    repeat_secs: Option<i64>,
+    locale: Locale,
+    /// Unix timestamp until which a delivery is in progress or waiting to be retried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    claimed_until: Option<i64>,
+    /// Failed deliveries of the current due time.
+    #[serde(default)]
+    attempts: u32,
+}
+
+/// What happens to a claimed reminder after a delivery attempt.
+#[derive(Debug, PartialEq, Eq)]
+enum Settlement {
+    /// One-off reminder that was delivered or given up on.
+    Remove,
+    /// Repeating reminder that was delivered or given up on, moves to its next due time.
+    Reschedule(i64),
+    /// Failed delivery that stays claimed until it's retried.
+    Retry { attempts: u32, retry_at: i64 },
+}
+
+impl Reminder {
+    fn short_id(&self) -> String {
+        self.id.to_hex()[16..].to_string()
+    }
+
+    fn destination(&self, locale: Locale) -> String {
+        match self.channel_id {
+            Some(channel_id) => format!("<#{}>", channel_id),
+            None => t!(locale, "remind.dm"),
+        }
+    }
+
+    /// First due time after `now` for repeating reminders, runs missed while the bot was offline
+    /// are skipped.
+    fn next_due(&self, now: i64) -> Option<i64> {
+        let repeat = self.repeat_secs.filter(|repeat| *repeat > 0)?;
+        let missed = now.saturating_sub(self.due_at).max(0) / repeat;
+        self.due_at
+            .checked_add(missed.checked_add(1)?.checked_mul(repeat)?)
+    }
+
+    fn settle(&self, delivered: bool, now: i64) -> Settlement {
+        let attempts = self.attempts + 1;
+        if !delivered && attempts < MAX_DELIVERY_ATTEMPTS {
+            return Settlement::Retry {
+                attempts,
+                retry_at: now + RETRY_DELAY * attempts as i64,
+            };
+        }
+        match self.next_due(now) {
+            Some(next_due) => Settlement::Reschedule(next_due),
+            None => Settlement::Remove,
+        }
+    }
+
+    fn summary(&self, locale: Locale) -> String {
+        let mut summary = t!(
+            locale,
+            "remind.summary",
+            id = self.short_id(),
+            timestamp = self.due_at,
+            destination = self.destination(locale)
+        );
+        if let Some(repeat) = self.repeat_secs {
+            summary.push_str(&t!(
+                locale,
+                "remind.every",
+                interval = format_duration(Duration::from_secs(repeat as u64))
+            ));
+        }
+        summary
+    }
+}
+
+fn get_collection(db_handle: &mongodb::Database) -> Collection<Reminder> {
+    db_handle.collection(REMINDERS_COLLECTION)
+}
+
+async fn user_reminders(ctx: &BotContext, user_id: UserId) -> Result<Vec<Reminder>> {
+    Ok(get_collection(&retrieve_db_handle(ctx.data.clone()).await?)
+        .find(
+            doc! {"user_id": user_id.get() as i64},
+            FindOptions::builder().sort(doc! {"due_at": 1}).build(),
+        )
+        .await?
+        .try_collect()
+        .await?)
+}
+
+/// Parses when a reminder is due, either a duration from now (`1h 30m`) or a UTC time today or
+/// tomorrow (`18:30`) or on a date (`2024-12-01 18:30`).
+fn parse_due(input: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let latest = now.checked_add_signed(chrono::Duration::from_std(MAX_DUE).ok()?)?;
+    parse_due_time(input.trim(), now).filter(|due| *due <= latest)
+}
+
+fn parse_due_time(input: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if let Some(duration) = parse_duration(input).filter(|duration| *duration <= MAX_DUE) {
+        return now.checked_add_signed(chrono::Duration::from_std(duration).ok()?);
+    }
+    if let Ok(time) = NaiveTime::parse_from_str(input, "%H:%M") {
+        let today = Utc.from_utc_datetime(&now.date_naive().and_time(time));
+        return Some(if today > now {
+            today
+        } else {
+            today + chrono::Duration::try_days(1)?
+        });
+    }
+    if let Ok(date_time) = NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M") {
+        return Some(Utc.from_utc_datetime(&date_time)).filter(|due| *due > now);
+    }
+    NaiveDate::parse_from_str(input, "%Y-%m-%d")
+        .ok()
+        .map(|date| Utc.from_utc_datetime(&date.and_time(NaiveTime::MIN)))
+        .filter(|due| *due > now)
+}
+
+pub(crate) fn register_commands(registry: &mut CommandRegistry) {
+    registry.register(
+        CommandGroup::new("remind", "Reminders")
+            .subcommand(RemindSetCommand)
+            .subcommand(RemindListCommand)
+            .subcommand(RemindCancelCommand),
+    );
+}
+
+command_options! {
+    struct SetOptions {
+        when: String => (IN_OPTION, "Duration like 1h 30m, or a UTC time like 18:30 or 2024-12-01 18:30"),
+        message: String => (MESSAGE_OPTION, "What to remind you of").length(1, MAX_MESSAGE_LENGTH),
+        channel: Option<ChannelId> => (CHANNEL_OPTION, "Channel to post the reminder in, this one by default"),
+        dm: Option<bool> => (DM_OPTION, "Send the reminder as a direct message"),
+        repeat: Option<String> => (REPEAT_OPTION, "Repeat the reminder, e.g. 1d"),
+    }
+}
+
+command_options! {
+    struct CancelOptions {
+        reminder: String => (REMINDER_OPTION, "Reminder to cancel").autocomplete(),
+    }
+}
+
+pub(crate) struct RemindSetCommand;
+impl MakeCommandResponse for RemindSetCommand {}
+
+impl RemindSetCommand {
+    fn channel(
+        ctx: &BotContext,
+        command: &CommandInteraction,
+        options: &SetOptions,
+    ) -> BotResult<Option<ChannelId>> {
+        if options.dm.unwrap_or(false) || command.guild_id.is_none() {
+            return Ok(None);
+        }
+        let Some(channel_id) = options.channel else {
+            return Ok(Some(command.channel_id));
+        };
+        let can_send = command
+            .data
+            .resolved
+            .channels
+            .get(&channel_id)
+            .and_then(|channel| channel.permissions)
+            .map(|permissions| permissions.contains(Permissions::SEND_MESSAGES))
+            .unwrap_or(true);
+        if !can_send {
+            return Err(BotError::bad_input(t!(
+                ctx.locale,
+                "remind.cannot_send",
+                channel = channel_id.mention()
+            )));
+        }
+        Ok(Some(channel_id))
+    }
+}
+
+#[async_trait]
+impl CommandRunner for RemindSetCommand {
+    fn name(&self) -> &'static str {
+        "set"
+    }
+
+    fn description(&self) -> &'static str {
+        "Set a reminder"
+    }
+
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        SetOptions::create_options(key)
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let options = SetOptions::parse(command, ctx.locale)?;
+        let now = Utc::now();
+        let due_at = parse_due(&options.when, now).ok_or_else(|| {
+            BotError::bad_input(t!(
+                ctx.locale,
+                "remind.invalid_time",
+                input = options.when,
+                max_days = MAX_DUE.as_secs() / DAY_SECS
+            ))
+        })?;
+        let repeat = match options.repeat.as_deref() {
+            Some(repeat) => match parse_duration(repeat) {
+                Some(repeat) if (MIN_REPEAT..=MAX_REPEAT).contains(&repeat) => Some(repeat),
+                _ => {
+                    return Err(BotError::bad_input(t!(
+                        ctx.locale,
+                        "remind.invalid_repeat",
+                        min = format_duration(MIN_REPEAT),
+                        max_days = MAX_REPEAT.as_secs() / DAY_SECS
+                    )))
+                }
+            },
+            None => None,
+        };
+        let channel_id = Self::channel(ctx, command, &options)?;
+
+        let collection = get_collection(&retrieve_db_handle(ctx.data.clone()).await?);
+        let user_id = command.user.id.get() as i64;
+        if collection
+            .count_documents(doc! {"user_id": user_id}, None)
+            .await?
+            >= MAX_REMINDERS
+        {
+            return Err(BotError::bad_input(t!(
+                ctx.locale,
+                "remind.too_many",
+                max = MAX_REMINDERS
+            )));
+        }
+        let reminder = Reminder {
+            id: ObjectId::new(),
+            user_id,
+            guild_id: command.guild_id.map(|guild_id| guild_id.get() as i64),
+            channel_id: channel_id.map(|channel_id| channel_id.get() as i64),
+            message: options.message,
+            due_at: due_at.timestamp(),
+            repeat_secs: repeat.map(|repeat| repeat.as_secs() as i64),
+            locale: ctx.locale,
+            claimed_until: None,
+            attempts: 0,
+        };
+        collection.insert_one(&reminder, None).await?;
+        info!(reminder_id = %reminder.id, "Reminder set");
+        Ok(self.make_response(
+            t!(
+                ctx.locale,
+                "remind.set",
+                summary = reminder.summary(ctx.locale)
+            ),
+            true,
+        ))
+    }
+}
+
+pub(crate) struct RemindListCommand;
+impl MakeCommandResponse for RemindListCommand {}
+
+#[async_trait]
+impl CommandRunner for RemindListCommand {
+    fn name(&self) -> &'static str {
+        "list"
+    }
+
+    fn description(&self) -> &'static str {
+        "List your reminders"
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let reminders = user_reminders(ctx, command.user.id).await?;
+        if reminders.is_empty() {
+            return Ok(self.make_response(t!(ctx.locale, "remind.no_reminders"), true));
+        }
+        let mut builder = MessageBuilder::new();
+        for reminder in reminders.iter() {
+            builder
+                .push_line(reminder.summary(ctx.locale))
+                .push_quote_line_safe(truncate_chars(&reminder.message, 200));
+        }
+        Ok(self.make_response(builder.build(), true))
+    }
+}
+
+pub(crate) struct RemindCancelCommand;
+impl MakeCommandResponse for RemindCancelCommand {}
+
+#[async_trait]
+impl CommandRunner for RemindCancelCommand {
+    fn name(&self) -> &'static str {
+        "cancel"
+    }
+
+    fn description(&self) -> &'static str {
+        "Cancel one of your reminders"
+    }
+
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        CancelOptions::create_options(key)
+    }
+
+    async fn autocomplete(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> Result<Vec<AutocompleteChoice>> {
+        let input = match command.data.autocomplete() {
+            Some(option) if option.name == REMINDER_OPTION => option.value.to_lowercase(),
+            _ => return Ok(Vec::new()),
+        };
+        Ok(user_reminders(ctx, command.user.id)
+            .await?
+            .into_iter()
+            .filter(|reminder| {
+                reminder.short_id().contains(input.trim())
+                    || reminder.message.to_lowercase().contains(input.trim())
+            })
+            .take(25)
+            .map(|reminder| {
+                AutocompleteChoice::new(
+                    truncate_chars(
+                        &format!("{}: {}", reminder.short_id(), reminder.message),
+                        100,
+                    ),
+                    reminder.short_id(),
+                )
+            })
+            .collect())
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let options = CancelOptions::parse(command, ctx.locale)?;
+        let input = options.reminder.trim().to_lowercase();
+        let reminder = user_reminders(ctx, command.user.id)
+            .await?
+            .into_iter()
+            .find(|reminder| reminder.short_id() == input || reminder.id.to_hex() == input)
+            .ok_or_else(|| {
+                BotError::not_found(t!(ctx.locale, "remind.not_found", id = options.reminder))
+            })?;
+        get_collection(&retrieve_db_handle(ctx.data.clone()).await?)
+            .delete_one(doc! {"_id": reminder.id}, None)
+            .await?;
+        info!(reminder_id = %reminder.id, "Reminder cancelled");
+        Ok(self.make_response(
+            t!(ctx.locale, "remind.cancelled", id = reminder.short_id()),
+            true,
+        ))
+    }
+}
+
+/// Delivers due reminders. Reminders are stored in Mongo, so the job itself keeps no state.
+pub(crate) struct ReminderJob;
+
+impl ReminderJob {
+    /// Reminders that are due and not claimed by a delivery in progress or waiting for a retry.
+    fn due_filter(now: i64) -> Document {
+        doc! {
+            "due_at": {"$lte": now},
+            "$or": [{"claimed_until": null}, {"claimed_until": {"$lte": now}}],
+        }
+    }
+
+    /// Leases the reminder for a delivery and returns the end of the lease, or nothing when the
+    /// reminder was cancelled, delivered or claimed in the meantime.
+    async fn claim(
+        collection: &Collection<Reminder>,
+        reminder: &Reminder,
+        now: i64,
+    ) -> Result<Option<i64>> {
+        let claimed_until = now + CLAIM_LEASE;
+        let mut filter = Self::due_filter(now);
+        filter.insert("_id", reminder.id);
+        let claimed = collection
+            .update_one(
+                filter,
+                doc! {"$set": {"claimed_until": claimed_until}},
+                None,
+            )
+            .await?
+            .modified_count;
+        Ok((claimed == 1).then_some(claimed_until))
+    }
+
+    /// Applies the outcome of a delivery, as long as the reminder is still under our lease.
+    async fn settle(
+        collection: &Collection<Reminder>,
+        reminder: &Reminder,
+        claimed_until: i64,
+        settlement: Settlement,
+    ) -> Result<()> {
+        let filter = doc! {"_id": reminder.id, "claimed_until": claimed_until};
+        match settlement {
+            Settlement::Remove => {
+                collection.delete_one(filter, None).await?;
+            }
+            Settlement::Reschedule(next_due) => {
+                let update = doc! {
+                    "$set": {"due_at": next_due, "attempts": 0},
+                    "$unset": {"claimed_until": ""},
+                };
+                collection.update_one(filter, update, None).await?;
+            }
+            Settlement::Retry { attempts, retry_at } => {
+                let update = doc! {"$set": {"attempts": attempts, "claimed_until": retry_at}};
+                collection.update_one(filter, update, None).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn deliver(ctx: &BotContext, reminder: &Reminder) -> Result<()> {
+        let user_id = UserId::new(reminder.user_id as u64);
+        let content = MessageBuilder::new()
+            .push(t!(
+                reminder.locale,
+                "remind.delivery",
+                user = user_id.mention()
+            ))
+            .push_quote_line_safe(&reminder.message)
+            .build();
+        // The message is user input, only the owner of the reminder may be pinged
+        let message = CreateMessage::new()
+            .content(content)
+            .allowed_mentions(CreateAllowedMentions::new().users([user_id]));
+        match reminder.channel_id {
+            Some(channel_id) => {
+                ChannelId::new(channel_id as u64)
+                    .send_message(&ctx.http, message)
+                    .await?
+            }
+            None => {
+                user_id
+                    .create_dm_channel(&ctx.http)
+                    .await?
+                    .send_message(&ctx.http, message)
+                    .await?
+            }
+        };
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Job for ReminderJob {
+    fn name(&self) -> &'static str {
+        "reminders"
+    }
+
+    fn schedule(&self) -> Schedule {
+        Schedule::every(Duration::from_secs(15))
+    }
+
+    fn persistent(&self) -> bool {
+        false
+    }
+
+    async fn run(&self, ctx: &BotContext) -> Result<()> {
+        let collection = get_collection(&retrieve_db_handle(ctx.data.clone()).await?);
+        let now = Utc::now().timestamp();
+        let due: Vec<Reminder> = collection
+            .find(Self::due_filter(now), None)
+            .await?
+            .try_collect()
+            .await?;
+        for reminder in due {
+            let span = info_span!(
+                "reminder",
+                reminder_id = %reminder.id,
+                user_id = reminder.user_id,
+                guild_id = reminder.guild_id,
+            );
+            async {
+                let claimed_until = match Self::claim(&collection, &reminder, now).await {
+                    Ok(Some(claimed_until)) => claimed_until,
+                    Ok(None) => return,
+                    Err(e) => {
+                        warn!("Failed to claim reminder: {:#?}", e);
+                        return;
+                    }
+                };
+                let delivered = match Self::deliver(ctx, &reminder).await {
+                    Ok(()) => {
+                        info!("Reminder delivered");
+                        true
+                    }
+                    Err(e) => {
+                        warn!(
+                            attempt = reminder.attempts + 1,
+                            "Failed to deliver reminder: {:#?}", e
+                        );
+                        false
+                    }
+                };
+                let settlement = reminder.settle(delivered, now);
+                if let Err(e) =
+                    Self::settle(&collection, &reminder, claimed_until, settlement).await
+                {
+                    warn!("Failed to update reminder after delivery: {:#?}", e);
+                }
+            }
+            .instrument(span)
+            .await;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn register_jobs(scheduler: &mut Scheduler) {
+    scheduler.register(ReminderJob);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{InteractionBuilder, TestHarness};
+
+    #[test]
+    fn parses_due_times() {
+        let now = Utc.with_ymd_and_hms(2023, 12, 1, 20, 0, 0).unwrap();
+        assert_eq!(
+            parse_due("1h 30m", now),
+            Utc.with_ymd_and_hms(2023, 12, 1, 21, 30, 0).single()
+        );
+        assert_eq!(
+            parse_due("21:15", now),
+            Utc.with_ymd_and_hms(2023, 12, 1, 21, 15, 0).single()
+        );
+        assert_eq!(
+            parse_due("06:00", now),
+            Utc.with_ymd_and_hms(2023, 12, 2, 6, 0, 0).single()
+        );
+        assert_eq!(
+            parse_due("2023-12-24 18:00", now),
+            Utc.with_ymd_and_hms(2023, 12, 24, 18, 0, 0).single()
+        );
+        assert_eq!(parse_due("2023-11-24 18:00", now), None);
+        assert_eq!(parse_due("soon", now), None);
+    }
+
+    #[test]
+    fn rejects_due_times_too_far_ahead() {
+        let now = Utc.with_ymd_and_hms(2023, 12, 1, 20, 0, 0).unwrap();
+        assert_eq!(
+            parse_due("260w", now),
+            Utc.with_ymd_and_hms(2028, 11, 24, 20, 0, 0).single()
+        );
+        assert_eq!(parse_due("100000000w", now), None);
+        assert_eq!(parse_due("1000000000000000s", now), None);
+        assert_eq!(parse_due("9999-12-31 23:59", now), None);
+    }
+
+    #[test]
+    fn stops_repeating_on_overflow() {
+        let reminder = Reminder {
+            id: ObjectId::new(),
+            user_id: 1,
+            guild_id: None,
+            channel_id: None,
+            message: "stretch".to_string(),
+            due_at: i64::MAX - 100,
+            repeat_secs: Some(600),
+            locale: Locale::En,
+            claimed_until: None,
+            attempts: 0,
+        };
+        assert_eq!(reminder.next_due(i64::MAX - 50), None);
+        let stale = Reminder {
+            due_at: i64::MIN,
+            repeat_secs: Some(1),
+            ..reminder
+        };
+        assert_eq!(stale.next_due(i64::MAX), None);
+    }
+
+    #[test]
+    fn skips_missed_repeats() {
+        let reminder = Reminder {
+            id: ObjectId::new(),
+            user_id: 1,
+            guild_id: None,
+            channel_id: None,
+            message: "stretch".to_string(),
+            due_at: 1000,
+            repeat_secs: Some(600),
+            locale: Locale::En,
+            claimed_until: None,
+            attempts: 0,
+        };
+        assert_eq!(reminder.next_due(1000), Some(1600));
+        assert_eq!(reminder.next_due(2500), Some(2800));
+        let once = Reminder {
+            repeat_secs: None,
+            ..reminder
+        };
+        assert_eq!(once.next_due(1000), None);
+    }
+
+    #[test]
+    fn retries_failed_deliveries() {
+        let reminder = Reminder {
+            id: ObjectId::new(),
+            user_id: 1,
+            guild_id: None,
+            channel_id: Some(2),
+            message: "stretch".to_string(),
+            due_at: 1000,
+            repeat_secs: None,
+            locale: Locale::En,
+            claimed_until: Some(1060),
+            attempts: 0,
+        };
+        assert_eq!(
+            reminder.settle(false, 1000),
+            Settlement::Retry {
+                attempts: 1,
+                retry_at: 1000 + RETRY_DELAY
+            }
+        );
+        assert_eq!(reminder.settle(true, 1000), Settlement::Remove);
+        let exhausted = Reminder {
+            attempts: MAX_DELIVERY_ATTEMPTS - 1,
+            ..reminder.clone()
+        };
+        assert_eq!(exhausted.settle(false, 1300), Settlement::Remove);
+        let repeating = Reminder {
+            repeat_secs: Some(600),
+            ..exhausted
+        };
+        assert_eq!(repeating.settle(false, 1300), Settlement::Reschedule(1600));
+        assert_eq!(
+            Reminder {
+                attempts: 2,
+                ..reminder
+            }
+            .settle(false, 1300),
+            Settlement::Retry {
+                attempts: 3,
+                retry_at: 1300 + 3 * RETRY_DELAY
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_times() {
+        let harness = TestHarness::new().await;
+        let command = InteractionBuilder::new("remind")
+            .subcommand("set")
+            .option("in", "whenever")
+            .option("message", "water the plants")
+            .build();
+        let err = harness.dispatch(&command).await.err().unwrap();
+        assert!(matches!(err, BotError::BadInput(_)), "{}", err);
+        let command = InteractionBuilder::new("remind")
+            .subcommand("set")
+            .option("in", "10m")
+            .option("message", "water the plants")
+            .option("repeat", "1m")
+            .build();
+        let err = harness.dispatch(&command).await.err().unwrap();
+        assert!(matches!(err, BotError::BadInput(_)), "{}", err);
+        for (when, repeat) in [("100000000w", "1d"), ("10m", "1000000000000000s")] {
+            let command = InteractionBuilder::new("remind")
+                .subcommand("set")
+                .option("in", when)
+                .option("message", "water the plants")
+                .option("repeat", repeat)
+                .build();
+            let err = harness.dispatch(&command).await.err().unwrap();
+            assert!(matches!(err, BotError::BadInput(_)), "{}", err);
+        }
+    }
+}
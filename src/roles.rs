@@ -1,16 +1,34 @@
 use anyhow::Result;
 
-use log::info;
 use mongodb::{bson::doc, Collection};
 use serde::{Deserialize, Serialize};
 use serenity::{
-    all::EditMember,
+    all::{EditMember, GatewayIntents, GuildId},
+    async_trait,
     futures::StreamExt,
     model::prelude::{Member, RoleId},
     prelude::Context,
 };
+use tracing::info;
 
-use crate::{event_handlers::mr_handler::MrHandler, util::retrieve_db_handle};
+use crate::{
+    guild_config::GuildModule,
+    modules::Module,
+    util::{retrieve_db_handle, retrieve_guild_config_service},
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct RolesConfig {
+    /// Saves members' roles and nicknames and restores them when they rejoin.
+    pub(crate) enabled: bool,
+}
+
+impl Default for RolesConfig {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct SavedUser {
@@ -32,7 +50,44 @@ impl SavedUser {
     }
 }
 
-impl MrHandler {
+/// Remembers members' roles and nicknames and restores them when they rejoin.
+pub(crate) struct RolesModule;
+
+#[async_trait]
+impl Module for RolesModule {
+    fn name(&self) -> &'static str {
+        "roles"
+    }
+
+    fn guild_module(&self) -> Option<GuildModule> {
+        Some(GuildModule::Roles)
+    }
+
+    fn intents(&self) -> GatewayIntents {
+        GatewayIntents::GUILD_MEMBERS
+    }
+
+    async fn guild_member_update(&self, ctx: &Context, member: &Member) -> Result<()> {
+        self.save_member_roles_on_update(ctx, member).await
+    }
+
+    async fn guild_member_addition(&self, ctx: &Context, member: &Member) -> Result<()> {
+        self.grant_roles_and_nickname(ctx, member).await
+    }
+
+    async fn cache_ready(&self, ctx: &Context, _guilds: &[GuildId]) -> Result<()> {
+        self.save_roles_on_startup(ctx).await
+    }
+}
+
+impl RolesModule {
+    async fn is_enabled(&self, ctx: &Context, guild_id: GuildId) -> Result<bool> {
+        retrieve_guild_config_service(ctx.data.clone())
+            .await?
+            .is_enabled(guild_id, GuildModule::Roles)
+            .await
+    }
+
     async fn record_roles(
         &self,
         collection: &Collection<SavedUser>,
@@ -78,9 +133,12 @@ impl MrHandler {
         })
     }
 
-    pub async fn save_roles_on_startup(&self, ctx: &Context) -> Result<()> {
+    async fn save_roles_on_startup(&self, ctx: &Context) -> Result<()> {
         let database_handle = retrieve_db_handle(ctx.data.clone()).await?;
         for guild in ctx.cache.guilds() {
+            if !self.is_enabled(ctx, guild).await? {
+                continue;
+            }
             info!("Saving members for guild: {}", guild.get());
             let saved_users_collection =
                 database_handle.collection::<SavedUser>(&guild.get().to_string());
@@ -99,7 +157,10 @@ impl MrHandler {
     }
 
     // TODO: check what changed
-    pub async fn save_member_roles_on_update(&self, ctx: &Context, member: &Member) -> Result<()> {
+    async fn save_member_roles_on_update(&self, ctx: &Context, member: &Member) -> Result<()> {
+        if !self.is_enabled(ctx, member.guild_id).await? {
+            return Ok(());
+        }
         info!(
             "Saving member {} in guild {}",
             member.user.id.get(),
@@ -116,7 +177,10 @@ impl MrHandler {
         Ok(())
     }
 
-    pub async fn grant_roles_and_nickname(&self, ctx: &Context, member: &mut Member) -> Result<()> {
+    async fn grant_roles_and_nickname(&self, ctx: &Context, member: &Member) -> Result<()> {
+        if !self.is_enabled(ctx, member.guild_id).await? {
+            return Ok(());
+        }
         let database_handle = retrieve_db_handle(ctx.data.clone()).await?;
         let guild_id = member.guild_id.get() as i64;
         let saved_users_collection = database_handle.collection::<SavedUser>(&guild_id.to_string());
@@ -136,7 +200,12 @@ impl MrHandler {
             }
             if let Some(nickname) = saved_user.nickname {
                 member
-                    .edit(&ctx.http, EditMember::new().nickname(nickname))
+                    .guild_id
+                    .edit_member(
+                        &ctx.http,
+                        member.user.id,
+                        EditMember::new().nickname(nickname),
+                    )
                     .await?;
                 info!("Updated nickname for member: {}", member.user.id.get());
             }
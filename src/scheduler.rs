@@ -0,0 +1,671 @@
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, TimeZone, Utc};
+use mongodb::{
+    bson::doc,
+    options::{FindOneAndUpdateOptions, ReturnDocument, UpdateOptions},
+    Collection, Database,
+};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serenity::{
+    all::{AutocompleteChoice, CommandInteraction, CreateCommandOption},
+    async_trait,
+    prelude::TypeMapKey,
+    utils::MessageBuilder,
+};
+use tokio::{sync::Notify, time::timeout};
+use tracing::{debug, error, info, info_span, warn, Instrument};
+
+use crate::{
+    commands::{
+        group::CommandGroup,
+        options::{command_options, CommandOptions},
+        registry::CommandRegistry,
+    },
+    context::BotContext,
+    error::{BotError, BotResult},
+    i18n::{t, Locale},
+    permissions::CommandPermissions,
+    shutdown::Shutdown,
+    util::{
+        format_duration, retrieve_metrics, retrieve_scheduler, truncate_chars, CommandRunner,
+        MakeCommandResponse,
+    },
+    CommandResponse,
+};
+
+const SCHEDULED_JOBS_COLLECTION: &str = "scheduled_jobs";
+
+const JOB_OPTION: &str = "job";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// When a job runs.
+#[derive(Clone, Debug)]
+pub(crate) enum Schedule {
+    /// Fixed delay between the starts of two runs. A job that was never run starts right away.
+    Interval(Duration),
+    /// Cron expression with seconds, e.g. `0 0 6 * 12 *` for 6:00 UTC every day of December.
+    Cron(Box<cron::Schedule>),
+}
+
+impl Schedule {
+    pub(crate) fn every(interval: Duration) -> Self {
+        Self::Interval(interval)
+    }
+
+    pub(crate) fn cron(expression: &str) -> Result<Self> {
+        let schedule = cron::Schedule::from_str(expression)
+            .with_context(|| format!("Invalid cron expression {}", expression))?;
+        Ok(Self::Cron(Box::new(schedule)))
+    }
+
+    /// Next run after a run that started at `last_run`. It can be in the past when runs were
+    /// missed, e.g. while the bot was offline, missed runs are caught up with a single run.
+    fn next_after(&self, last_run: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Interval(interval) => {
+                Some(last_run + chrono::Duration::from_std(*interval).ok()?)
+            }
+            Self::Cron(schedule) => schedule.after(&last_run).next(),
+        }
+    }
+
+    fn first_run(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Interval(_) => Some(now),
+            Self::Cron(schedule) => schedule.after(&now).next(),
+        }
+    }
+
+    fn describe(&self, locale: Locale) -> String {
+        match self {
+            Self::Interval(interval) => {
+                t!(locale, "jobs.every", interval = format_duration(*interval))
+            }
+            Self::Cron(schedule) => t!(locale, "jobs.cron", expression = schedule),
+        }
+    }
+}
+
+/// Exponential delay before retrying a failed run, instead of waiting for the next scheduled
+/// one.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Backoff {
+    pub(crate) initial: Duration,
+    pub(crate) max: Duration,
+}
+
+impl Backoff {
+    fn delay(&self, failures: u32) -> Duration {
+        let exponent = failures.saturating_sub(1).min(16);
+        self.initial.saturating_mul(1 << exponent).min(self.max)
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(30),
+            max: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// Background work run by the [`Scheduler`].
+#[async_trait]
+pub(crate) trait Job: Send + Sync {
+    /// Unique name, also the key of the job's state in Mongo.
+    fn name(&self) -> &'static str;
+
+    fn schedule(&self) -> Schedule;
+
+    /// Up to this much is randomly added to every scheduled run, so runs are spread out.
+    fn jitter(&self) -> Duration {
+        Duration::ZERO
+    }
+
+    fn backoff(&self) -> Backoff {
+        Backoff::default()
+    }
+
+    /// Runs taking longer are cancelled and count as failed.
+    fn timeout(&self) -> Duration {
+        DEFAULT_TIMEOUT
+    }
+
+    /// Whether the last run is stored in Mongo so the schedule survives restarts. Jobs whose
+    /// work does not outlive the process, e.g. leaving idle voice calls, keep it in memory.
+    fn persistent(&self) -> bool {
+        true
+    }
+
+    async fn run(&self, ctx: &BotContext) -> Result<()>;
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct JobRecord {
+    #[serde(rename = "_id")]
+    name: String,
+    last_run: Option<i64>,
+    last_duration_ms: Option<i64>,
+    last_error: Option<String>,
+    #[serde(default)]
+    failures: u32,
+    /// Other runs are locked out until then, so runs of the same job never overlap even across
+    /// restarts.
+    locked_until: Option<i64>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct JobState {
+    last_run: Option<DateTime<Utc>>,
+    last_duration: Option<Duration>,
+    last_error: Option<String>,
+    failures: u32,
+    next_run: Option<DateTime<Utc>>,
+}
+
+impl From<JobRecord> for JobState {
+    fn from(record: JobRecord) -> Self {
+        Self {
+            last_run: record
+                .last_run
+                .and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single()),
+            last_duration: record
+                .last_duration_ms
+                .map(|millis| Duration::from_millis(millis.max(0) as u64)),
+            last_error: record.last_error,
+            failures: record.failures,
+            next_run: None,
+        }
+    }
+}
+
+struct ScheduledJob {
+    job: Box<dyn Job>,
+    state: Mutex<JobState>,
+    running: AtomicBool,
+    trigger: Notify,
+}
+
+impl ScheduledJob {
+    fn state(&self) -> JobState {
+        self.state.lock().expect("Job state lock poisoned").clone()
+    }
+
+    /// Next run according to the schedule, or after the backoff delay when the last run failed.
+    fn next_run(&self, state: &JobState, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let next_run = match state.last_run {
+            Some(last_run) if state.failures > 0 => {
+                let delay = self.job.backoff().delay(state.failures);
+                return Some(last_run + chrono::Duration::from_std(delay).ok()?);
+            }
+            Some(last_run) => self.job.schedule().next_after(last_run)?,
+            None => self.job.schedule().first_run(now)?,
+        };
+        let jitter = self.job.jitter().as_millis() as i64;
+        if jitter == 0 {
+            return Some(next_run);
+        }
+        let jitter = chrono::Duration::try_milliseconds(rand::thread_rng().gen_range(0..=jitter))?;
+        Some(next_run + jitter)
+    }
+}
+
+pub(crate) enum TriggerOutcome {
+    Triggered,
+    AlreadyRunning,
+}
+
+pub(crate) struct SchedulerHandle;
+impl TypeMapKey for SchedulerHandle {
+    type Value = Arc<Scheduler>;
+}
+
+/// Runs [`Job`]s on interval or cron schedules until shutdown.
+///
+/// A run is skipped while the previous run of the same job has not finished. Failed runs are
+/// retried with [`Backoff`].
+pub(crate) struct Scheduler {
+    db_handle: Database,
+    jobs: Vec<Arc<ScheduledJob>>,
+}
+
+impl Scheduler {
+    pub(crate) fn new(db_handle: Database) -> Self {
+        Self {
+            db_handle,
+            jobs: Vec::new(),
+        }
+    }
+
+    pub(crate) fn register(&mut self, job: impl Job + 'static) -> &mut Self {
+        let name = job.name();
+        if self.get(name).is_some() {
+            warn!("Job {} registered twice, replacing previous one", name);
+            self.jobs.retain(|scheduled| scheduled.job.name() != name);
+        }
+        info!("Job registered: {}", name);
+        self.jobs.push(Arc::new(ScheduledJob {
+            job: Box::new(job),
+            state: Mutex::new(JobState::default()),
+            running: AtomicBool::new(false),
+            trigger: Notify::new(),
+        }));
+        self
+    }
+
+    fn get(&self, name: &str) -> Option<&Arc<ScheduledJob>> {
+        self.jobs
+            .iter()
+            .find(|scheduled| scheduled.job.name() == name)
+    }
+
+    pub(crate) fn job_names(&self) -> Vec<&'static str> {
+        self.jobs
+            .iter()
+            .map(|scheduled| scheduled.job.name())
+            .collect()
+    }
+
+    fn get_collection(&self) -> Collection<JobRecord> {
+        self.db_handle.collection(SCHEDULED_JOBS_COLLECTION)
+    }
+
+    /// Spawns a task per job that waits for its runs until shutdown.
+    pub(crate) fn start(self: &Arc<Self>, ctx: BotContext, shutdown: &Shutdown) {
+        for scheduled in self.jobs.iter() {
+            let scheduler = self.clone();
+            let scheduled = scheduled.clone();
+            let ctx = ctx.clone();
+            let job_shutdown = shutdown.clone();
+            let span = info_span!("job", job = scheduled.job.name());
+            shutdown.spawn(
+                async move { scheduler.run_loop(scheduled, ctx, job_shutdown).await }
+                    .instrument(span),
+            );
+        }
+    }
+
+    /// Runs the job as soon as possible, unless a run is already in progress.
+    pub(crate) fn trigger(&self, name: &str) -> Option<TriggerOutcome> {
+        let scheduled = self.get(name)?;
+        if scheduled.running.load(Ordering::SeqCst) {
+            return Some(TriggerOutcome::AlreadyRunning);
+        }
+        scheduled.trigger.notify_one();
+        Some(TriggerOutcome::Triggered)
+    }
+
+    async fn run_loop(&self, scheduled: Arc<ScheduledJob>, ctx: BotContext, shutdown: Shutdown) {
+        if scheduled.job.persistent() {
+            match self.load(scheduled.job.name()).await {
+                Ok(Some(record)) => {
+                    *scheduled.state.lock().expect("Job state lock poisoned") = record.into()
+                }
+                Ok(None) => {}
+                Err(e) => error!("Failed to load job state: {:#?}", e),
+            }
+        }
+        loop {
+            let now = Utc::now();
+            let next_run = {
+                let mut state = scheduled.state.lock().expect("Job state lock poisoned");
+                state.next_run = scheduled.next_run(&state, now);
+                state.next_run
+            };
+            let Some(next_run) = next_run else {
+                warn!("Job has no upcoming runs");
+                return;
+            };
+            let delay = (next_run - now).to_std().unwrap_or(Duration::ZERO);
+            tokio::select! {
+                _ = shutdown.cancelled() => return,
+                _ = tokio::time::sleep(delay) => {}
+                _ = scheduled.trigger.notified() => info!("Job triggered"),
+            }
+            if shutdown.is_cancelled() {
+                return;
+            }
+            self.run_once(&scheduled, &ctx).await;
+        }
+    }
+
+    async fn run_once(&self, scheduled: &ScheduledJob, ctx: &BotContext) {
+        if scheduled.running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let job = &scheduled.job;
+        let started = Utc::now();
+        // A run that cannot take the lock counts as failed, so it is retried with backoff
+        let locked = if job.persistent() {
+            match self.lock(job.as_ref(), started).await {
+                Ok(true) => Ok(()),
+                Ok(false) => Err(anyhow!("Previous run has not finished")),
+                Err(e) => Err(e.context("Failed to lock job")),
+            }
+        } else {
+            Ok(())
+        };
+        let holds_lock = job.persistent() && locked.is_ok();
+        let result = match locked {
+            Ok(()) => {
+                debug!("Running job");
+                match timeout(job.timeout(), job.run(ctx)).await {
+                    Ok(result) => result,
+                    Err(_) => Err(anyhow!("Timed out after {:?}", job.timeout())),
+                }
+            }
+            Err(e) => Err(e),
+        };
+        let duration = (Utc::now() - started).to_std().unwrap_or(Duration::ZERO);
+        let outcome = match result.as_ref() {
+            Ok(_) => "ok",
+            Err(_) => "error",
+        };
+        if let Ok(metrics) = retrieve_metrics(ctx.data.clone()).await {
+            metrics.job_run(job.name(), outcome);
+        }
+        let state = {
+            let mut state = scheduled.state.lock().expect("Job state lock poisoned");
+            state.last_run = Some(started);
+            state.last_duration = Some(duration);
+            match result {
+                Ok(_) => {
+                    debug!("Job finished in {:?}", duration);
+                    state.last_error = None;
+                    state.failures = 0;
+                }
+                Err(e) => {
+                    state.failures += 1;
+                    error!("Job failed {} times in a row: {:#?}", state.failures, e);
+                    state.last_error = Some(format!("{:#}", e));
+                }
+            }
+            state.clone()
+        };
+        if holds_lock {
+            if let Err(e) = self.save(job.name(), &state).await {
+                error!("Failed to save job state: {:#?}", e);
+            }
+        }
+        scheduled.running.store(false, Ordering::SeqCst);
+    }
+
+    async fn load(&self, name: &str) -> Result<Option<JobRecord>> {
+        Ok(self
+            .get_collection()
+            .find_one(doc! {"_id": name}, None)
+            .await?)
+    }
+
+    /// Takes the lock of the job for as long as a run may take, returns false when it is held.
+    async fn lock(&self, job: &dyn Job, now: DateTime<Utc>) -> Result<bool> {
+        let collection = self.get_collection();
+        collection
+            .update_one(
+                doc! {"_id": job.name()},
+                doc! {"$setOnInsert": {"failures": 0}},
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        let locked_until = now.timestamp() + job.timeout().as_secs() as i64;
+        let locked = collection
+            .find_one_and_update(
+                doc! {
+                    "_id": job.name(),
+                    "$or": [
+                        {"locked_until": null},
+                        {"locked_until": {"$lte": now.timestamp()}},
+                    ],
+                },
+                doc! {"$set": {"locked_until": locked_until}},
+                FindOneAndUpdateOptions::builder()
+                    .return_document(ReturnDocument::After)
+                    .build(),
+            )
+            .await?;
+        Ok(locked.is_some())
+    }
+
+    /// Stores the finished run and releases the lock.
+    async fn save(&self, name: &str, state: &JobState) -> Result<()> {
+        self.get_collection()
+            .update_one(
+                doc! {"_id": name},
+                doc! {"$set": {
+                    "last_run": state.last_run.map(|last_run| last_run.timestamp()),
+                    "last_duration_ms": state
+                        .last_duration
+                        .map(|duration| duration.as_millis() as i64),
+                    "last_error": state.last_error.clone(),
+                    "failures": state.failures,
+                    "locked_until": null,
+                }},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(crate) fn render(&self, locale: Locale) -> String {
+        let mut builder = MessageBuilder::new();
+        if self.jobs.is_empty() {
+            builder.push(t!(locale, "jobs.no_jobs"));
+        }
+        for scheduled in self.jobs.iter() {
+            let state = scheduled.state();
+            builder
+                .push_bold(scheduled.job.name())
+                .push(" - ")
+                .push_line(scheduled.job.schedule().describe(locale));
+            if scheduled.running.load(Ordering::SeqCst) {
+                builder.push_quote_line(t!(locale, "jobs.running"));
+            }
+            match state.last_run {
+                Some(last_run) => {
+                    let duration = state.last_duration.unwrap_or_default();
+                    builder.push_quote_line(t!(
+                        locale,
+                        "jobs.last_run",
+                        timestamp = last_run.timestamp(),
+                        duration = format_duration(duration)
+                    ))
+                }
+                None => builder.push_quote_line(t!(locale, "jobs.never_run")),
+            };
+            if let Some(error) = state.last_error.as_deref() {
+                builder.push_quote_line(t!(
+                    locale,
+                    "jobs.failed",
+                    failures = state.failures,
+                    error = truncate_chars(error, 200)
+                ));
+            }
+            if let Some(next_run) = state.next_run {
+                builder.push_quote_line(t!(
+                    locale,
+                    "jobs.next_run",
+                    timestamp = next_run.timestamp()
+                ));
+            }
+        }
+        builder.build()
+    }
+}
+
+pub(crate) fn register_commands(registry: &mut CommandRegistry) {
+    registry.register(
+        CommandGroup::new("jobs", "Scheduled background jobs")
+            .permissions(CommandPermissions {
+                owner_only: true,
+                ..Default::default()
+            })
+            .subcommand(JobsListCommand)
+            .subcommand(JobsRunCommand),
+    );
+}
+
+command_options! {
+    struct RunOptions {
+        job: String => (JOB_OPTION, "Job to run").autocomplete(),
+    }
+}
+
+pub(crate) struct JobsListCommand;
+impl MakeCommandResponse for JobsListCommand {}
+
+#[async_trait]
+impl CommandRunner for JobsListCommand {
+    fn name(&self) -> &'static str {
+        "list"
+    }
+
+    fn description(&self) -> &'static str {
+        "List jobs with their last and next runs"
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        _command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let scheduler = retrieve_scheduler(ctx.data.clone()).await?;
+        Ok(self.make_response(scheduler.render(ctx.locale), true))
+    }
+}
+
+pub(crate) struct JobsRunCommand;
+impl MakeCommandResponse for JobsRunCommand {}
+
+#[async_trait]
+impl CommandRunner for JobsRunCommand {
+    fn name(&self) -> &'static str {
+        "run"
+    }
+
+    fn description(&self) -> &'static str {
+        "Run a job now"
+    }
+
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        RunOptions::create_options(key)
+    }
+
+    async fn autocomplete(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> Result<Vec<AutocompleteChoice>> {
+        let input = match command.data.autocomplete() {
+            Some(option) if option.name == JOB_OPTION => option.value.to_lowercase(),
+            _ => return Ok(Vec::new()),
+        };
+        Ok(retrieve_scheduler(ctx.data.clone())
+            .await?
+            .job_names()
+            .into_iter()
+            .filter(|name| name.contains(input.trim()))
+            .map(|name| AutocompleteChoice::new(name, name))
+            .collect())
+    }
+
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let options = RunOptions::parse(command, ctx.locale)?;
+        let scheduler = retrieve_scheduler(ctx.data.clone()).await?;
+        let content = match scheduler.trigger(options.job.trim()) {
+            Some(TriggerOutcome::Triggered) => t!(ctx.locale, "jobs.triggered", job = options.job),
+            Some(TriggerOutcome::AlreadyRunning) => {
+                t!(ctx.locale, "jobs.already_running", job = options.job)
+            }
+            None => {
+                return Err(BotError::not_found(t!(
+                    ctx.locale,
+                    "jobs.unknown_job",
+                    job = options.job
+                )))
+            }
+        };
+        Ok(self.make_response(content, true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{InteractionBuilder, TestHarness};
+
+    #[test]
+    fn computes_next_runs() {
+        let last_run = Utc.with_ymd_and_hms(2023, 12, 1, 6, 0, 30).unwrap();
+        assert_eq!(
+            Schedule::every(Duration::from_secs(15 * 60)).next_after(last_run),
+            Utc.with_ymd_and_hms(2023, 12, 1, 6, 15, 30).single()
+        );
+        let schedule = Schedule::cron("0 0 6 * 12 *").unwrap();
+        assert_eq!(
+            schedule.next_after(last_run),
+            Utc.with_ymd_and_hms(2023, 12, 2, 6, 0, 0).single()
+        );
+        assert_eq!(
+            schedule.next_after(Utc.with_ymd_and_hms(2023, 12, 31, 7, 0, 0).unwrap()),
+            Utc.with_ymd_and_hms(2024, 12, 1, 6, 0, 0).single()
+        );
+        assert!(Schedule::cron("every day").is_err());
+    }
+
+    #[test]
+    fn describes_schedules_in_the_locale() {
+        let interval = Schedule::every(Duration::from_secs(15 * 60));
+        assert_eq!(interval.describe(Locale::En), "every 15m");
+        assert_eq!(interval.describe(Locale::Hr), "svakih 15m");
+        assert_eq!(
+            Schedule::cron("0 0 4 * * *").unwrap().describe(Locale::Hr),
+            "cron `0 0 4 * * *`"
+        );
+    }
+
+    #[test]
+    fn backs_off_exponentially() {
+        let backoff = Backoff {
+            initial: Duration::from_secs(30),
+            max: Duration::from_secs(300),
+        };
+        assert_eq!(backoff.delay(1), Duration::from_secs(30));
+        assert_eq!(backoff.delay(3), Duration::from_secs(120));
+        assert_eq!(backoff.delay(5), Duration::from_secs(300));
+        assert_eq!(backoff.delay(u32::MAX), Duration::from_secs(300));
+    }
+
+    #[tokio::test]
+    async fn lists_and_triggers_jobs() {
+        let harness = TestHarness::new().await;
+        let command = InteractionBuilder::new("jobs").subcommand("list").build();
+        let content = harness.dispatch(&command).await.unwrap().content;
+        assert!(
+            content.contains("**cooldown_cleanup** - cron `0 0 4 * * *`\n> Never run"),
+            "{}",
+            content
+        );
+        let command = InteractionBuilder::new("jobs")
+            .subcommand("run")
+            .option("job", "nope")
+            .build();
+        let err = harness.dispatch(&command).await.err().unwrap();
+        assert!(matches!(err, BotError::NotFound(_)), "{}", err);
+    }
+}
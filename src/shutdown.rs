@@ -0,0 +1,81 @@
+use std::{future::Future, time::Duration};
+
+use anyhow::Result;
+use serenity::prelude::TypeMapKey;
+use tokio::{signal, time::timeout};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+use tracing::{info, warn};
+
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub(crate) struct ShutdownHandle;
+impl TypeMapKey for ShutdownHandle {
+    type Value = Shutdown;
+}
+
+/// Signals background work to stop and keeps track of it so `main` can wait for it before the
+/// runtime is dropped.
+#[derive(Clone, Default)]
+pub(crate) struct Shutdown {
+    token: CancellationToken,
+    tracker: TaskTracker,
+}
+
+impl Shutdown {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawns a task that is waited for on shutdown. It has to finish soon after
+    /// [`Self::cancelled`] resolves.
+    pub(crate) fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tracker.spawn(task);
+    }
+
+    pub(crate) async fn cancelled(&self) {
+        self.token.cancelled().await
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    pub(crate) fn trigger(&self) {
+        self.token.cancel();
+    }
+
+    /// Waits for every spawned task, tasks still running after [`SHUTDOWN_TIMEOUT`] are dropped
+    /// with the runtime.
+    pub(crate) async fn wait_for_tasks(&self) {
+        self.tracker.close();
+        info!("Waiting for {} background tasks", self.tracker.len());
+        if timeout(SHUTDOWN_TIMEOUT, self.tracker.wait())
+            .await
+            .is_err()
+        {
+            warn!(
+                "{} background tasks did not finish in {:?}",
+                self.tracker.len(),
+                SHUTDOWN_TIMEOUT
+            );
+        }
+    }
+}
+
+/// Resolves on Ctrl-C, or SIGTERM on unix.
+pub(crate) async fn wait_for_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        let mut terminate = signal::unix::signal(signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            result = signal::ctrl_c() => result?,
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    signal::ctrl_c().await?;
+    Ok(())
+}
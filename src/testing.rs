@@ -0,0 +1,279 @@
+//! Runs commands without a Discord or database connection.
+
+use std::sync::Arc;
+
+use mongodb::options::ClientOptions;
+use serde_json::{json, Value};
+use serenity::{
+    all::{Cache, CommandInteraction, CommandOptionType, Http},
+    prelude::{RwLock, TypeMap},
+};
+
+use crate::{
+    build_scheduler,
+    config::BotConfig,
+    context::BotContext,
+    error::BotResult,
+    insert_data,
+    metrics::Metrics,
+    modules::Modules,
+    shutdown::Shutdown,
+    util::{retrieve_command_registry, CommandRunner},
+    CommandResponse,
+};
+
+pub(crate) const TEST_GUILD_ID: u64 = 100;
+pub(crate) const TEST_CHANNEL_ID: u64 = 200;
+pub(crate) const TEST_USER_ID: u64 = 300;
+
+// The client connects lazily, so nothing is contacted unless a test queries the database
+const OFFLINE_MONGO_URL: &str = "mongodb://localhost:27017";
+const TEST_DATABASE: &str = "papa_klement_test";
+
+/// Builds a [`CommandInteraction`] as Discord would send it for a slash command.
+pub(crate) struct InteractionBuilder {
+    name: String,
+    subcommands: Vec<String>,
+    options: Vec<Value>,
+    guild_id: Option<u64>,
+    user_id: u64,
+    roles: Vec<u64>,
+}
+
+impl InteractionBuilder {
+    pub(crate) fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            subcommands: Vec::new(),
+            options: Vec::new(),
+            guild_id: Some(TEST_GUILD_ID),
+            user_id: TEST_USER_ID,
+            roles: Vec::new(),
+        }
+    }
+
+    /// Invokes a subcommand, call it twice for a subcommand inside a group.
+    pub(crate) fn subcommand(mut self, name: &str) -> Self {
+        self.subcommands.push(name.to_string());
+        self
+    }
+
+    /// Adds an option, the type is derived from the JSON value.
+    pub(crate) fn option(self, name: &str, value: impl Into<Value>) -> Self {
+        let value = value.into();
+        let kind = match &value {
+            Value::Bool(_) => CommandOptionType::Boolean,
+            Value::Number(number) if number.is_f64() => CommandOptionType::Number,
+            Value::Number(_) => CommandOptionType::Integer,
+            _ => CommandOptionType::String,
+        };
+        self.typed_option(name, kind, value)
+    }
+
+    /// Adds an option whose type cannot be derived, e.g. a user or a role.
+    pub(crate) fn typed_option(
+        mut self,
+        name: &str,
+        kind: CommandOptionType,
+        value: impl Into<Value>,
+    ) -> Self {
+        let value = match value.into() {
+            // Snowflakes are sent as strings
+            Value::Number(id) if is_snowflake(kind) => Value::String(id.to_string()),
+            value => value,
+        };
+        self.options.push(json!({
+            "name": name,
+            "type": u8::from(kind),
+            "value": value,
+        }));
+        self
+    }
+
+    #[cfg(any(feature = "aoc", feature = "bans"))]
+    pub(crate) fn without_guild(mut self) -> Self {
+        self.guild_id = None;
+        self
+    }
+
+    pub(crate) fn user(mut self, user_id: u64) -> Self {
+        self.user_id = user_id;
+        self
+    }
+
+    pub(crate) fn role(mut self, role_id: u64) -> Self {
+        self.roles.push(role_id);
+        self
+    }
+
+    pub(crate) fn build(self) -> CommandInteraction {
+        let options = self.subcommands.iter().enumerate().rev().fold(
+            self.options,
+            |options, (depth, name)| {
+                // Only the innermost entry is a subcommand, everything above it is a group
+                let kind = if depth + 1 == self.subcommands.len() {
+                    CommandOptionType::SubCommand
+                } else {
+                    CommandOptionType::SubCommandGroup
+                };
+                vec![json!({
+                    "name": name,
+                    "type": u8::from(kind),
+                    "options": options,
+                })]
+            },
+        );
+        let user = json!({
+            "id": self.user_id.to_string(),
+            "username": "tester",
+            "discriminator": "0",
+            "global_name": null,
+            "avatar": null,
+        });
+        let mut interaction = json!({
+            "id": "1",
+            "application_id": "2",
+            "type": 2,
+            "data": {
+                "id": "3",
+                "name": self.name,
+                "type": 1,
+                "options": options,
+            },
+            "channel_id": TEST_CHANNEL_ID.to_string(),
+            "user": user,
+            "token": "token",
+            "version": 1,
+            "app_permissions": null,
+            "locale": "en-US",
+            "guild_locale": null,
+            "entitlements": [],
+        });
+        if let Some(guild_id) = self.guild_id {
+            interaction["guild_id"] = json!(guild_id.to_string());
+            interaction["member"] = json!({
+                "user": user,
+                "roles": self.roles.iter().map(u64::to_string).collect::<Vec<String>>(),
+                "joined_at": "2020-01-01T00:00:00.000000+00:00",
+                "deaf": false,
+                "mute": false,
+                "flags": 0,
+                "permissions": "0",
+            });
+        }
+        serde_json::from_value(interaction).expect("Test interaction must deserialize")
+    }
+}
+
+fn is_snowflake(kind: CommandOptionType) -> bool {
+    matches!(
+        kind,
+        CommandOptionType::User
+            | CommandOptionType::Role
+            | CommandOptionType::Channel
+            | CommandOptionType::Mentionable
+    )
+}
+
+/// Bot data as set up in `main`, with a detached context and a database that is never connected
+/// to unless a test queries it.
+pub(crate) struct TestHarness {
+    ctx: BotContext,
+}
+
+impl TestHarness {
+    pub(crate) async fn new() -> Self {
+        Self::with_config(BotConfig::test()).await
+    }
+
+    pub(crate) async fn with_config(config: BotConfig) -> Self {
+        let options = ClientOptions::parse(OFFLINE_MONGO_URL)
+            .await
+            .expect("Test database URL must parse");
+        let database = mongodb::Client::with_options(options)
+            .expect("Test database client")
+            .database(TEST_DATABASE);
+        let modules = Arc::new(Modules::new(&config));
+        let scheduler = Arc::new(build_scheduler(&modules, database.clone()));
+        let mut data = TypeMap::new();
+        insert_data(
+            &mut data,
+            Arc::new(config),
+            database,
+            modules,
+            scheduler,
+            Arc::new(Metrics::new()),
+            Shutdown::new(),
+        )
+        .expect("Test modules must initialize");
+        Self {
+            ctx: BotContext::detached(
+                Arc::new(RwLock::new(data)),
+                Arc::new(Http::new("")),
+                Arc::new(Cache::new()),
+            ),
+        }
+    }
+
+    #[cfg(all(feature = "aoc", feature = "bans"))]
+    pub(crate) fn ctx(&self) -> &BotContext {
+        &self.ctx
+    }
+
+    pub(crate) async fn run(
+        &self,
+        runner: &dyn CommandRunner,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        runner.run(&self.ctx, command).await
+    }
+
+    /// Runs the command the registry resolves the interaction to. Unlike `MrHandler` it skips
+    /// the permission, module and cooldown checks, those need a database.
+    pub(crate) async fn dispatch(
+        &self,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse> {
+        let runner = retrieve_command_registry(self.ctx.data.clone())
+            .await?
+            .resolve(command)
+            .unwrap_or_else(|| panic!("Unknown command: {}", command.data.name));
+        self.run(runner.as_ref(), command).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_nested_subcommand_options() {
+        let command = InteractionBuilder::new("aoc")
+            .subcommand("speedrun")
+            .option("day", 3)
+            .build();
+        assert_eq!(command.guild_id.map(|id| id.get()), Some(TEST_GUILD_ID));
+        assert_eq!(command.user.id.get(), TEST_USER_ID);
+        assert_eq!(
+            crate::util::command_options(&command)[0].value.as_i64(),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn builds_snowflake_options() {
+        let command = InteractionBuilder::new("permissions")
+            .subcommand("allow")
+            .typed_option("user", CommandOptionType::User, 42)
+            .user(8)
+            .role(7)
+            .build();
+        let options = crate::util::command_options(&command);
+        assert_eq!(options[0].value.as_user_id().map(|id| id.get()), Some(42));
+        assert_eq!(command.user.id.get(), 8);
+        assert_eq!(
+            command.member.as_ref().map(|member| member.roles.len()),
+            Some(1)
+        );
+    }
+}
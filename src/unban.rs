@@ -1,12 +1,18 @@
-use crate::{event_handlers::mr_handler::MrHandler, util::retrieve_db_handle, UNDERSCOREBANS};
+use crate::{
+    database::BanRecordUser,
+    guild_config::GuildModule,
+    modules::Module,
+    util::{retrieve_db_handle, retrieve_guild_config_service},
+    UNDERSCOREBANS,
+};
 
 use anyhow::Result;
 use chrono::{DateTime, Utc};
-use log::{error, info};
 use mongodb::{bson::doc, IndexModel};
 use serde::{Deserialize, Serialize};
 use serenity::{
-    all::{ChannelType, CreateInvite, CreateMessage},
+    all::{ChannelType, CreateInvite, CreateMessage, GatewayIntents},
+    async_trait,
     model::{
         guild::audit_log::Action,
         prelude::{GuildId, MemberAction},
@@ -14,9 +20,24 @@ use serenity::{
     },
     prelude::Context,
 };
+use tracing::{error, info};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub(crate) struct BanRecordUser(pub(crate) i64);
+#[serde(default)]
+pub(crate) struct UnbanConfig {
+    /// Lifts every ban as soon as it is issued, bans are recorded either way.
+    pub(crate) enabled: bool,
+    pub(crate) send_invite: bool,
+}
+
+impl Default for UnbanConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            send_invite: true,
+        }
+    }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub(crate) struct BanRecord {
@@ -26,7 +47,30 @@ pub(crate) struct BanRecord {
     pub(crate) timestamp: DateTime<Utc>,
 }
 
-impl MrHandler {
+/// Records every ban for `/bantop` and lifts it again in guilds with the unban module enabled.
+pub(crate) struct UnbanModule;
+
+#[async_trait]
+impl Module for UnbanModule {
+    fn name(&self) -> &'static str {
+        "unban"
+    }
+
+    fn intents(&self) -> GatewayIntents {
+        GatewayIntents::GUILD_MODERATION
+    }
+
+    async fn guild_ban_addition(
+        &self,
+        ctx: &Context,
+        guild_id: GuildId,
+        banned_user: &User,
+    ) -> Result<()> {
+        self.unban(ctx, &guild_id, banned_user).await
+    }
+}
+
+impl UnbanModule {
     async fn record_ban(&self, ctx: &Context, guild: &GuildId, banned_user: &User) -> Result<()> {
         let ban_logs = guild
             .audit_logs(
@@ -77,17 +121,22 @@ impl MrHandler {
         Ok(())
     }
 
-    pub(crate) async fn unban(
-        &self,
-        ctx: &Context,
-        guild: &GuildId,
-        banned_user: &User,
-    ) -> Result<()> {
+    async fn unban(&self, ctx: &Context, guild: &GuildId, banned_user: &User) -> Result<()> {
+        let config_service = retrieve_guild_config_service(ctx.data.clone()).await?;
+        if !config_service
+            .is_enabled(*guild, GuildModule::Unban)
+            .await?
+        {
+            return self.record_ban(ctx, guild, banned_user).await;
+        }
         guild.unban(&ctx.http, banned_user.id).await?;
         info!("Unbanned {}", banned_user.id);
         if let Err(e) = self.record_ban(ctx, guild, banned_user).await {
             error!("Record ban error: {:#?}", e);
         }
+        if !config_service.get(*guild).await?.unban.send_invite {
+            return Ok(());
+        }
         let mut invite = None;
         for (channel_id, channel) in guild.channels(&ctx.http).await?.iter() {
             if channel.kind == ChannelType::Text {
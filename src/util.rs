@@ -1,17 +1,31 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use anyhow::Result;
 use mongodb::Database;
 use serenity::{
     all::{
-        CommandInteraction, CreateCommand, CreateInteractionResponse,
-        CreateInteractionResponseMessage,
+        AutocompleteChoice, CommandDataOption, CommandDataOptionValue, CommandInteraction,
+        CommandOptionType, CommandType, CreateCommand, CreateCommandOption,
+        CreateInteractionResponse, CreateInteractionResponseMessage,
     },
     async_trait,
-    prelude::{Context, RwLock, TypeMap},
+    prelude::{RwLock, TypeMap},
 };
 
-use crate::{music::SaveHandler, CommandResponse, MongoDatabaseHandle, SaveHandlerHandle};
+use crate::{
+    commands::registry::{CommandRegistry, CommandRegistryHandle},
+    config::{BotConfig, BotConfigHandle},
+    context::BotContext,
+    cooldowns::{Cooldown, CooldownService, CooldownServiceHandle},
+    error::BotResult,
+    guild_config::{GuildConfigService, GuildConfigServiceHandle, GuildModule},
+    i18n::{command_key, localize_command, localize_option},
+    metrics::{Metrics, MetricsHandle},
+    modules::{Modules, ModulesHandle},
+    permissions::CommandPermissions,
+    scheduler::{Scheduler, SchedulerHandle},
+    CommandResponse, MongoDatabaseHandle,
+};
 
 pub(crate) async fn retrieve_db_handle(data: Arc<RwLock<TypeMap>>) -> Result<Database> {
     let database_handle = {
@@ -24,28 +38,182 @@ pub(crate) async fn retrieve_db_handle(data: Arc<RwLock<TypeMap>>) -> Result<Dat
     Ok(database_handle)
 }
 
-pub(crate) async fn retrieve_save_handler(data: Arc<RwLock<TypeMap>>) -> Result<Arc<SaveHandler>> {
+#[cfg(feature = "music")]
+pub(crate) async fn retrieve_save_handler(
+    data: Arc<RwLock<TypeMap>>,
+) -> Result<Arc<crate::music::SaveHandler>> {
     Ok(data
         .read()
         .await
-        .get::<SaveHandlerHandle>()
+        .get::<crate::music::SaveHandlerHandle>()
         .ok_or_else(|| anyhow::anyhow!("Failed to retrieve SaveHandlerHandle from data"))?
         .clone())
 }
 
-pub(crate) async fn defer_response(ctx: &Context, command: &CommandInteraction) -> Result<()> {
-    Ok(command
+pub(crate) async fn retrieve_command_registry(
+    data: Arc<RwLock<TypeMap>>,
+) -> Result<Arc<CommandRegistry>> {
+    Ok(data
+        .read()
+        .await
+        .get::<CommandRegistryHandle>()
+        .ok_or_else(|| anyhow::anyhow!("Failed to retrieve CommandRegistryHandle from data"))?
+        .clone())
+}
+
+pub(crate) async fn retrieve_cooldown_service(
+    data: Arc<RwLock<TypeMap>>,
+) -> Result<Arc<CooldownService>> {
+    Ok(data
+        .read()
+        .await
+        .get::<CooldownServiceHandle>()
+        .ok_or_else(|| anyhow::anyhow!("Failed to retrieve CooldownServiceHandle from data"))?
+        .clone())
+}
+
+pub(crate) async fn retrieve_bot_config(data: Arc<RwLock<TypeMap>>) -> Result<Arc<BotConfig>> {
+    Ok(data
+        .read()
+        .await
+        .get::<BotConfigHandle>()
+        .ok_or_else(|| anyhow::anyhow!("Failed to retrieve BotConfigHandle from data"))?
+        .clone())
+}
+
+pub(crate) async fn retrieve_metrics(data: Arc<RwLock<TypeMap>>) -> Result<Arc<Metrics>> {
+    Ok(data
+        .read()
+        .await
+        .get::<MetricsHandle>()
+        .ok_or_else(|| anyhow::anyhow!("Failed to retrieve MetricsHandle from data"))?
+        .clone())
+}
+
+pub(crate) async fn retrieve_guild_config_service(
+    data: Arc<RwLock<TypeMap>>,
+) -> Result<Arc<GuildConfigService>> {
+    Ok(data
+        .read()
+        .await
+        .get::<GuildConfigServiceHandle>()
+        .ok_or_else(|| anyhow::anyhow!("Failed to retrieve GuildConfigServiceHandle from data"))?
+        .clone())
+}
+
+pub(crate) async fn retrieve_scheduler(data: Arc<RwLock<TypeMap>>) -> Result<Arc<Scheduler>> {
+    Ok(data
+        .read()
+        .await
+        .get::<SchedulerHandle>()
+        .ok_or_else(|| anyhow::anyhow!("Failed to retrieve SchedulerHandle from data"))?
+        .clone())
+}
+
+pub(crate) async fn retrieve_modules(data: Arc<RwLock<TypeMap>>) -> Result<Arc<Modules>> {
+    Ok(data
+        .read()
+        .await
+        .get::<ModulesHandle>()
+        .ok_or_else(|| anyhow::anyhow!("Failed to retrieve ModulesHandle from data"))?
+        .clone())
+}
+
+pub(crate) async fn defer_response(ctx: &BotContext, command: &CommandInteraction) -> Result<()> {
+    command
         .create_response(
             &ctx.http,
             CreateInteractionResponse::Defer(CreateInteractionResponseMessage::new()),
         )
-        .await?)
+        .await?;
+    ctx.mark_deferred();
+    Ok(())
+}
+
+/// Returns the options of the invoked leaf subcommand, or the top level options if the command
+/// has no subcommands.
+pub(crate) fn command_options(command: &CommandInteraction) -> &[CommandDataOption] {
+    let mut options = command.data.options.as_slice();
+    while let Some(
+        CommandDataOptionValue::SubCommand(nested)
+        | CommandDataOptionValue::SubCommandGroup(nested),
+    ) = options.first().map(|option| &option.value)
+    {
+        options = nested;
+    }
+    options
 }
 
 #[async_trait()]
-pub(crate) trait CommandRunner: Send {
-    async fn run(&self, ctx: &Context, command: &CommandInteraction) -> Result<CommandResponse>;
-    fn register(&self) -> CreateCommand;
+pub(crate) trait CommandRunner: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    /// Slash command by default, user and message commands show up in the context menu and
+    /// only have a name.
+    fn kind(&self) -> CommandType {
+        CommandType::ChatInput
+    }
+    async fn run(
+        &self,
+        ctx: &BotContext,
+        command: &CommandInteraction,
+    ) -> BotResult<CommandResponse>;
+    /// Answers an autocomplete interaction for the focused option, see
+    /// [`CommandInteraction::autocomplete`].
+    async fn autocomplete(
+        &self,
+        _ctx: &BotContext,
+        _command: &CommandInteraction,
+    ) -> Result<Vec<AutocompleteChoice>> {
+        Ok(Vec::new())
+    }
+    /// `key` is the catalog key of the command, used to localize the options.
+    fn options(&self, key: &str) -> Vec<CreateCommandOption> {
+        self.subcommands()
+            .iter()
+            .map(|subcommand| subcommand.register_as_option(key))
+            .collect()
+    }
+    fn subcommands(&self) -> &[Arc<dyn CommandRunner>] {
+        &[]
+    }
+    fn permissions(&self) -> CommandPermissions {
+        CommandPermissions::default()
+    }
+    fn cooldowns(&self) -> Vec<Cooldown> {
+        Vec::new()
+    }
+    /// Module the command belongs to, the command is rejected in guilds that disabled it.
+    fn module(&self) -> Option<GuildModule> {
+        None
+    }
+    fn register(&self) -> CreateCommand {
+        let key = command_key(self.name());
+        let command = match self.kind() {
+            CommandType::ChatInput => CreateCommand::new(self.name())
+                .description(self.description())
+                .set_options(self.options(&key)),
+            kind => CreateCommand::new(self.name()).kind(kind),
+        };
+        let command = localize_command(command, &key, self.kind()).dm_permission(false);
+        match self.permissions().default_member_permissions {
+            Some(permissions) => command.default_member_permissions(permissions),
+            None => command,
+        }
+    }
+    fn register_as_option(&self, parent_key: &str) -> CreateCommandOption {
+        let key = format!("{}.{}", parent_key, command_key(self.name()));
+        let kind = if self.subcommands().is_empty() {
+            CommandOptionType::SubCommand
+        } else {
+            CommandOptionType::SubCommandGroup
+        };
+        let option = self.options(&key).into_iter().fold(
+            CreateCommandOption::new(kind, self.name(), self.description()),
+            |option, sub_option| option.add_sub_option(sub_option),
+        );
+        localize_option(option, &key)
+    }
     fn has_deferred_response(&self) -> bool {
         false
     }
@@ -59,3 +227,91 @@ where
         CommandResponse::new(content.into(), ephemeral, self.has_deferred_response())
     }
 }
+
+/// Shortens `value` to at most `max_chars` characters without splitting a character.
+pub(crate) fn truncate_chars(value: &str, max_chars: usize) -> String {
+    value.chars().take(max_chars).collect()
+}
+
+/// Splits `content` into chunks of at most `max_chars` characters, breaking on line boundaries
+/// and only splitting a line when it does not fit into a chunk on its own.
+pub(crate) fn split_message(content: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+    for line in content.split_inclusive('\n') {
+        let line_chars = line.chars().count();
+        if current_chars + line_chars > max_chars && !current.is_empty() {
+            chunks.push(std::mem::take(&mut current));
+            current_chars = 0;
+        }
+        if line_chars > max_chars {
+            let mut chars = line.chars().peekable();
+            while chars.peek().is_some() {
+                let part: String = chars.by_ref().take(max_chars).collect();
+                if chars.peek().is_some() {
+                    chunks.push(part);
+                } else {
+                    current_chars = part.chars().count();
+                    current = part;
+                }
+            }
+        } else {
+            current.push_str(line);
+            current_chars += line_chars;
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Parses durations written like [`format_duration`] formats them, e.g. `1h 30m` or `2d`.
+pub(crate) fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim().to_lowercase();
+    if input.is_empty() {
+        return None;
+    }
+    let mut total = 0u64;
+    let mut number = String::new();
+    for c in input.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            ' ' if number.is_empty() => {}
+            unit => {
+                let seconds = match unit {
+                    's' => 1,
+                    'm' => 60,
+                    'h' => 60 * 60,
+                    'd' => 24 * 60 * 60,
+                    'w' => 7 * 24 * 60 * 60,
+                    _ => return None,
+                };
+                total = total.checked_add(number.parse::<u64>().ok()?.checked_mul(seconds)?)?;
+                number.clear();
+            }
+        }
+    }
+    if !number.is_empty() || total == 0 {
+        return None;
+    }
+    Some(Duration::from_secs(total))
+}
+
+/// Formats a duration for users, e.g. `1h 5m 3s`.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+    let mut parts = Vec::new();
+    if hours > 0 {
+        parts.push(format!("{}h", hours));
+    }
+    if minutes > 0 {
+        parts.push(format!("{}m", minutes));
+    }
+    if seconds > 0 || parts.is_empty() {
+        parts.push(format!("{}s", seconds));
+    }
+    parts.join(" ")
+}